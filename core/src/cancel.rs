@@ -0,0 +1,101 @@
+//! Cooperative cancellation shared by a command and the modules it drives.
+//!
+//! A [`Cancel`] is cloned into module options. Once [`Cancel::cancel`]led, modules stop starting
+//! new probes and let the ones in flight finish, but only until the drain deadline: work wrapped
+//! in [`Cancel::drain`] is abandoned when it passes, so results that were not decided in time are
+//! left out rather than reported.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How long probes in flight may take to finish after cancellation, unless configured.
+pub const DEFAULT_DRAIN: Duration = Duration::from_secs(3);
+
+#[derive(Debug)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+    drain: Duration,
+}
+
+/// Cancellation token; clones share their state.
+#[derive(Debug, Clone)]
+pub struct Cancel {
+    inner: Arc<Inner>,
+}
+
+impl Default for Cancel {
+    fn default() -> Self {
+        Cancel::new(DEFAULT_DRAIN)
+    }
+}
+
+impl Cancel {
+    /// A token whose in-flight work may run for `drain` after cancellation.
+    pub fn new(drain: Duration) -> Self {
+        Cancel { inner: Arc::new(Inner { cancelled: AtomicBool::new(false), notify: Notify::new(), drain }) }
+    }
+
+    pub fn cancel(&self) {
+        if !self.inner.cancelled.swap(true, Ordering::AcqRel) { self.inner.notify.notify_waiters(); }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::Acquire)
+    }
+
+    pub fn drain_time(&self) -> Duration {
+        self.inner.drain
+    }
+
+    /// Resolves once cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registered before the check so a cancel in between is not missed.
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() { return; }
+            notified.await;
+        }
+    }
+
+    /// Resolves once the drain deadline after cancellation has passed.
+    pub async fn expired(&self) {
+        self.cancelled().await;
+        tokio::time::sleep(self.inner.drain).await;
+    }
+
+    /// Run `fut` to completion, or `None` if the drain deadline passes first.
+    pub async fn drain<F: Future>(&self, fut: F) -> Option<F::Output> {
+        tokio::select! {
+            biased;
+            out = fut => Some(out),
+            _ = self.expired() => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drains_in_flight_work_up_to_the_deadline() {
+        let cancel = Cancel::new(Duration::from_millis(50));
+        assert_eq!(cancel.drain(async { 1 }).await, Some(1));
+        let waiter = tokio::spawn({
+            let c = cancel.clone();
+            async move { c.cancelled().await }
+        });
+        let (quick, slow) = (cancel.clone(), cancel.clone());
+        let quick = tokio::spawn(async move { quick.drain(tokio::time::sleep(Duration::from_millis(10))).await });
+        let slow = tokio::spawn(async move { slow.drain(tokio::time::sleep(Duration::from_secs(10))).await });
+        cancel.cancel();
+        assert!(cancel.is_cancelled());
+        waiter.await.unwrap();
+        assert_eq!(quick.await.unwrap(), Some(()));
+        assert_eq!(slow.await.unwrap(), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! AIMD congestion control for probe pacing.
+//!
+//! Probes report whether the target answered (connected or refused) or timed out. Every epoch
+//! (at least one window of probes) the controller either adds a fixed step to the rate and window
+//! or, if the timeout fraction exceeds the threshold, multiplies both down. The rate is pushed
+//! into the shared [`RateLimiter`]; modules size their concurrency from [`Aimd::window`].
+
+use crate::ratelimiter::RateLimiter;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy)]
+pub struct AimdConfig {
+    pub min_rate: f64,
+    pub max_rate: f64,
+    /// Added to the rate after a clean epoch.
+    pub rate_step: f64,
+    pub min_window: usize,
+    pub max_window: usize,
+    /// Added to the window after a clean epoch.
+    pub window_step: usize,
+    /// Timeout fraction within an epoch above which the controller backs off.
+    pub loss_threshold: f64,
+    /// Multiplier applied to rate and window on back-off.
+    pub decrease: f64,
+    /// Minimum probes per epoch.
+    pub epoch: u32,
+}
+
+impl AimdConfig {
+    /// Bounds around a starting point: rate in [start/10, start*10], window in [1, start*4].
+    pub fn around(start_rate: f64, start_window: usize) -> Self {
+        AimdConfig {
+            min_rate: (start_rate / 10.0).max(1.0),
+            max_rate: start_rate * 10.0,
+            rate_step: (start_rate / 10.0).max(1.0),
+            min_window: 1,
+            max_window: start_window.max(1) * 4,
+            window_step: 1,
+            loss_threshold: 0.1,
+            decrease: 0.5,
+            epoch: 32,
+        }
+    }
+}
+
+/// What a single probe attempt observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feedback {
+    /// The target answered promptly (connect, refusal).
+    Responded,
+    /// No answer within the timeout, or a network-level error.
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AimdSummary {
+    pub start_rate: f64,
+    pub final_rate: f64,
+    pub min_rate_seen: f64,
+    pub max_rate_seen: f64,
+    pub start_window: usize,
+    pub final_window: usize,
+    pub increases: u32,
+    pub decreases: u32,
+    pub responded: u64,
+    pub timeouts: u64,
+}
+
+struct State {
+    cfg: AimdConfig,
+    rate: f64,
+    window: f64,
+    epoch_responded: u32,
+    epoch_timeouts: u32,
+    summary: AimdSummary,
+}
+
+/// Shared AIMD controller; clones observe and drive the same state.
+#[derive(Clone)]
+pub struct Aimd {
+    state: Arc<Mutex<State>>,
+    limiter: Option<RateLimiter>,
+}
+
+impl Aimd {
+    /// Inverted bounds (such as a maximum below the default minimum) give way to the maximum.
+    pub fn new(mut cfg: AimdConfig, start_rate: f64, start_window: usize, limiter: Option<RateLimiter>) -> Self {
+        cfg.min_rate = cfg.min_rate.min(cfg.max_rate);
+        cfg.max_window = cfg.max_window.max(1);
+        cfg.min_window = cfg.min_window.clamp(1, cfg.max_window);
+        let rate = start_rate.max(cfg.min_rate).min(cfg.max_rate);
+        let window = start_window.clamp(cfg.min_window, cfg.max_window);
+        if let Some(l) = &limiter { l.set_rate(rate); }
+        let summary = AimdSummary {
+            start_rate: rate,
+            final_rate: rate,
+            min_rate_seen: rate,
+            max_rate_seen: rate,
+            start_window: window,
+            final_window: window,
+            increases: 0,
+            decreases: 0,
+            responded: 0,
+            timeouts: 0,
+        };
+        let state = State { cfg, rate, window: window as f64, epoch_responded: 0, epoch_timeouts: 0, summary };
+        Aimd { state: Arc::new(Mutex::new(state)), limiter }
+    }
+
+    pub fn record(&self, fb: Feedback) {
+        let new_rate = {
+            let mut s = self.state.lock().unwrap();
+            match fb {
+                Feedback::Responded => { s.epoch_responded += 1; s.summary.responded += 1; }
+                Feedback::Timeout => { s.epoch_timeouts += 1; s.summary.timeouts += 1; }
+            }
+            let total = s.epoch_responded + s.epoch_timeouts;
+            if total < s.cfg.epoch.max(s.window as u32) { return; }
+            let loss = s.epoch_timeouts as f64 / total as f64;
+            let cfg = s.cfg;
+            if loss > cfg.loss_threshold {
+                s.rate = (s.rate * cfg.decrease).max(cfg.min_rate);
+                s.window = (s.window * cfg.decrease).max(cfg.min_window.max(1) as f64);
+                s.summary.decreases += 1;
+            } else {
+                s.rate = (s.rate + cfg.rate_step).min(cfg.max_rate);
+                s.window = (s.window + cfg.window_step as f64).min(cfg.max_window.max(1) as f64);
+                s.summary.increases += 1;
+            }
+            s.epoch_responded = 0;
+            s.epoch_timeouts = 0;
+            s.summary.final_rate = s.rate;
+            s.summary.final_window = s.window as usize;
+            s.summary.min_rate_seen = s.summary.min_rate_seen.min(s.rate);
+            s.summary.max_rate_seen = s.summary.max_rate_seen.max(s.rate);
+            s.rate
+        };
+        if let Some(l) = &self.limiter { l.set_rate(new_rate); }
+    }
+
+    /// Current concurrency window.
+    pub fn window(&self) -> usize {
+        self.state.lock().unwrap().window as usize
+    }
+
+    /// Upper bound the window can grow to.
+    pub fn max_window(&self) -> usize {
+        self.state.lock().unwrap().cfg.max_window.max(1)
+    }
+
+    /// Current rate in probes/s.
+    pub fn rate(&self) -> f64 {
+        self.state.lock().unwrap().rate
+    }
+
+    pub fn summary(&self) -> AimdSummary {
+        self.state.lock().unwrap().summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(a: &Aimd, fb: Feedback, n: usize) {
+        for _ in 0..n { a.record(fb); }
+    }
+
+    #[test]
+    fn grows_on_responses_and_halves_on_timeouts() {
+        let a = Aimd::new(AimdConfig::around(100.0, 8), 100.0, 8, None);
+        feed(&a, Feedback::Responded, 64);
+        assert_eq!(a.rate(), 120.0);
+        assert_eq!(a.window(), 10);
+        feed(&a, Feedback::Timeout, 32);
+        assert_eq!(a.rate(), 60.0);
+        assert_eq!(a.window(), 5);
+        let s = a.summary();
+        assert_eq!((s.increases, s.decreases), (2, 1));
+        assert_eq!(s.max_rate_seen, 120.0);
+    }
+
+    #[test]
+    fn respects_bounds_and_drives_limiter() {
+        let rl = RateLimiter::with_burst(1.0, 1);
+        let a = Aimd::new(AimdConfig::around(100.0, 2), 100.0, 2, Some(rl.clone()));
+        feed(&a, Feedback::Timeout, 32 * 20);
+        assert_eq!(a.rate(), 10.0);
+        assert_eq!(a.window(), 1);
+        assert!((rl.rate() - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn inverted_bounds_yield_to_the_maximum() {
+        let mut cfg = AimdConfig::around(1000.0, 8);
+        (cfg.max_rate, cfg.min_window, cfg.max_window) = (50.0, 9, 4);
+        let a = Aimd::new(cfg, 1000.0, 8, None);
+        assert_eq!((a.rate(), a.window()), (50.0, 4));
+        feed(&a, Feedback::Timeout, 32 * 4);
+        assert_eq!(a.rate(), 50.0);
+        feed(&a, Feedback::Responded, 64);
+        assert_eq!((a.rate(), a.window()), (50.0, 4));
+    }
+}
@@ -0,0 +1,346 @@
+//! Async caching resolver shared by every module.
+//!
+//! Modules resolve names through the process-wide [`Resolver`] returned by [`global`]. It caches
+//! answers for their TTL (clamped to configured bounds), coalesces concurrent lookups of the same
+//! name, paces and bounds lookups independently of probe traffic, and remembers which names
+//! resolved to which addresses so results can carry hostnames. Reverse (PTR) lookups are
+//! forward-confirmed: the PTR name must resolve back to the address. By default it follows the
+//! system configuration; [`install`] replaces it with explicit nameservers, such as a local stand-in.
+
+use crate::ratelimiter::RateLimiter;
+use crate::target::{Family, Host};
+use hickory_resolver::config::{LookupIpStrategy, NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{OnceCell, Semaphore};
+
+#[derive(Debug, Error)]
+pub enum DnsError {
+    #[error("failed to read system resolver configuration: {0}")]
+    Config(String),
+    #[error("resolver already installed")]
+    AlreadyInstalled,
+    #[error("lookup of {name} failed: {reason}")]
+    Lookup { name: String, reason: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsConfig {
+    /// Nameservers to query (UDP, falling back to TCP); empty uses the system configuration.
+    pub nameservers: Vec<SocketAddr>,
+    /// Lookups per second across the process; 0 disables pacing.
+    pub qps: u32,
+    /// Lookups in flight at once.
+    pub concurrency: usize,
+    /// Per-query timeout and attempts per nameserver.
+    pub timeout: Duration,
+    pub attempts: usize,
+    /// Answers are cached for their TTL clamped to `[min_ttl, max_ttl]`.
+    pub min_ttl: Duration,
+    pub max_ttl: Duration,
+    /// How long a failed lookup is remembered.
+    pub negative_ttl: Duration,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            nameservers: Vec::new(),
+            qps: 0,
+            concurrency: 64,
+            timeout: Duration::from_secs(2),
+            attempts: 2,
+            min_ttl: Duration::from_secs(5),
+            max_ttl: Duration::from_secs(3600),
+            negative_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+/// PTR name of an address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ptr {
+    pub name: String,
+    /// The name resolves back to the address; an unconfirmed PTR may be stale or spoofed.
+    pub confirmed: bool,
+}
+
+struct Entry<V> {
+    value: V,
+    expires: Instant,
+}
+
+/// One cell per key, shared by concurrent lookups until it expires.
+struct Cache<K, V> {
+    cells: Mutex<HashMap<K, Arc<OnceCell<Entry<V>>>>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    fn new() -> Self {
+        Cache { cells: Mutex::new(HashMap::new()) }
+    }
+
+    async fn get<F: Future<Output = Entry<V>>>(&self, key: K, fetch: impl FnOnce() -> F) -> V {
+        let cell = {
+            let mut cells = self.cells.lock().unwrap();
+            let cell = cells.entry(key).or_default();
+            if cell.get().is_some_and(|e| e.expires <= Instant::now()) { *cell = Arc::default(); }
+            cell.clone()
+        };
+        cell.get_or_init(fetch).await.value.clone()
+    }
+
+    fn remove(&self, key: &K) {
+        self.cells.lock().unwrap().remove(key);
+    }
+}
+
+pub struct Resolver {
+    inner: TokioAsyncResolver,
+    cfg: DnsConfig,
+    /// Forward answers; `Err` holds the failure reason.
+    forward: Cache<String, Result<Arc<[IpAddr]>, String>>,
+    reverse: Cache<IpAddr, Option<Ptr>>,
+    /// First name seen for each address.
+    names: Mutex<HashMap<IpAddr, String>>,
+    limiter: Option<RateLimiter>,
+    slots: Semaphore,
+}
+
+impl Resolver {
+    pub fn new(cfg: DnsConfig) -> Result<Self, DnsError> {
+        let (config, mut opts) = if cfg.nameservers.is_empty() {
+            hickory_resolver::system_conf::read_system_conf().map_err(|e| DnsError::Config(e.to_string()))?
+        } else {
+            let servers: Vec<_> = cfg.nameservers.iter().flat_map(|a| [NameServerConfig::new(*a, Protocol::Udp), NameServerConfig::new(*a, Protocol::Tcp)]).collect();
+            (ResolverConfig::from_parts(None, vec![], NameServerConfigGroup::from(servers)), ResolverOpts::default())
+        };
+        opts.timeout = cfg.timeout;
+        opts.attempts = cfg.attempts.max(1);
+        opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+        let limiter = (cfg.qps > 0).then(|| RateLimiter::new(cfg.qps));
+        let slots = Semaphore::new(cfg.concurrency.max(1));
+        Ok(Resolver { inner: TokioAsyncResolver::tokio(config, opts), cfg, forward: Cache::new(), reverse: Cache::new(), names: Mutex::new(HashMap::new()), limiter, slots })
+    }
+
+    /// Every address of `name` (A and AAAA), from the cache while it is fresh.
+    pub async fn lookup(&self, name: &str) -> Result<Arc<[IpAddr]>, DnsError> {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        let addrs = self.forward.get(name.clone(), || self.fetch(&name)).await;
+        addrs.map_err(|reason| DnsError::Lookup { name, reason })
+    }
+
+    async fn fetch(&self, name: &str) -> Entry<Result<Arc<[IpAddr]>, String>> {
+        let _slot = self.slots.acquire().await;
+        if let Some(l) = &self.limiter { l.acquire().await; }
+        let now = Instant::now();
+        match self.inner.lookup_ip(name).await {
+            Ok(lookup) => {
+                let mut addrs: Vec<IpAddr> = Vec::new();
+                for ip in lookup.iter() {
+                    if !addrs.contains(&ip) { addrs.push(ip); }
+                }
+                let mut names = self.names.lock().unwrap();
+                for ip in &addrs { names.entry(*ip).or_insert_with(|| name.to_string()); }
+                let ttl = self.ttl(lookup.valid_until(), now);
+                Entry { value: Ok(addrs.into()), expires: now + ttl }
+            }
+            Err(e) => Entry { value: Err(e.to_string()), expires: now + self.cfg.negative_ttl },
+        }
+    }
+
+    /// Drop any cached answer for `name`, so the next lookup goes to the nameservers.
+    pub fn forget(&self, name: &str) {
+        self.forward.remove(&name.trim_end_matches('.').to_ascii_lowercase());
+    }
+
+    fn ttl(&self, valid_until: Instant, now: Instant) -> Duration {
+        valid_until.saturating_duration_since(now).clamp(self.cfg.min_ttl, self.cfg.max_ttl.max(self.cfg.min_ttl))
+    }
+
+    /// PTR name of `ip`, forward-confirmed; `None` if there is no PTR record or the lookup fails.
+    pub async fn reverse(&self, ip: IpAddr) -> Option<Ptr> {
+        self.reverse.get(ip, || self.fetch_ptr(ip)).await
+    }
+
+    async fn fetch_ptr(&self, ip: IpAddr) -> Entry<Option<Ptr>> {
+        let now = Instant::now();
+        // The slot is released before the confirming forward lookup takes its own.
+        let answer = {
+            let _slot = self.slots.acquire().await;
+            if let Some(l) = &self.limiter { l.acquire().await; }
+            self.inner.reverse_lookup(ip).await
+        };
+        let Ok(lookup) = answer else { return Entry { value: None, expires: now + self.cfg.negative_ttl } };
+        let expires = now + self.ttl(lookup.valid_until(), now);
+        let Some(name) = lookup.iter().next().map(|n| n.to_utf8().trim_end_matches('.').to_string()) else {
+            return Entry { value: None, expires: now + self.cfg.negative_ttl };
+        };
+        let confirmed = self.lookup(&name).await.is_ok_and(|addrs| addrs.contains(&ip));
+        Entry { value: Some(Ptr { name, confirmed }), expires }
+    }
+
+    /// [`Resolver::reverse`] for many addresses at once (bounded by the lookup concurrency);
+    /// addresses without a PTR name are left out.
+    pub async fn reverse_all(self: &Arc<Self>, ips: impl IntoIterator<Item = IpAddr>) -> HashMap<IpAddr, Ptr> {
+        let mut set = tokio::task::JoinSet::new();
+        for ip in ips {
+            let r = self.clone();
+            set.spawn(async move { (ip, r.reverse(ip).await) });
+        }
+        let mut out = HashMap::new();
+        while let Some(res) = set.join_next().await {
+            if let Ok((ip, Some(ptr))) = res { out.insert(ip, ptr); }
+        }
+        out
+    }
+
+    /// Addresses of `host` in `family`: the literal itself, or every resolved address of a name
+    /// (none if the lookup fails).
+    pub async fn resolve(&self, host: &Host, family: Family) -> Vec<IpAddr> {
+        let addrs = match host {
+            Host::Addr(ip) => return if family.allows(ip) { vec![*ip] } else { Vec::new() },
+            Host::Name(name) => self.lookup(name).await.unwrap_or_else(|_| Arc::from([])),
+        };
+        addrs.iter().copied().filter(|ip| family.allows(ip)).collect()
+    }
+
+    /// Name that resolved to `ip` earlier in this process, if any.
+    pub fn hostname(&self, ip: &IpAddr) -> Option<String> {
+        self.names.lock().unwrap().get(ip).cloned()
+    }
+}
+
+static GLOBAL: OnceLock<Arc<Resolver>> = OnceLock::new();
+
+/// Make `resolver` the process-wide resolver. Fails once [`global`] has been used or a resolver
+/// was installed.
+pub fn install(resolver: Resolver) -> Result<(), DnsError> {
+    GLOBAL.set(Arc::new(resolver)).map_err(|_| DnsError::AlreadyInstalled)
+}
+
+/// The process-wide resolver; the system configuration unless [`install`] was called first.
+pub fn global() -> Arc<Resolver> {
+    GLOBAL
+        .get_or_init(|| {
+            let r = Resolver::new(DnsConfig::default()).unwrap_or_else(|_| {
+                // No usable system configuration: only the hosts file and literals resolve.
+                let opts = ResolverOpts::default();
+                let cfg = DnsConfig::default();
+                Resolver { inner: TokioAsyncResolver::tokio(ResolverConfig::new(), opts), limiter: None, slots: Semaphore::new(cfg.concurrency), cfg, forward: Cache::new(), reverse: Cache::new(), names: Mutex::default() }
+            });
+            Arc::new(r)
+        })
+        .clone()
+}
+
+/// A local DNS stand-in, for tests here and in modules that resolve through [`global`] (enable
+/// the `test-util` feature in their dev-dependencies).
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Minimal authoritative stand-in on its own thread: `app.lab` has one A and one AAAA record
+    /// (TTL 1 s), `local.lab` has 127.0.0.1 and ::1, both 10.9.8.7 and 10.0.0.1 have PTR
+    /// `app.lab`, everything else is NXDOMAIN. Returns its address and the number of queries seen.
+    pub fn stand_in() -> (SocketAddr, Arc<AtomicUsize>) {
+        let sock = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = sock.local_addr().unwrap();
+        let queries = Arc::new(AtomicUsize::new(0));
+        let count = queries.clone();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            loop {
+                let Ok((n, peer)) = sock.recv_from(&mut buf) else { return };
+                count.fetch_add(1, Ordering::SeqCst);
+                let q = &buf[..n];
+                let mut i = 12;
+                let mut labels = Vec::new();
+                while q[i] != 0 {
+                    labels.push(String::from_utf8_lossy(&q[i + 1..i + 1 + q[i] as usize]).to_lowercase());
+                    i += 1 + q[i] as usize;
+                }
+                let qtype = u16::from_be_bytes([q[i + 1], q[i + 2]]);
+                let question = &q[12..i + 5];
+                let rdata: Option<Vec<u8>> = match (labels.join(".").as_str(), qtype) {
+                    ("app.lab", 1) => Some(vec![10, 9, 8, 7]),
+                    ("app.lab", 28) => Some("2001:db8::7".parse::<std::net::Ipv6Addr>().unwrap().octets().to_vec()),
+                    ("local.lab", 1) => Some(vec![127, 0, 0, 1]),
+                    ("local.lab", 28) => Some(std::net::Ipv6Addr::LOCALHOST.octets().to_vec()),
+                    ("app.lab" | "local.lab", _) => None,
+                    ("7.8.9.10.in-addr.arpa" | "1.0.0.10.in-addr.arpa", 12) => Some(vec![3, b'a', b'p', b'p', 3, b'l', b'a', b'b', 0]),
+                    _ => { let mut r = q[..2].to_vec(); r.extend([0x81, 0x83, 0, 1, 0, 0, 0, 0, 0, 0]); r.extend(question); let _ = sock.send_to(&r, peer); continue }
+                };
+                let mut r = q[..2].to_vec();
+                r.extend([0x81, 0x80, 0, 1, 0, rdata.is_some() as u8, 0, 0, 0, 0]);
+                r.extend(question);
+                if let Some(d) = rdata {
+                    r.extend([0xc0, 0x0c]);
+                    r.extend(qtype.to_be_bytes());
+                    r.extend([0, 1, 0, 0, 0, 1]);
+                    r.extend((d.len() as u16).to_be_bytes());
+                    r.extend(d);
+                }
+                let _ = sock.send_to(&r, peer);
+            }
+        });
+        (addr, queries)
+    }
+
+    /// Make [`global`] resolve through one shared [`stand_in`]. Tests of a crate that resolves
+    /// through it call this first, each of them, as the global resolver is set once per process.
+    pub fn install() {
+        static SERVER: OnceLock<SocketAddr> = OnceLock::new();
+        let server = *SERVER.get_or_init(|| stand_in().0);
+        let r = GLOBAL.get_or_init(|| Arc::new(Resolver::new(DnsConfig { nameservers: vec![server], ..DnsConfig::default() }).unwrap()));
+        assert_eq!(r.cfg.nameservers, [server], "dns::global() was used before dns::testing::install()");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::stand_in;
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn caches_answers_for_their_ttl() {
+        let (server, queries) = stand_in();
+        let cfg = DnsConfig { nameservers: vec![server], min_ttl: Duration::ZERO, negative_ttl: Duration::from_secs(60), ..DnsConfig::default() };
+        let r = Resolver::new(cfg).unwrap();
+        let host = Host::Name("app.lab".into());
+        let (a, b) = tokio::join!(r.resolve(&host, Family::Any), r.resolve(&host, Family::V6));
+        assert_eq!(a, vec!["10.9.8.7".parse::<IpAddr>().unwrap(), "2001:db8::7".parse().unwrap()]);
+        assert_eq!(b, vec!["2001:db8::7".parse::<IpAddr>().unwrap()]);
+        assert_eq!(queries.load(Ordering::SeqCst), 2, "A and AAAA once, shared by both callers");
+        assert_eq!(r.hostname(&"10.9.8.7".parse().unwrap()).as_deref(), Some("app.lab"));
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(r.resolve(&host, Family::V4).await.len(), 1);
+        assert_eq!(queries.load(Ordering::SeqCst), 4, "expired entry is looked up again");
+
+        assert!(r.lookup("missing.lab").await.is_err());
+        let before = queries.load(Ordering::SeqCst);
+        assert!(r.lookup("missing.lab").await.is_err());
+        assert_eq!(queries.load(Ordering::SeqCst), before, "failures are cached too");
+        assert!(r.resolve(&"::1".parse().unwrap(), Family::V4).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reverse_lookups_are_forward_confirmed() {
+        let (server, _) = stand_in();
+        let r = Arc::new(Resolver::new(DnsConfig { nameservers: vec![server], ..DnsConfig::default() }).unwrap());
+        let ips: Vec<IpAddr> = ["10.9.8.7", "10.0.0.1", "10.0.0.2"].iter().map(|s| s.parse().unwrap()).collect();
+        let ptrs = r.reverse_all(ips.clone()).await;
+        assert_eq!(ptrs.get(&ips[0]), Some(&Ptr { name: "app.lab".into(), confirmed: true }));
+        assert_eq!(ptrs.get(&ips[1]), Some(&Ptr { name: "app.lab".into(), confirmed: false }));
+        assert!(!ptrs.contains_key(&ips[2]));
+    }
+}
@@ -4,17 +4,19 @@ pub const fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
 
-/// Example shared type used across modules.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Target(pub String);
-
-impl From<&str> for Target {
-    fn from(s: &str) -> Self {
-        Target(s.to_string())
-    }
-}
-
+pub mod audit;
+pub mod cancel;
+pub mod congestion;
+pub mod connect;
+pub mod dns;
+pub mod policy;
+pub mod progress;
 pub mod ratelimiter;
+pub mod scope;
+pub mod socket;
+pub mod target;
+
+pub use target::{Exclusions, Family, Host, Target, TargetError, TargetIndex, TargetSet, TargetSpec, Targets};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,137 @@
+//! Intrusiveness levels of module capabilities and the policy that gates them.
+//!
+//! Every module lists its [`Capability`]s with a [`Level`]. Intrusive capabilities call
+//! [`require`] before they act; the installed [`Policy`] allows everything up to its level plus
+//! capabilities allowed by name. Without an installed policy everything is allowed.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// How much a capability touches the target, in increasing order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Nothing is sent to the target (local files, lookups in public DNS).
+    Passive,
+    /// Ordinary connections and probes a service expects to see.
+    SafeActive,
+    /// Guessing credentials, brute forcing or anything that may trip alarms or change state.
+    Intrusive,
+}
+
+impl Level {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Level::Passive => "passive",
+            Level::SafeActive => "safe-active",
+            Level::Intrusive => "intrusive",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Level {
+    type Err = PolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "passive" => Ok(Level::Passive),
+            "safe-active" => Ok(Level::SafeActive),
+            "intrusive" => Ok(Level::Intrusive),
+            _ => Err(PolicyError::Level(s.to_string())),
+        }
+    }
+}
+
+/// Something a module can do, e.g. `udp-probe.snmp`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    /// `module.capability`
+    pub name: &'static str,
+    pub level: Level,
+    pub summary: &'static str,
+}
+
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("invalid intrusiveness level: {0} (expected passive, safe-active or intrusive)")]
+    Level(String),
+    #[error("policy already installed")]
+    AlreadyInstalled,
+}
+
+/// A refused capability.
+#[derive(Debug, Clone, Error)]
+#[error("{name} is {level} and not allowed by the current policy (use --active to allow it)")]
+pub struct Refused {
+    pub name: &'static str,
+    pub level: Level,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    /// Highest level allowed.
+    pub max: Level,
+    /// Capabilities allowed regardless of `max`.
+    pub allow: Vec<String>,
+}
+
+impl Default for Policy {
+    /// Safe-active: intrusive capabilities need an explicit opt-in.
+    fn default() -> Self {
+        Policy { max: Level::SafeActive, allow: Vec::new() }
+    }
+}
+
+impl Policy {
+    pub fn allows(&self, cap: &Capability) -> bool {
+        cap.level <= self.max || self.allow.iter().any(|a| a == cap.name)
+    }
+}
+
+static GLOBAL: OnceLock<Policy> = OnceLock::new();
+
+/// Install the process-wide policy.
+pub fn install(policy: Policy) -> Result<(), PolicyError> {
+    GLOBAL.set(policy).map_err(|_| PolicyError::AlreadyInstalled)
+}
+
+/// The installed policy, if any.
+pub fn global() -> Option<&'static Policy> {
+    GLOBAL.get()
+}
+
+/// Fail unless the installed policy allows `cap`. Refusals are recorded in the audit log.
+pub fn require(cap: &Capability) -> Result<(), Refused> {
+    match GLOBAL.get() {
+        Some(p) if !p.allows(cap) => {
+            crate::audit::record("policy", "refused", cap.name);
+            Err(Refused { name: cap.name, level: cap.level })
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gates_by_level_and_name() {
+        let snmp = Capability { name: "udp-probe.snmp", level: Level::Intrusive, summary: "" };
+        let dns = Capability { name: "udp-probe.dns", level: Level::SafeActive, summary: "" };
+        let default = Policy::default();
+        assert!(default.allows(&dns) && !default.allows(&snmp));
+        assert!(Policy { max: Level::Intrusive, allow: vec![] }.allows(&snmp));
+        assert!(Policy { max: Level::Passive, allow: vec!["udp-probe.snmp".into()] }.allows(&snmp));
+        assert!(!Policy { max: Level::Passive, allow: vec![] }.allows(&dns));
+        assert_eq!("safe-active".parse::<Level>().unwrap(), Level::SafeActive);
+        assert!("loud".parse::<Level>().is_err());
+    }
+}
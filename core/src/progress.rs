@@ -0,0 +1,178 @@
+//! Live progress counters shared by a command and the modules it drives.
+//!
+//! Modules count into the [`Progress`] cloned into their options as they work; the command samples
+//! it with a [`Meter`] for rates and an ETA. Work is counted in units the module defines (a port
+//! scan counts target×port pairs drawn from the target generator, discovery counts targets) against
+//! a total the command sets when the size of the generator is known.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    hosts: AtomicU64,
+    found: AtomicU64,
+    errors: AtomicU64,
+    done: AtomicU64,
+    /// 0 while unknown.
+    total: AtomicU64,
+}
+
+/// Progress counters; clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    inner: Arc<Counters>,
+}
+
+/// Counters at one point in time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Probes sent, retries included.
+    pub sent: u64,
+    /// Hosts whose probes have all finished.
+    pub hosts: u64,
+    /// Open ports, live hosts or answering endpoints.
+    pub found: u64,
+    /// Probes or targets that failed with an error (not a timeout or refusal).
+    pub errors: u64,
+    /// Units of work finished, including work skipped or done by an earlier run.
+    pub done: u64,
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    pub fn sent(&self, n: u64) {
+        self.inner.sent.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn host_done(&self) {
+        self.inner.hosts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn found(&self) {
+        self.inner.found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn error(&self) {
+        self.inner.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn done(&self, units: u64) {
+        self.inner.done.fetch_add(units, Ordering::Relaxed);
+    }
+
+    /// Units of work the whole run amounts to.
+    pub fn set_total(&self, units: u64) {
+        self.inner.total.store(units, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let c = &*self.inner;
+        let total = c.total.load(Ordering::Relaxed);
+        Snapshot {
+            sent: c.sent.load(Ordering::Relaxed),
+            hosts: c.hosts.load(Ordering::Relaxed),
+            found: c.found.load(Ordering::Relaxed),
+            errors: c.errors.load(Ordering::Relaxed),
+            done: c.done.load(Ordering::Relaxed),
+            total: (total > 0).then_some(total),
+        }
+    }
+}
+
+/// A sampled snapshot with rates and the estimated time left.
+#[derive(Debug, Clone, Copy)]
+pub struct Report {
+    pub snapshot: Snapshot,
+    pub elapsed: Duration,
+    /// Probes sent per second, smoothed over recent samples.
+    pub rate: f64,
+    /// Share of the total done, 0 to 100.
+    pub percent: Option<f64>,
+    /// Time left at the recent pace of finishing work; unknown without a total or any work done.
+    pub eta: Option<Duration>,
+}
+
+/// Weight of the newest interval in the smoothed rates.
+const SMOOTHING: f64 = 0.3;
+
+/// Turns successive snapshots into smoothed rates and an ETA.
+#[derive(Debug)]
+pub struct Meter {
+    started: Instant,
+    last: Option<(Instant, Snapshot)>,
+    /// Smoothed probes and work units per second.
+    rates: Option<(f64, f64)>,
+}
+
+impl Default for Meter {
+    fn default() -> Self {
+        Meter::new(Instant::now())
+    }
+}
+
+impl Meter {
+    /// A meter for a run that started at `started`.
+    pub fn new(started: Instant) -> Self {
+        Meter { started, last: None, rates: None }
+    }
+
+    pub fn sample(&mut self, snapshot: Snapshot) -> Report {
+        self.sample_at(snapshot, Instant::now())
+    }
+
+    fn sample_at(&mut self, s: Snapshot, now: Instant) -> Report {
+        let (since, before) = self.last.unwrap_or((self.started, Snapshot::default()));
+        let secs = now.saturating_duration_since(since).as_secs_f64();
+        if secs > 0.0 {
+            let sent = s.sent.saturating_sub(before.sent) as f64 / secs;
+            let done = s.done.saturating_sub(before.done) as f64 / secs;
+            self.rates = Some(match self.rates {
+                Some((r, d)) => (r + SMOOTHING * (sent - r), d + SMOOTHING * (done - d)),
+                None => (sent, done),
+            });
+            self.last = Some((now, s));
+        }
+        let (rate, done_rate) = self.rates.unwrap_or_default();
+        let remaining = s.total.map(|t| t.saturating_sub(s.done));
+        let eta = match remaining {
+            Some(0) => Some(Duration::ZERO),
+            Some(left) if done_rate > 0.0 => Some(Duration::from_secs_f64(left as f64 / done_rate)),
+            _ => None,
+        };
+        let percent = s.total.map(|t| s.done.min(t) as f64 * 100.0 / t as f64);
+        Report { snapshot: s, elapsed: now.saturating_duration_since(self.started), rate, percent, eta }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_time_left_from_recent_pace() {
+        let progress = Progress::default();
+        progress.set_total(1000);
+        let t0 = Instant::now();
+        let mut meter = Meter::new(t0);
+        assert_eq!(meter.sample_at(progress.snapshot(), t0).eta, None);
+
+        progress.sent(120);
+        progress.done(100);
+        let r = meter.sample_at(progress.snapshot(), t0 + Duration::from_secs(1));
+        assert_eq!((r.rate, r.percent), (120.0, Some(10.0)));
+        assert_eq!(r.eta, Some(Duration::from_secs(9)));
+
+        // Slower second interval: the smoothed pace falls between the two.
+        progress.done(40);
+        let r = meter.sample_at(progress.snapshot(), t0 + Duration::from_secs(2));
+        let eta = r.eta.unwrap().as_secs_f64();
+        assert!(860.0 / 100.0 < eta && eta < 860.0 / 40.0, "{}", eta);
+
+        progress.done(860);
+        assert_eq!(meter.sample_at(progress.snapshot(), t0 + Duration::from_secs(3)).eta, Some(Duration::ZERO));
+        assert_eq!(Progress::default().snapshot().total, None);
+    }
+}
@@ -1,33 +1,131 @@
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+/// Token bucket shared by all clones, implemented as GCRA (virtual scheduling).
+///
+/// There is no background refill task: each `acquire` reserves the next send slot under a mutex
+/// and sleeps until it, so fractional and very high rates are exact and dropping the last clone
+/// releases everything.
+#[derive(Clone)]
 pub struct RateLimiter {
-    sem: Arc<Semaphore>,
+    state: Arc<Mutex<Bucket>>,
 }
 
-impl Clone for RateLimiter {
-    fn clone(&self) -> Self { RateLimiter { sem: self.sem.clone() } }
+struct Bucket {
+    base: Instant,
+    /// Seconds between tokens; 0 means unlimited.
+    interval: f64,
+    burst: u32,
+    /// Theoretical arrival time of the next token, in seconds since `base`.
+    tat: f64,
+}
+
+impl Bucket {
+    fn tolerance(&self) -> f64 {
+        self.interval * (self.burst.max(1) - 1) as f64
+    }
 }
 
 impl RateLimiter {
+    /// Limiter at `tokens_per_sec` with the default burst (see [`default_burst`]).
     pub fn new(tokens_per_sec: u32) -> Self {
-        let sem = Arc::new(Semaphore::new(0));
-        let sem_bg = sem.clone();
-        let interval_ms = (1000u32 / tokens_per_sec.max(1)) as u64;
-        // Refill in a background task
-        tokio::spawn(async move {
-            let mut t = tokio::time::interval(std::time::Duration::from_millis(interval_ms));
-            t.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
-            loop {
-                t.tick().await;
-                sem_bg.add_permits(1);
-            }
-        });
-        RateLimiter { sem }
+        let rate = tokens_per_sec as f64;
+        Self::with_burst(rate, default_burst(rate))
     }
 
+    /// Limiter at a fractional `rate` (tokens/s) allowing up to `burst` back-to-back tokens.
+    /// A non-positive or non-finite rate disables pacing.
+    pub fn with_burst(rate: f64, burst: u32) -> Self {
+        let bucket = Bucket { base: Instant::now(), interval: interval_for(rate), burst: burst.max(1), tat: 0.0 };
+        RateLimiter { state: Arc::new(Mutex::new(bucket)) }
+    }
+
+    /// Wait for one token.
     pub async fn acquire(&self) {
-        let _ = self.sem.acquire().await;
+        let wait = {
+            let mut b = self.state.lock().unwrap();
+            if b.interval == 0.0 { return; }
+            let now = b.base.elapsed().as_secs_f64();
+            let tat = b.tat.max(now);
+            let send_at = (tat - b.tolerance()).max(now);
+            b.tat = tat + b.interval;
+            send_at - now
+        };
+        if wait > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+
+    /// Current rate in tokens/s (`f64::INFINITY` when unlimited).
+    pub fn rate(&self) -> f64 {
+        let b = self.state.lock().unwrap();
+        if b.interval == 0.0 { f64::INFINITY } else { 1.0 / b.interval }
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.state.lock().unwrap().burst
+    }
+
+    /// Change the rate for all clones. Slots already handed out are kept; the new spacing applies
+    /// from the next `acquire`.
+    pub fn set_rate(&self, rate: f64) {
+        let mut b = self.state.lock().unwrap();
+        let now = b.base.elapsed().as_secs_f64();
+        b.interval = interval_for(rate);
+        // Re-anchor so the new spacing isn't queued behind slots reserved at the old one.
+        b.tat = b.tat.min(now + b.interval);
+    }
+
+    pub fn set_burst(&self, burst: u32) {
+        self.state.lock().unwrap().burst = burst.max(1);
     }
 }
 
+/// Burst of ~10 ms worth of tokens (at least 1), so timer granularity doesn't cap high rates.
+pub fn default_burst(rate: f64) -> u32 {
+    if rate.is_finite() && rate > 0.0 { (rate / 100.0).ceil().max(1.0) as u32 } else { 1 }
+}
+
+fn interval_for(rate: f64) -> f64 {
+    if rate.is_finite() && rate > 0.0 { 1.0 / rate } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn time_acquires(rl: &RateLimiter, n: usize) -> Duration {
+        let start = Instant::now();
+        for _ in 0..n { rl.acquire().await; }
+        start.elapsed()
+    }
+
+    #[tokio::test]
+    async fn burst_then_paced() {
+        let rl = RateLimiter::with_burst(100.0, 5);
+        // 5 immediate, then 10 more at 10 ms spacing
+        let d = time_acquires(&rl, 15).await;
+        assert!(d >= Duration::from_millis(90), "{:?}", d);
+        assert!(d < Duration::from_millis(400), "{:?}", d);
+    }
+
+    #[tokio::test]
+    async fn high_rate_is_not_capped_at_1000() {
+        let rl = RateLimiter::new(5000);
+        let d = time_acquires(&rl, 1000).await;
+        assert!(d >= Duration::from_millis(150), "{:?}", d);
+        assert!(d < Duration::from_millis(600), "{:?}", d);
+    }
+
+    #[tokio::test]
+    async fn set_rate_applies_to_clones() {
+        let rl = RateLimiter::with_burst(1.0, 1);
+        let clone = rl.clone();
+        clone.set_rate(0.0);
+        assert!(rl.rate().is_infinite());
+        let d = time_acquires(&rl, 100).await;
+        assert!(d < Duration::from_millis(50), "{:?}", d);
+        rl.set_rate(2.5);
+        assert!((clone.rate() - 2.5).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,302 @@
+//! Engagement scope enforced before every connection.
+//!
+//! A [`Scope`] lists included and excluded CIDR blocks and domains (`example.com` matches the name
+//! itself, `*.example.com` its subdomains) plus an optional port allow-list. Once [`install`]ed,
+//! every module calls [`check`] before it connects (web-surface also for each redirect hop), and
+//! attempts outside the scope are refused and reported to the hook given at installation. Without
+//! an installed scope everything is allowed.
+//!
+//! A destination is in scope when it is not excluded (by address, name or port) and either the
+//! include list is empty, its address is inside an included block, or the name it was reached by
+//! matches an included domain.
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScopeError {
+    #[error("invalid scope entry: {0}")]
+    Entry(String),
+    #[error("invalid scope ports: {0}")]
+    Ports(String),
+    #[error("scope already installed")]
+    AlreadyInstalled,
+}
+
+/// A refused connection attempt.
+#[derive(Debug, Clone, Error)]
+#[error("out of scope: {target} ({reason})")]
+pub struct OutOfScope {
+    /// `name`, `address` or `name/address`, with `:port` when known.
+    pub target: String,
+    pub reason: &'static str,
+}
+
+/// A domain pattern: an exact name or `*.` followed by a parent domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Domain {
+    name: String,
+    wildcard: bool,
+}
+
+impl Domain {
+    fn matches(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if self.wildcard {
+            host.len() > self.name.len() && host.ends_with(&self.name) && host.as_bytes()[host.len() - self.name.len() - 1] == b'.'
+        } else {
+            host == self.name
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Rules {
+    nets: Vec<IpNet>,
+    domains: Vec<Domain>,
+}
+
+impl Rules {
+    fn parse<S: AsRef<str>>(entries: &[S]) -> Result<Self, ScopeError> {
+        let mut rules = Rules::default();
+        for e in entries {
+            let e = e.as_ref().trim();
+            if let Ok(net) = e.parse::<IpNet>() {
+                rules.nets.push(net);
+            } else if let Ok(ip) = e.parse::<IpAddr>() {
+                rules.nets.push(IpNet::from(ip));
+            } else {
+                let (wildcard, name) = match e.strip_prefix("*.") { Some(rest) => (true, rest), None => (false, e) };
+                let name = name.trim_end_matches('.').to_ascii_lowercase();
+                let valid = !name.is_empty() && name.split('.').all(|l| !l.is_empty() && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+                if !valid { return Err(ScopeError::Entry(e.to_string())); }
+                rules.domains.push(Domain { name, wildcard });
+            }
+        }
+        Ok(rules)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nets.is_empty() && self.domains.is_empty()
+    }
+
+    fn has_ip(&self, ip: &IpAddr) -> bool {
+        self.nets.iter().any(|n| n.contains(ip))
+    }
+
+    fn has_name(&self, name: &str) -> bool {
+        self.domains.iter().any(|d| d.matches(name))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scope {
+    include: Rules,
+    exclude: Rules,
+    /// Allowed port ranges (inclusive); `None` allows every port.
+    ports: Option<Vec<(u16, u16)>>,
+}
+
+impl Scope {
+    /// Build from include/exclude entries (CIDRs, addresses, domains, `*.domain` wildcards) and
+    /// an optional port list such as `22,80,8000-8100`.
+    pub fn new<S: AsRef<str>>(include: &[S], exclude: &[S], ports: Option<&str>) -> Result<Self, ScopeError> {
+        let ports = match ports {
+            Some(spec) => Some(parse_ports(spec)?),
+            None => None,
+        };
+        Ok(Scope { include: Rules::parse(include)?, exclude: Rules::parse(exclude)?, ports })
+    }
+
+    /// Further limit the allowed ports to those in `spec` as well.
+    pub fn restrict_ports(&mut self, spec: &str) -> Result<(), ScopeError> {
+        let limit = parse_ports(spec)?;
+        self.ports = Some(match self.ports.take() {
+            None => limit,
+            Some(cur) => cur.iter().flat_map(|&(a, b)| limit.iter().filter_map(move |&(c, d)| (a.max(c) <= b.min(d)).then_some((a.max(c), b.min(d))))).collect(),
+        });
+        Ok(())
+    }
+
+    pub fn allows_port(&self, port: u16) -> bool {
+        match &self.ports {
+            Some(ranges) => ranges.iter().any(|(a, b)| (*a..=*b).contains(&port)),
+            None => true,
+        }
+    }
+
+    /// Decide one destination. `name` is the hostname it is reached by, if any. With no address
+    /// (a name not resolved yet) only exclusions and the port are checked; the address is checked
+    /// again once known.
+    pub fn check(&self, name: Option<&str>, ip: Option<IpAddr>, port: Option<u16>) -> Result<(), OutOfScope> {
+        let reason = if name.is_some_and(|n| self.exclude.has_name(n)) || ip.is_some_and(|ip| self.exclude.has_ip(&ip)) {
+            Some("excluded")
+        } else if port.is_some_and(|p| !self.allows_port(p)) {
+            Some("port not in scope")
+        } else if let Some(ip) = ip {
+            let included = self.include.is_empty() || self.include.has_ip(&ip) || name.is_some_and(|n| self.include.has_name(n));
+            (!included).then_some("not in scope")
+        } else {
+            None
+        };
+        match reason {
+            None => Ok(()),
+            Some(reason) => Err(OutOfScope { target: describe(name, ip, port), reason }),
+        }
+    }
+
+    /// Decide a name resolved elsewhere (by a proxy), whose address is never seen: besides
+    /// exclusions and the port, it must match an included domain unless the include list is empty.
+    pub fn check_remote(&self, name: &str, port: Option<u16>) -> Result<(), OutOfScope> {
+        self.check(Some(name), None, port)?;
+        if self.include.is_empty() || self.include.has_name(name) { return Ok(()); }
+        Err(OutOfScope { target: describe(Some(name), None, port), reason: "not in scope" })
+    }
+}
+
+fn describe(name: Option<&str>, ip: Option<IpAddr>, port: Option<u16>) -> String {
+    let host = match (name, ip) {
+        (Some(n), Some(ip)) => format!("{}/{}", n, ip),
+        (Some(n), None) => n.to_string(),
+        (None, Some(IpAddr::V6(v6))) if port.is_some() => format!("[{}]", v6),
+        (None, Some(ip)) => ip.to_string(),
+        (None, None) => "?".to_string(),
+    };
+    match port {
+        Some(p) => format!("{}:{}", host, p),
+        None => host,
+    }
+}
+
+fn parse_ports(spec: &str) -> Result<Vec<(u16, u16)>, ScopeError> {
+    let bad = || ScopeError::Ports(spec.to_string());
+    let mut out = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (a, b) = match part.split_once('-') {
+            Some((a, b)) => (a.trim().parse::<u16>().map_err(|_| bad())?, b.trim().parse::<u16>().map_err(|_| bad())?),
+            None => {
+                let p = part.parse::<u16>().map_err(|_| bad())?;
+                (p, p)
+            }
+        };
+        if a == 0 || a > b { return Err(bad()); }
+        out.push((a, b));
+    }
+    if out.is_empty() { return Err(bad()); }
+    Ok(out)
+}
+
+/// Called with every refused attempt.
+pub type BlockedHook = Box<dyn Fn(&OutOfScope) + Send + Sync>;
+
+struct Installed {
+    scope: Scope,
+    hook: Option<BlockedHook>,
+    blocked: AtomicU64,
+}
+
+impl Installed {
+    fn report(&self, result: Result<(), OutOfScope>) -> Result<(), OutOfScope> {
+        if let Err(e) = &result {
+            self.blocked.fetch_add(1, Ordering::Relaxed);
+            if let Some(hook) = &self.hook { hook(e); }
+        }
+        result
+    }
+}
+
+static GLOBAL: OnceLock<Installed> = OnceLock::new();
+
+/// Install the process-wide scope; `hook` sees every refused attempt.
+pub fn install(scope: Scope, hook: Option<BlockedHook>) -> Result<(), ScopeError> {
+    GLOBAL.set(Installed { scope, hook, blocked: AtomicU64::new(0) }).map_err(|_| ScopeError::AlreadyInstalled)
+}
+
+/// The installed scope, if any.
+pub fn global() -> Option<&'static Scope> {
+    GLOBAL.get().map(|i| &i.scope)
+}
+
+/// Check a destination against the installed scope (see [`Scope::check`]), counting and
+/// reporting refusals. Always passes when no scope is installed.
+pub fn check(name: Option<&str>, ip: Option<IpAddr>, port: Option<u16>) -> Result<(), OutOfScope> {
+    let Some(inst) = GLOBAL.get() else { return Ok(()) };
+    inst.report(inst.scope.check(name, ip, port))
+}
+
+/// [`Scope::check_remote`] against the installed scope, counting and reporting refusals.
+pub fn check_remote(name: &str, port: Option<u16>) -> Result<(), OutOfScope> {
+    let Some(inst) = GLOBAL.get() else { return Ok(()) };
+    inst.report(inst.scope.check_remote(name, port))
+}
+
+/// First of `ips` (the addresses `name` resolved to, if any) that passes [`check`]: `Ok(None)`
+/// when there are no addresses, the first refusal when none is in scope.
+pub fn first_allowed(name: Option<&str>, ips: impl IntoIterator<Item = IpAddr>, port: Option<u16>) -> Result<Option<IpAddr>, OutOfScope> {
+    let mut refused = None;
+    for ip in ips {
+        match check(name, Some(ip), port) {
+            Ok(()) => return Ok(Some(ip)),
+            Err(e) => { refused.get_or_insert(e); }
+        }
+    }
+    refused.map_or(Ok(None), Err)
+}
+
+/// Every one of `ips` that passes [`check`]: empty when there are no addresses, the first
+/// refusal when none is in scope.
+pub fn allowed(name: Option<&str>, ips: impl IntoIterator<Item = IpAddr>, port: Option<u16>) -> Result<Vec<IpAddr>, OutOfScope> {
+    let (mut ok, mut refused) = (Vec::new(), None);
+    for ip in ips {
+        match check(name, Some(ip), port) {
+            Ok(()) => ok.push(ip),
+            Err(e) => { refused.get_or_insert(e); }
+        }
+    }
+    match refused {
+        Some(e) if ok.is_empty() => Err(e),
+        _ => Ok(ok),
+    }
+}
+
+/// Number of attempts refused so far.
+pub fn blocked() -> u64 {
+    GLOBAL.get().map_or(0, |i| i.blocked.load(Ordering::Relaxed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_excludes_and_ports() {
+        let scope = Scope::new(&["10.0.0.0/16", "*.example.com", "example.org"], &["10.0.5.0/24", "vpn.example.com"], Some("22,80,8000-8100")).unwrap();
+        let ip = |s: &str| Some(s.parse::<IpAddr>().unwrap());
+        assert!(scope.check(None, ip("10.0.1.1"), Some(22)).is_ok());
+        assert_eq!(scope.check(None, ip("10.0.5.1"), Some(22)).unwrap_err().reason, "excluded");
+        assert_eq!(scope.check(None, ip("10.0.1.1"), Some(443)).unwrap_err().reason, "port not in scope");
+        assert!(scope.check(None, ip("10.0.1.1"), Some(8050)).is_ok());
+        assert_eq!(scope.check(None, ip("192.0.2.1"), None).unwrap_err().to_string(), "out of scope: 192.0.2.1 (not in scope)");
+        // Names in an included domain are in scope wherever they point, unless excluded.
+        assert!(scope.check(Some("www.Example.com."), ip("192.0.2.1"), Some(80)).is_ok());
+        assert!(scope.check(Some("example.com"), ip("192.0.2.1"), Some(80)).is_err());
+        assert!(scope.check(Some("example.org"), ip("192.0.2.1"), Some(80)).is_ok());
+        assert!(scope.check(Some("vpn.example.com"), ip("10.0.1.1"), None).is_err());
+        // Unresolved names only face exclusions and ports.
+        assert!(scope.check(Some("elsewhere.test"), None, Some(80)).is_ok());
+        assert!(scope.check(Some("vpn.example.com"), None, Some(80)).is_err());
+        // Names resolved by a proxy must be included by name.
+        assert!(scope.check_remote("app.example.com", Some(80)).is_ok());
+        assert_eq!(scope.check_remote("elsewhere.test", Some(80)).unwrap_err().reason, "not in scope");
+        assert!(Scope::new(&["bad host"], &[], None).is_err());
+        assert!(Scope::new::<&str>(&[], &[], Some("0-10")).is_err());
+        assert!(Scope::new::<&str>(&[], &[], None).unwrap().check(None, ip("192.0.2.1"), Some(1)).is_ok());
+        let mut narrowed = scope.clone();
+        narrowed.restrict_ports("80-8010").unwrap();
+        assert!(narrowed.allows_port(80) && narrowed.allows_port(8010));
+        assert!(!narrowed.allows_port(22) && !narrowed.allows_port(8050));
+    }
+}
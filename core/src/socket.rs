@@ -0,0 +1,223 @@
+//! Outbound sockets bound to a chosen source address, interface and source port range.
+//!
+//! Modules open their TCP and UDP sockets through the process-wide [`Binding`] returned by
+//! [`global`], and bind raw sockets with [`Binding::bind_raw`]. Without an [`install`]ed one the
+//! kernel picks the source address, interface and port as usual. Interface binding uses
+//! `SO_BINDTODEVICE` and is only available on Linux.
+
+use socket2::{Domain, Protocol, Socket, Type};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use thiserror::Error;
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+
+#[derive(Debug, Error)]
+pub enum BindError {
+    #[error("invalid source binding {0}: {1}")]
+    Invalid(String, &'static str),
+    #[error("source binding already installed")]
+    AlreadyInstalled,
+}
+
+/// Where outbound traffic leaves from. Unset fields are left to the kernel.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Binding {
+    pub source_v4: Option<Ipv4Addr>,
+    pub source_v6: Option<Ipv6Addr>,
+    /// Interface name for `SO_BINDTODEVICE`.
+    pub interface: Option<String>,
+    /// Inclusive source port range; each socket takes the next free port in it.
+    pub ports: Option<(u16, u16)>,
+}
+
+/// Rotates through the source port range so consecutive sockets start at different ports.
+static NEXT_PORT: AtomicU32 = AtomicU32::new(0);
+
+impl Binding {
+    /// At most one source address per family, an interface name and a port or `lo-hi` range.
+    pub fn new(sources: &[IpAddr], interface: Option<&str>, ports: Option<&str>) -> Result<Self, BindError> {
+        let mut b = Binding::default();
+        for ip in sources {
+            let taken = match ip {
+                IpAddr::V4(v4) => b.source_v4.replace(*v4).is_some(),
+                IpAddr::V6(v6) => b.source_v6.replace(*v6).is_some(),
+            };
+            if taken { return Err(BindError::Invalid(ip.to_string(), "one source address per family")); }
+        }
+        if let Some(name) = interface.map(str::trim).filter(|n| !n.is_empty()) {
+            if cfg!(not(target_os = "linux")) { return Err(BindError::Invalid(name.into(), "interface binding needs Linux")); }
+            if name.len() >= 16 { return Err(BindError::Invalid(name.into(), "interface names are at most 15 bytes")); }
+            b.interface = Some(name.to_string());
+        }
+        if let Some(spec) = ports {
+            let bad = || BindError::Invalid(spec.to_string(), "source ports must be PORT or LO-HI");
+            let (lo, hi) = spec.trim().split_once('-').unwrap_or((spec.trim(), spec.trim()));
+            let (lo, hi) = (lo.trim().parse::<u16>().map_err(|_| bad())?, hi.trim().parse::<u16>().map_err(|_| bad())?);
+            if lo == 0 || lo > hi { return Err(bad()); }
+            b.ports = Some((lo, hi));
+        }
+        Ok(b)
+    }
+
+    /// Whether everything is left to the kernel.
+    pub fn is_default(&self) -> bool {
+        *self == Binding::default()
+    }
+
+    /// Configured source address for `dst`'s family.
+    pub fn source(&self, dst: IpAddr) -> Option<IpAddr> {
+        match dst {
+            IpAddr::V4(_) => self.source_v4.map(IpAddr::V4),
+            IpAddr::V6(_) => self.source_v6.map(IpAddr::V6),
+        }
+    }
+
+    /// Source address packets to `dst` leave from: the configured one, or the one the kernel
+    /// routes from (through the bound interface, if any). For raw sockets that build headers.
+    pub fn source_for(&self, dst: IpAddr) -> io::Result<IpAddr> {
+        if let Some(ip) = self.source(dst) { return Ok(ip); }
+        let sock = Socket::new(Domain::for_address(SocketAddr::new(dst, 0)), Type::DGRAM, Some(Protocol::UDP))?;
+        self.bind_device(&sock)?;
+        sock.connect(&SocketAddr::new(dst, 9).into())?;
+        sock.local_addr()?.as_socket().map(|a| a.ip()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no local address"))
+    }
+
+    /// Bind a raw (or ping) socket to the interface and source address; ports do not apply.
+    pub fn bind_raw(&self, sock: &Socket, v6: bool) -> io::Result<()> {
+        self.bind_device(sock)?;
+        let ip = if v6 { self.source_v6.map(IpAddr::V6) } else { self.source_v4.map(IpAddr::V4) };
+        if let Some(ip) = ip { sock.bind(&SocketAddr::new(ip, 0).into())?; }
+        Ok(())
+    }
+
+    /// Bind `sock` to the interface, source address (or the unspecified one) and the next free port
+    /// of the range (an ephemeral port without one). Fails with `AddrInUse` when every port of the range is taken.
+    pub fn bind(&self, sock: &Socket, v6: bool) -> io::Result<()> {
+        self.bind_device(sock)?;
+        let ip = match (v6, self.source_v4, self.source_v6) {
+            (false, Some(v4), _) => IpAddr::V4(v4),
+            (true, _, Some(v6)) => IpAddr::V6(v6),
+            (false, None, _) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            (true, _, None) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let Some((lo, hi)) = self.ports else { return sock.bind(&SocketAddr::new(ip, 0).into()) };
+        // Lets a TCP port whose last connection is in TIME_WAIT be reused; on UDP it would let two
+        // sockets share a port, so a busy one would never be skipped.
+        if sock.r#type()? == Type::STREAM { sock.set_reuse_address(true)?; }
+        let span = (hi - lo) as u32 + 1;
+        let start = NEXT_PORT.fetch_add(1, Ordering::Relaxed);
+        for i in 0..span {
+            let port = lo + ((start.wrapping_add(i)) % span) as u16;
+            match sock.bind(&SocketAddr::new(ip, port).into()) {
+                Err(e) if e.kind() == io::ErrorKind::AddrInUse => continue,
+                r => return r,
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::AddrInUse, format!("no free source port in {}-{}", lo, hi)))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn bind_device(&self, sock: &Socket) -> io::Result<()> {
+        match &self.interface {
+            Some(name) => sock.bind_device(Some(name.as_bytes())),
+            None => Ok(()),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn bind_device(&self, _sock: &Socket) -> io::Result<()> {
+        match &self.interface {
+            Some(_) => Err(io::Error::new(io::ErrorKind::Unsupported, "interface binding needs Linux")),
+            None => Ok(()),
+        }
+    }
+
+    /// Open a TCP connection to `dst` from the configured source. With a port range, a port
+    /// whose 4-tuple is still in use is skipped for the next one.
+    pub async fn connect(&self, dst: SocketAddr) -> io::Result<TcpStream> {
+        if self.is_default() { return TcpStream::connect(dst).await; }
+        let tries = self.ports.map_or(1, |(lo, hi)| (hi - lo) as u32 + 1);
+        let mut last = None;
+        for _ in 0..tries {
+            let sock = Socket::new(Domain::for_address(dst), Type::STREAM, Some(Protocol::TCP))?;
+            sock.set_nonblocking(true)?;
+            self.bind(&sock, dst.is_ipv6())?;
+            match TcpSocket::from_std_stream(sock.into()).connect(dst).await {
+                Err(e) if self.ports.is_some() && matches!(e.kind(), io::ErrorKind::AddrInUse | io::ErrorKind::AddrNotAvailable) => last = Some(e),
+                r => return r,
+            }
+        }
+        Err(last.unwrap_or_else(|| io::Error::new(io::ErrorKind::AddrInUse, "no free source port")))
+    }
+
+    /// A UDP socket of `dst`'s family bound to the configured source.
+    pub fn udp(&self, dst: &SocketAddr) -> io::Result<UdpSocket> {
+        let sock = Socket::new(Domain::for_address(*dst), Type::DGRAM, Some(Protocol::UDP))?;
+        sock.set_nonblocking(true)?;
+        self.bind(&sock, dst.is_ipv6())?;
+        UdpSocket::from_std(sock.into())
+    }
+}
+
+static GLOBAL: OnceLock<Binding> = OnceLock::new();
+static DEFAULT: Binding = Binding { source_v4: None, source_v6: None, interface: None, ports: None };
+
+/// Install the process-wide binding.
+pub fn install(binding: Binding) -> Result<(), BindError> {
+    GLOBAL.set(binding).map_err(|_| BindError::AlreadyInstalled)
+}
+
+/// The installed binding, or one leaving everything to the kernel.
+pub fn global() -> &'static Binding {
+    GLOBAL.get().unwrap_or(&DEFAULT)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn parses_bindings() {
+        let b = Binding::new(&["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()], None, Some("40000-40009")).unwrap();
+        assert_eq!(b.source("192.0.2.1".parse().unwrap()), Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(b.ports, Some((40000, 40009)));
+        assert_eq!(Binding::new(&[], None, Some("5353")).unwrap().ports, Some((5353, 5353)));
+        assert!(Binding::new(&["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()], None, None).is_err());
+        assert!(Binding::new(&[], None, Some("9-1")).is_err());
+        assert!(Binding::new(&[], None, Some("0")).is_err());
+        assert!(Binding::new(&[], Some(""), None).unwrap().is_default());
+    }
+
+    #[tokio::test]
+    async fn connects_and_sends_from_the_bound_source() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dst = listener.local_addr().unwrap();
+        let free = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let b = Binding::new(&["127.0.0.1".parse().unwrap()], None, Some(&format!("{0}-{0}", free))).unwrap();
+        let stream = b.connect(dst).await.unwrap();
+        assert_eq!(stream.local_addr().unwrap(), SocketAddr::new("127.0.0.1".parse().unwrap(), free));
+        let (accepted, from) = listener.accept().await.unwrap();
+        assert_eq!(from.port(), free);
+        let udp = b.udp(&dst).unwrap();
+        assert_eq!(udp.local_addr().unwrap().port(), free);
+        drop((stream, accepted, udp));
+
+        assert_eq!(b.source_for("127.0.0.2".parse().unwrap()).unwrap(), "127.0.0.1".parse::<IpAddr>().unwrap());
+        assert!(Binding::default().source_for("127.0.0.1".parse().unwrap()).unwrap().is_loopback());
+    }
+
+    #[test]
+    fn udp_skips_ports_in_use() {
+        // Even a holder that allows address reuse keeps the port to itself.
+        let busy = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).unwrap();
+        busy.set_reuse_address(true).unwrap();
+        busy.bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into()).unwrap();
+        let port = busy.local_addr().unwrap().as_socket().unwrap().port();
+        let b = Binding::new(&["127.0.0.1".parse().unwrap()], None, Some(&port.to_string())).unwrap();
+        let dst = SocketAddr::new("127.0.0.1".parse().unwrap(), 9);
+        assert_eq!(b.udp(&dst).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+    }
+}
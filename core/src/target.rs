@@ -0,0 +1,702 @@
+//! Target specification grammar and lazy expansion.
+//!
+//! Accepted forms (one per argument or file line):
+//! - `192.0.2.10`, `2001:db8::1`, `[2001:db8::1]` — address literals
+//! - `host.example.com` — hostname (resolved through [`crate::dns`])
+//! - `10.0.0.0/24`, `2001:db8::/120` — CIDR blocks
+//! - `10.0.0.1-50`, `10.0.0.1-10.0.1.20` — dash ranges (last octet or full end address)
+//! - `host:8080`, `[2001:db8::1]:8443` — host with an explicit port
+//! - `https://host[:port]/path` — full URLs
+//! - `@targets.txt` — newline-delimited include (blank lines and `#` comments skipped); an `@file`
+//!   inside an include resolves relative to the including file
+//!
+//! IPv6 blocks and ranges are limited to [`MAX_V6_SPAN`] addresses; wider IPv6 space should be
+//! given as a hitlist file of addresses or small prefixes.
+
+use ipnet::{IpAddrRange, IpNet, Ipv4AddrRange, Ipv6AddrRange};
+use std::fmt;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TargetError {
+    #[error("invalid target: {0}")]
+    Invalid(String),
+    #[error("invalid address range: {0}")]
+    Range(String),
+    #[error("failed to read target file {path}: {source}")]
+    Include { path: PathBuf, source: std::io::Error },
+    #[error("target file {0} includes itself")]
+    IncludeCycle(PathBuf),
+    #[error("target file {0} nested more than {MAX_INCLUDE_DEPTH} includes deep")]
+    IncludeDepth(PathBuf),
+    #[error("target set too large to index")]
+    TooLarge,
+    #[error("IPv6 block too large to enumerate: {0} (at most {MAX_V6_SPAN} addresses, e.g. a /112; use a hitlist file for sparse space)")]
+    TooBroad(String),
+}
+
+/// Deepest chain of `@file` includes followed before giving up.
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Largest IPv6 CIDR block or range (in addresses) that a spec may expand to.
+pub const MAX_V6_SPAN: u128 = 1 << 16;
+
+/// Address family restriction (`-4`/`-6`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Family {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl Family {
+    pub fn from_flags(v4_only: bool, v6_only: bool) -> Self {
+        match (v4_only, v6_only) {
+            (true, false) => Family::V4,
+            (false, true) => Family::V6,
+            _ => Family::Any,
+        }
+    }
+
+    pub fn allows(&self, ip: &IpAddr) -> bool {
+        match self {
+            Family::Any => true,
+            Family::V4 => ip.is_ipv4(),
+            Family::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self { Family::Any => "any", Family::V4 => "IPv4", Family::V6 => "IPv6" })
+    }
+}
+
+/// Host part of a target: either a literal address or a name that still needs resolving.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Host {
+    Addr(IpAddr),
+    Name(String),
+}
+
+impl Host {
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Host::Addr(ip) => Some(*ip),
+            Host::Name(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Host::Addr(ip) => write!(f, "{}", ip),
+            Host::Name(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl FromStr for Host {
+    type Err = TargetError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let bare = s.strip_prefix('[').and_then(|r| r.strip_suffix(']')).unwrap_or(s);
+        if let Ok(ip) = bare.parse::<IpAddr>() {
+            return Ok(Host::Addr(ip));
+        }
+        if is_hostname(s) { Ok(Host::Name(s.to_ascii_lowercase())) } else { Err(TargetError::Invalid(s.to_string())) }
+    }
+}
+
+/// A single expanded target as handed to a module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Target {
+    pub host: Host,
+    /// Explicit port from `host:port` or a URL; modules fall back to their own port list.
+    pub port: Option<u16>,
+    /// Original URL when the target was given as one.
+    pub url: Option<String>,
+}
+
+impl Target {
+    pub fn new(host: Host, port: Option<u16>) -> Self {
+        Target { host, port, url: None }
+    }
+
+    /// Ports to probe: the explicit port if present, otherwise `default`.
+    pub fn ports_or(&self, default: &[u16]) -> Vec<u16> {
+        match self.port {
+            Some(p) => vec![p],
+            None => default.to_vec(),
+        }
+    }
+
+    /// Host formatted for use in an authority (`host:port`), bracketing IPv6 literals.
+    pub fn authority_host(&self) -> String {
+        match &self.host {
+            Host::Addr(IpAddr::V6(v6)) => format!("[{}]", v6),
+            h => h.to_string(),
+        }
+    }
+}
+
+impl From<IpAddr> for Target {
+    fn from(ip: IpAddr) -> Self {
+        Target::new(Host::Addr(ip), None)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(u) = &self.url { return write!(f, "{}", u); }
+        match self.port {
+            Some(p) => write!(f, "{}:{}", self.authority_host(), p),
+            None => write!(f, "{}", self.host),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = TargetError;
+    /// Parse a spec that must denote exactly one target (no CIDR, range or include).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<TargetSpec>()? {
+            TargetSpec::Single(t) => Ok(t),
+            _ => Err(TargetError::Invalid(format!("{} (expected a single host)", s.trim()))),
+        }
+    }
+}
+
+/// One parsed target specification; expands lazily into [`Target`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetSpec {
+    Single(Target),
+    Cidr(IpNet),
+    Range { start: IpAddr, end: IpAddr },
+    Include { path: PathBuf, specs: Vec<TargetSpec> },
+}
+
+impl TargetSpec {
+    /// True if this spec denotes exactly one host.
+    pub fn is_single(&self) -> bool {
+        matches!(self, TargetSpec::Single(_))
+    }
+
+    /// Lazily expand into individual targets. CIDRs skip network/broadcast addresses like
+    /// [`IpNet::hosts`].
+    pub fn expand(&self) -> Targets {
+        let it: Box<dyn Iterator<Item = Target> + Send> = match self {
+            TargetSpec::Single(t) => Box::new(std::iter::once(t.clone())),
+            TargetSpec::Cidr(net) => Box::new(net.hosts().map(Target::from)),
+            TargetSpec::Range { start, end } => {
+                let range = match (start, end) {
+                    (IpAddr::V4(s), IpAddr::V4(e)) => IpAddrRange::from(Ipv4AddrRange::new(*s, *e)),
+                    (IpAddr::V6(s), IpAddr::V6(e)) => IpAddrRange::from(Ipv6AddrRange::new(*s, *e)),
+                    _ => unreachable!("range families are checked at parse time"),
+                };
+                Box::new(range.map(Target::from))
+            }
+            TargetSpec::Include { specs, .. } => {
+                let specs = specs.clone();
+                Box::new(specs.into_iter().flat_map(|s| s.expand()))
+            }
+        };
+        Targets { inner: it }
+    }
+
+    fn parse_range(s: &str, left: &str, right: &str) -> Result<Self, TargetError> {
+        let (start, end) = range_bounds(s, left, right)?;
+        if let (IpAddr::V6(a), IpAddr::V6(b)) = (start, end) {
+            if u128::from(b) - u128::from(a) >= MAX_V6_SPAN { return Err(TargetError::TooBroad(s.to_string())); }
+        }
+        Ok(TargetSpec::Range { start, end })
+    }
+
+    fn parse_include(path: &str, chain: &mut IncludeChain) -> Result<Self, TargetError> {
+        let (path, text) = chain.open(path)?;
+        let specs: Result<Vec<_>, _> = spec_lines(&text).map(|l| match l.strip_prefix('@') {
+            Some(inner) => TargetSpec::parse_include(inner, chain),
+            None => l.parse(),
+        }).collect();
+        chain.close();
+        Ok(TargetSpec::Include { path, specs: specs? })
+    }
+}
+
+/// Include files being expanded, outermost first, so nested `@file`s resolve against the file that
+/// names them and cycles or runaway nesting fail instead of recursing forever.
+#[derive(Debug, Default)]
+struct IncludeChain(Vec<PathBuf>);
+
+impl IncludeChain {
+    /// Read the file an `@path` names and push it onto the chain; pair with [`IncludeChain::close`].
+    fn open(&mut self, path: &str) -> Result<(PathBuf, String), TargetError> {
+        let path = match self.0.last().and_then(|f| f.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let read_err = |source| TargetError::Include { path: path.clone(), source };
+        let canonical = fs::canonicalize(&path).map_err(read_err)?;
+        if self.0.contains(&canonical) { return Err(TargetError::IncludeCycle(path)); }
+        if self.0.len() >= MAX_INCLUDE_DEPTH { return Err(TargetError::IncludeDepth(path)); }
+        let text = fs::read_to_string(&canonical).map_err(read_err)?;
+        self.0.push(canonical);
+        Ok((path, text))
+    }
+
+    fn close(&mut self) {
+        self.0.pop();
+    }
+}
+
+impl FromStr for TargetSpec {
+    type Err = TargetError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(TargetError::Invalid(s.to_string()));
+        }
+        if let Some(path) = s.strip_prefix('@') {
+            return TargetSpec::parse_include(path, &mut IncludeChain::default());
+        }
+        if s.contains("://") {
+            return parse_url(s).map(TargetSpec::Single);
+        }
+        if s.contains('/') {
+            let net: IpNet = s.parse().map_err(|_| TargetError::Invalid(s.to_string()))?;
+            if let IpNet::V6(v6) = net {
+                match 1u128.checked_shl(128 - v6.prefix_len() as u32) {
+                    Some(n) if n <= MAX_V6_SPAN => {}
+                    _ => return Err(TargetError::TooBroad(s.to_string())),
+                }
+            }
+            return Ok(TargetSpec::Cidr(net));
+        }
+        if let Ok(ip) = s.parse::<IpAddr>() {
+            return Ok(TargetSpec::Single(Target::from(ip)));
+        }
+        if let Some(rest) = s.strip_prefix('[') {
+            // [v6] or [v6]:port
+            let (addr, tail) = rest.split_once(']').ok_or_else(|| TargetError::Invalid(s.to_string()))?;
+            let ip: IpAddr = addr.parse().map_err(|_| TargetError::Invalid(s.to_string()))?;
+            let port = match tail {
+                "" => None,
+                t => Some(parse_port(s, t.strip_prefix(':').unwrap_or(t))?),
+            };
+            return Ok(TargetSpec::Single(Target::new(Host::Addr(ip), port)));
+        }
+        if let Some((left, right)) = s.split_once('-') {
+            if left.parse::<IpAddr>().is_ok() {
+                return TargetSpec::parse_range(s, left, right);
+            }
+        }
+        if let Some((host, port)) = s.rsplit_once(':') {
+            let port = parse_port(s, port)?;
+            return Ok(TargetSpec::Single(Target::new(host.parse()?, Some(port))));
+        }
+        Ok(TargetSpec::Single(Target::new(s.parse()?, None)))
+    }
+}
+
+/// Lazy iterator over expanded targets.
+pub struct Targets {
+    inner: Box<dyn Iterator<Item = Target> + Send>,
+}
+
+impl Iterator for Targets {
+    type Item = Target;
+    fn next(&mut self) -> Option<Target> {
+        self.inner.next()
+    }
+}
+
+/// An ordered set of specs, e.g. everything given on the command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetSet {
+    pub specs: Vec<TargetSpec>,
+}
+
+impl TargetSet {
+    pub fn parse<I, S>(items: I) -> Result<Self, TargetError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let specs = items.into_iter().map(|s| s.as_ref().parse()).collect::<Result<Vec<_>, _>>()?;
+        Ok(TargetSet { specs })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// True if the whole set denotes exactly one host.
+    pub fn is_single(&self) -> bool {
+        self.specs.len() == 1 && self.specs[0].is_single()
+    }
+
+    /// Lazily expand every spec in order.
+    pub fn expand(&self) -> Targets {
+        let specs = self.specs.clone();
+        Targets { inner: Box::new(specs.into_iter().flat_map(|s| s.expand())) }
+    }
+
+    /// Random access over the expansion (same order as [`TargetSet::expand`]).
+    pub fn index(&self) -> Result<TargetIndex, TargetError> {
+        let mut idx = TargetIndex { leaves: Vec::new(), len: 0 };
+        for spec in &self.specs { idx.push(spec)?; }
+        Ok(idx)
+    }
+
+    /// BLAKE3 hash (hex) of the specs in order, with includes replaced by the specs read from
+    /// them: equal for sets listing the same specs in the same order, whatever the include files
+    /// are called.
+    pub fn fingerprint(&self) -> String {
+        fn feed(h: &mut blake3::Hasher, spec: &TargetSpec) {
+            let line = match spec {
+                TargetSpec::Single(t) => t.to_string(),
+                TargetSpec::Cidr(net) => net.to_string(),
+                TargetSpec::Range { start, end } => format!("{}-{}", start, end),
+                TargetSpec::Include { specs, .. } => return specs.iter().for_each(|s| feed(h, s)),
+            };
+            h.update(line.as_bytes());
+            h.update(b"\n");
+        }
+        let mut h = blake3::Hasher::new();
+        self.specs.iter().for_each(|s| feed(&mut h, s));
+        h.finalize().to_hex().to_string()
+    }
+}
+
+/// Addresses to leave out of an expansion (`--exclude`): address literals, CIDR blocks of any
+/// size, dash ranges and `@file` includes. Hostnames are rejected; names are checked by the
+/// addresses they resolve to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exclusions {
+    /// Sorted, non-overlapping inclusive ranges (IPv4 addresses widened to u128).
+    v4: Vec<(u128, u128)>,
+    v6: Vec<(u128, u128)>,
+}
+
+impl Exclusions {
+    pub fn parse<I, S>(items: I) -> Result<Self, TargetError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut ex = Exclusions::default();
+        for item in items { ex.add(item.as_ref(), &mut IncludeChain::default())?; }
+        ex.v4 = merge(std::mem::take(&mut ex.v4));
+        ex.v6 = merge(std::mem::take(&mut ex.v6));
+        Ok(ex)
+    }
+
+    fn add(&mut self, s: &str, chain: &mut IncludeChain) -> Result<(), TargetError> {
+        let s = s.trim();
+        if let Some(path) = s.strip_prefix('@') {
+            let (_, text) = chain.open(path)?;
+            let added = spec_lines(&text).try_for_each(|line| self.add(line, chain));
+            chain.close();
+            return added;
+        }
+        let (start, end) = if s.contains('/') {
+            let net: IpNet = s.parse().map_err(|_| TargetError::Invalid(s.to_string()))?;
+            (net.network(), net.broadcast())
+        } else if let Ok(ip) = s.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+            (ip, ip)
+        } else if let Some((left, right)) = s.split_once('-') {
+            range_bounds(s, left, right)?
+        } else {
+            return Err(TargetError::Invalid(s.to_string()));
+        };
+        match (start, end) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => self.v4.push((u32::from(a).into(), u32::from(b).into())),
+            (IpAddr::V6(a), IpAddr::V6(b)) => self.v6.push((a.into(), b.into())),
+            _ => unreachable!("bounds share a family"),
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        fn within(ranges: &[(u128, u128)], x: u128) -> bool {
+            let k = ranges.partition_point(|(start, _)| *start <= x);
+            k > 0 && x <= ranges[k - 1].1
+        }
+        match ip {
+            IpAddr::V4(v4) => within(&self.v4, u32::from(*v4).into()),
+            IpAddr::V6(v6) => within(&self.v6, u128::from(*v6)),
+        }
+    }
+
+    /// True if `target` is an address literal inside an excluded range.
+    pub fn excludes(&self, target: &Target) -> bool {
+        matches!(target.host.ip(), Some(ip) if self.contains(&ip))
+    }
+}
+
+/// Sort ranges and merge overlapping or adjacent ones.
+fn merge(mut ranges: Vec<(u128, u128)>) -> Vec<(u128, u128)> {
+    ranges.sort();
+    let mut out: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+    for (a, b) in ranges {
+        match out.last_mut() {
+            Some(last) if a <= last.1.saturating_add(1) => last.1 = last.1.max(b),
+            _ => out.push((a, b)),
+        }
+    }
+    out
+}
+
+/// Address span of one spec: `len` consecutive addresses from `start`.
+#[derive(Debug, Clone)]
+enum Leaf {
+    Single(Target),
+    V4 { start: u32 },
+    V6 { start: u128 },
+}
+
+/// Index-addressable view of a [`TargetSet`]; memory is per spec, not per target.
+#[derive(Debug, Clone)]
+pub struct TargetIndex {
+    /// Offset of each leaf's first target, ascending.
+    leaves: Vec<(u64, Leaf)>,
+    len: u64,
+}
+
+impl TargetIndex {
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The `i`-th target, or `None` past the end.
+    pub fn get(&self, i: u64) -> Option<Target> {
+        if i >= self.len { return None; }
+        let k = self.leaves.partition_point(|(off, _)| *off <= i) - 1;
+        let (off, leaf) = &self.leaves[k];
+        let d = i - off;
+        Some(match leaf {
+            Leaf::Single(t) => t.clone(),
+            Leaf::V4 { start } => Target::from(IpAddr::V4(Ipv4Addr::from(start + d as u32))),
+            Leaf::V6 { start } => Target::from(IpAddr::V6(Ipv6Addr::from(start + d as u128))),
+        })
+    }
+
+    fn push(&mut self, spec: &TargetSpec) -> Result<(), TargetError> {
+        let (leaf, n) = match spec {
+            TargetSpec::Single(t) => (Leaf::Single(t.clone()), 1u128),
+            TargetSpec::Cidr(net) => span(net.hosts().next(), net.hosts().next_back()),
+            TargetSpec::Range { start, end } => span(Some(*start), Some(*end)),
+            TargetSpec::Include { specs, .. } => {
+                for s in specs { self.push(s)?; }
+                return Ok(());
+            }
+        };
+        if n == 0 { return Ok(()); }
+        let n = u64::try_from(n).map_err(|_| TargetError::TooLarge)?;
+        self.leaves.push((self.len, leaf));
+        self.len = self.len.checked_add(n).ok_or(TargetError::TooLarge)?;
+        Ok(())
+    }
+}
+
+fn span(first: Option<IpAddr>, last: Option<IpAddr>) -> (Leaf, u128) {
+    match (first, last) {
+        (Some(IpAddr::V4(a)), Some(IpAddr::V4(b))) => (Leaf::V4 { start: a.into() }, (u32::from(b) - u32::from(a)) as u128 + 1),
+        (Some(IpAddr::V6(a)), Some(IpAddr::V6(b))) => {
+            let n = (u128::from(b) - u128::from(a)).saturating_add(1);
+            (Leaf::V6 { start: a.into() }, n)
+        }
+        _ => (Leaf::V4 { start: 0 }, 0),
+    }
+}
+
+/// Parse newline-delimited specs, skipping blanks and `#` comments.
+pub fn parse_lines(text: &str) -> Result<Vec<TargetSpec>, TargetError> {
+    spec_lines(text).map(str::parse).collect()
+}
+
+fn spec_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+/// Endpoints of a dash range (`10.0.0.1-50` or `start-end`), in order and of one family.
+fn range_bounds(s: &str, left: &str, right: &str) -> Result<(IpAddr, IpAddr), TargetError> {
+    let start: IpAddr = left.parse().map_err(|_| TargetError::Range(s.to_string()))?;
+    let end: IpAddr = match (start, right.parse::<IpAddr>()) {
+        (_, Ok(end)) => end,
+        (IpAddr::V4(v4), Err(_)) => {
+            let last: u8 = right.parse().map_err(|_| TargetError::Range(s.to_string()))?;
+            let o = v4.octets();
+            IpAddr::V4(Ipv4Addr::new(o[0], o[1], o[2], last))
+        }
+        (IpAddr::V6(_), Err(_)) => return Err(TargetError::Range(s.to_string())),
+    };
+    if start.is_ipv4() != end.is_ipv4() || start > end {
+        return Err(TargetError::Range(s.to_string()));
+    }
+    Ok((start, end))
+}
+
+fn parse_port(spec: &str, p: &str) -> Result<u16, TargetError> {
+    match p.parse::<u16>() {
+        Ok(port) if port != 0 => Ok(port),
+        _ => Err(TargetError::Invalid(spec.to_string())),
+    }
+}
+
+fn parse_url(s: &str) -> Result<Target, TargetError> {
+    let url = url::Url::parse(s).map_err(|_| TargetError::Invalid(s.to_string()))?;
+    let host = match url.host() {
+        Some(url::Host::Ipv4(v4)) => Host::Addr(IpAddr::V4(v4)),
+        Some(url::Host::Ipv6(v6)) => Host::Addr(IpAddr::V6(v6)),
+        Some(url::Host::Domain(d)) => Host::Name(d.to_ascii_lowercase()),
+        None => return Err(TargetError::Invalid(s.to_string())),
+    };
+    Ok(Target { host, port: url.port_or_known_default(), url: Some(url.to_string()) })
+}
+
+fn is_hostname(s: &str) -> bool {
+    !s.is_empty()
+        && s.len() <= 253
+        && s.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_single_forms() {
+        let t: Target = "192.0.2.1".parse().unwrap();
+        assert_eq!(t.host, Host::Addr("192.0.2.1".parse().unwrap()));
+        let t: Target = "[2001:db8::1]:8443".parse().unwrap();
+        assert_eq!(t.port, Some(8443));
+        assert_eq!(t.to_string(), "[2001:db8::1]:8443");
+        let t: Target = "Example.com:80".parse().unwrap();
+        assert_eq!(t.host, Host::Name("example.com".into()));
+        let t: Target = "https://example.com/login".parse().unwrap();
+        assert_eq!(t.port, Some(443));
+        assert_eq!(t.url.as_deref(), Some("https://example.com/login"));
+    }
+
+    #[test]
+    fn expand_cidr_and_ranges() {
+        let v: Vec<_> = "10.0.0.0/30".parse::<TargetSpec>().unwrap().expand().map(|t| t.to_string()).collect();
+        assert_eq!(v, vec!["10.0.0.1", "10.0.0.2"]);
+        assert_eq!("10.0.0.1-50".parse::<TargetSpec>().unwrap().expand().count(), 50);
+        assert_eq!("10.0.0.250-10.0.1.5".parse::<TargetSpec>().unwrap().expand().count(), 12);
+        assert!("10.0.0.9-3".parse::<TargetSpec>().is_err());
+        assert!("10.0.0.1-::1".parse::<TargetSpec>().is_err());
+    }
+
+    #[test]
+    fn fingerprint_covers_included_specs() {
+        let path = std::env::temp_dir().join(format!("toolbox-fingerprint-{}.txt", std::process::id()));
+        std::fs::write(&path, "10.0.0.1\n10.0.0.0/30\n").unwrap();
+        let included = TargetSet::parse([format!("@{}", path.display()), "host:80".into()]).unwrap();
+        let inline = TargetSet::parse(["10.0.0.1", "10.0.0.0/30", "host:80"]).unwrap();
+        assert_eq!(included.fingerprint(), inline.fingerprint());
+        assert_ne!(inline.fingerprint(), TargetSet::parse(["10.0.0.0/30", "10.0.0.1", "host:80"]).unwrap().fingerprint());
+        std::fs::write(&path, "10.0.0.2\n10.0.0.0/30\n").unwrap();
+        let changed = TargetSet::parse([format!("@{}", path.display()), "host:80".into()]).unwrap();
+        assert_ne!(included.fingerprint(), changed.fingerprint());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn exclusions_filter_expansion() {
+        let ex = Exclusions::parse(["10.0.0.0/30", "10.0.0.4", "10.0.0.6-7", "2001:db8::/32"]).unwrap();
+        let set = TargetSet::parse(["10.0.0.0/28", "2001:db8::1"]).unwrap();
+        let v: Vec<_> = set.expand().filter(|t| !ex.excludes(t)).map(|t| t.to_string()).collect();
+        assert_eq!(v, vec!["10.0.0.5", "10.0.0.8", "10.0.0.9", "10.0.0.10", "10.0.0.11", "10.0.0.12", "10.0.0.13", "10.0.0.14"]);
+        assert_eq!(ex.v4.len(), 2, "adjacent ranges merge");
+        assert!(Exclusions::parse(["example.com"]).is_err());
+    }
+
+    #[test]
+    fn reject_garbage() {
+        assert!("bad host".parse::<TargetSpec>().is_err());
+        assert!("host:0".parse::<TargetSpec>().is_err());
+        assert!("10.0.0.0/33".parse::<TargetSpec>().is_err());
+        assert!("10.0.0.0/24".parse::<Target>().is_err());
+    }
+
+    #[test]
+    fn include_file() {
+        let path = std::env::temp_dir().join(format!("toolbox-targets-{}.txt", std::process::id()));
+        fs::write(&path, "# lab\n192.0.2.1\n\nweb.local:8080\n192.0.2.8/31\n").unwrap();
+        let set = TargetSet::parse([format!("@{}", path.display())]).unwrap();
+        let v: Vec<_> = set.expand().map(|t| t.to_string()).collect();
+        assert_eq!(v, vec!["192.0.2.1", "web.local:8080", "192.0.2.8", "192.0.2.9"]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn nested_includes_resolve_beside_their_file() {
+        let dir = std::env::temp_dir().join(format!("toolbox-nested-{}", std::process::id()));
+        fs::create_dir_all(dir.join("lab")).unwrap();
+        fs::write(dir.join("all.txt"), "@lab/hosts.txt\n192.0.2.1\n").unwrap();
+        fs::write(dir.join("lab/hosts.txt"), "@more.txt\n").unwrap();
+        fs::write(dir.join("lab/more.txt"), "192.0.2.2\n").unwrap();
+        let set = TargetSet::parse([format!("@{}", dir.join("all.txt").display())]).unwrap();
+        let v: Vec<_> = set.expand().map(|t| t.to_string()).collect();
+        assert_eq!(v, vec!["192.0.2.2", "192.0.2.1"]);
+        let ex = Exclusions::parse([format!("@{}", dir.join("all.txt").display())]).unwrap();
+        assert!(ex.contains(&"192.0.2.2".parse().unwrap()));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let dir = std::env::temp_dir().join(format!("toolbox-cycle-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("self.txt"), "192.0.2.1\n@self.txt\n").unwrap();
+        fs::write(dir.join("a.txt"), "@b.txt\n").unwrap();
+        fs::write(dir.join("b.txt"), "@./a.txt\n").unwrap();
+        for name in ["self.txt", "a.txt"] {
+            let spec = format!("@{}", dir.join(name).display());
+            assert!(matches!(spec.parse::<TargetSpec>(), Err(TargetError::IncludeCycle(_))), "{}", name);
+            assert!(matches!(Exclusions::parse([&spec]), Err(TargetError::IncludeCycle(_))), "{}", name);
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn index_matches_expand() {
+        let set = TargetSet::parse(["10.0.0.0/30", "web.local:8080", "10.0.1.254-10.0.2.1", "2001:db8::/126"]).unwrap();
+        let idx = set.index().unwrap();
+        let all: Vec<_> = set.expand().collect();
+        assert_eq!(idx.len(), all.len() as u64);
+        for (i, t) in all.iter().enumerate() { assert_eq!(idx.get(i as u64).as_ref(), Some(t)); }
+        assert!(idx.get(idx.len()).is_none());
+    }
+
+    #[test]
+    fn ipv6_blocks_are_bounded() {
+        assert_eq!("2001:db8::/112".parse::<TargetSpec>().unwrap().expand().count() as u128, MAX_V6_SPAN);
+        assert!(matches!("2001:db8::/64".parse::<TargetSpec>(), Err(TargetError::TooBroad(_))));
+        assert!(matches!("::/0".parse::<TargetSpec>(), Err(TargetError::TooBroad(_))));
+        assert!("2001:db8::1-2001:db8::ffff".parse::<TargetSpec>().is_ok());
+        assert!(matches!("2001:db8::-2001:db8::1:0".parse::<TargetSpec>(), Err(TargetError::TooBroad(_))));
+    }
+}
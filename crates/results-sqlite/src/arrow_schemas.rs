@@ -12,6 +12,7 @@ pub fn ports_schema() -> Schema {
         Field::new("confidence", DataType::Float64, false),
         Field::new("first_seen_ms", DataType::Int64, false),
         Field::new("last_seen_ms", DataType::Int64, false),
+        Field::new("rtt_us", DataType::Int64, true),
     ])
 }
 
@@ -58,6 +58,7 @@ struct RowBatch {
     p_confidence: Float64Builder,
     p_first: Int64Builder,
     p_last: Int64Builder,
+    p_rtt: Int64Builder,
     // Http
     h_http_id: Int64Builder,
     h_port_id: Int64Builder,
@@ -80,11 +81,11 @@ impl RowBatch {
     fn new(kind: &TableKind) -> Self {
         match kind {
             TableKind::Ports => RowBatch {
-                p_port_id: Int64Builder::new(), p_host_id: Int64Builder::new(), p_transport: StringBuilder::new(), p_port: Int64Builder::new(), p_state: StringBuilder::new(), p_reason: StringBuilder::new(), p_service_name: StringBuilder::new(), p_confidence: Float64Builder::new(), p_first: Int64Builder::new(), p_last: Int64Builder::new(),
+                p_port_id: Int64Builder::new(), p_host_id: Int64Builder::new(), p_transport: StringBuilder::new(), p_port: Int64Builder::new(), p_state: StringBuilder::new(), p_reason: StringBuilder::new(), p_service_name: StringBuilder::new(), p_confidence: Float64Builder::new(), p_first: Int64Builder::new(), p_last: Int64Builder::new(), p_rtt: Int64Builder::new(),
                 h_http_id: Int64Builder::new(), h_port_id: Int64Builder::new(), h_scheme: StringBuilder::new(), h_authority: StringBuilder::new(), h_path: StringBuilder::new(), h_status: Int64Builder::new(), h_h2: Int64Builder::new(), h_server: StringBuilder::new(), h_ct: StringBuilder::new(), h_fav: StringBuilder::new(), h_tags: StringBuilder::new(), h_ja3: StringBuilder::new(), h_ja3s: StringBuilder::new(), h_chain: StringBuilder::new(), h_collected: Int64Builder::new(),
             },
             TableKind::Http => RowBatch {
-                p_port_id: Int64Builder::new(), p_host_id: Int64Builder::new(), p_transport: StringBuilder::new(), p_port: Int64Builder::new(), p_state: StringBuilder::new(), p_reason: StringBuilder::new(), p_service_name: StringBuilder::new(), p_confidence: Float64Builder::new(), p_first: Int64Builder::new(), p_last: Int64Builder::new(),
+                p_port_id: Int64Builder::new(), p_host_id: Int64Builder::new(), p_transport: StringBuilder::new(), p_port: Int64Builder::new(), p_state: StringBuilder::new(), p_reason: StringBuilder::new(), p_service_name: StringBuilder::new(), p_confidence: Float64Builder::new(), p_first: Int64Builder::new(), p_last: Int64Builder::new(), p_rtt: Int64Builder::new(),
                 h_http_id: Int64Builder::new(), h_port_id: Int64Builder::new(), h_scheme: StringBuilder::new(), h_authority: StringBuilder::new(), h_path: StringBuilder::new(), h_status: Int64Builder::new(), h_h2: Int64Builder::new(), h_server: StringBuilder::new(), h_ct: StringBuilder::new(), h_fav: StringBuilder::new(), h_tags: StringBuilder::new(), h_ja3: StringBuilder::new(), h_ja3s: StringBuilder::new(), h_chain: StringBuilder::new(), h_collected: Int64Builder::new(),
             },
         }
@@ -98,11 +99,12 @@ impl RowBatch {
                 self.p_transport.append_value(row.get::<_, String>(2)?)?;
                 self.p_port.append_value(row.get::<_, i64>(3)?)?;
                 self.p_state.append_value(row.get::<_, String>(4)?)?;
-                self.append_opt_str(&mut self.p_reason, row.get::<_, Option<String>>(5)?)?;
-                self.append_opt_str(&mut self.p_service_name, row.get::<_, Option<String>>(6)?)?;
+                Self::append_opt_str(&mut self.p_reason, row.get::<_, Option<String>>(5)?)?;
+                Self::append_opt_str(&mut self.p_service_name, row.get::<_, Option<String>>(6)?)?;
                 self.p_confidence.append_value(row.get::<_, f64>(7)?)?;
                 self.p_first.append_value(row.get::<_, i64>(8)?)?;
                 self.p_last.append_value(row.get::<_, i64>(9)?)?;
+                Self::append_opt_i64(&mut self.p_rtt, row.get::<_, Option<i64>>(10)?)?;
             }
             TableKind::Http => {
                 self.h_http_id.append_value(row.get::<_, i64>(0)?)?;
@@ -110,23 +112,23 @@ impl RowBatch {
                 self.h_scheme.append_value(row.get::<_, String>(2)?)?;
                 self.h_authority.append_value(row.get::<_, String>(3)?)?;
                 self.h_path.append_value(row.get::<_, String>(4)?)?;
-                self.append_opt_i64(&mut self.h_status, row.get::<_, Option<i64>>(5)?)?;
+                Self::append_opt_i64(&mut self.h_status, row.get::<_, Option<i64>>(5)?)?;
                 self.h_h2.append_value(row.get::<_, i64>(6)?)?;
-                self.append_opt_str(&mut self.h_server, row.get::<_, Option<String>>(7)?)?;
-                self.append_opt_str(&mut self.h_ct, row.get::<_, Option<String>>(8)?)?;
-                self.append_opt_str(&mut self.h_fav, row.get::<_, Option<String>>(9)?)?;
-                self.append_opt_str(&mut self.h_tags, row.get::<_, Option<String>>(10)?)?;
-                self.append_opt_str(&mut self.h_ja3, row.get::<_, Option<String>>(11)?)?;
-                self.append_opt_str(&mut self.h_ja3s, row.get::<_, Option<String>>(12)?)?;
-                self.append_opt_str(&mut self.h_chain, row.get::<_, Option<String>>(13)?)?;
+                Self::append_opt_str(&mut self.h_server, row.get::<_, Option<String>>(7)?)?;
+                Self::append_opt_str(&mut self.h_ct, row.get::<_, Option<String>>(8)?)?;
+                Self::append_opt_str(&mut self.h_fav, row.get::<_, Option<String>>(9)?)?;
+                Self::append_opt_str(&mut self.h_tags, row.get::<_, Option<String>>(10)?)?;
+                Self::append_opt_str(&mut self.h_ja3, row.get::<_, Option<String>>(11)?)?;
+                Self::append_opt_str(&mut self.h_ja3s, row.get::<_, Option<String>>(12)?)?;
+                Self::append_opt_str(&mut self.h_chain, row.get::<_, Option<String>>(13)?)?;
                 self.h_collected.append_value(row.get::<_, i64>(14)?)?;
             }
         }
         Ok(())
     }
 
-    fn append_opt_str(&self, b: &mut StringBuilder, v: Option<String>) -> Result<()> { match v { Some(s) => b.append_value(s)?, None => b.append_null()? }; Ok(()) }
-    fn append_opt_i64(&self, b: &mut Int64Builder, v: Option<i64>) -> Result<()> { match v { Some(x) => b.append_value(x)?, None => b.append_null()? }; Ok(()) }
+    fn append_opt_str(b: &mut StringBuilder, v: Option<String>) -> Result<()> { match v { Some(s) => b.append_value(s)?, None => b.append_null()? }; Ok(()) }
+    fn append_opt_i64(b: &mut Int64Builder, v: Option<i64>) -> Result<()> { match v { Some(x) => b.append_value(x)?, None => b.append_null()? }; Ok(()) }
 
     fn finish(self, kind: &TableKind) -> Result<Vec<Arc<dyn arrow::array::Array>>> {
         Ok(match kind {
@@ -141,6 +143,7 @@ impl RowBatch {
                 Arc::new(self.p_confidence.finish()),
                 Arc::new(self.p_first.finish()),
                 Arc::new(self.p_last.finish()),
+                Arc::new(self.p_rtt.finish()),
             ],
             TableKind::Http => vec![
                 Arc::new(self.h_http_id.finish()),
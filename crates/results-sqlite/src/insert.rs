@@ -12,6 +12,12 @@ impl Db {
         Ok(meta.run_id)
     }
 
+    /// Replace the recorded arguments of a run (e.g. once the profile it ran under is known).
+    pub fn set_run_args(&self, run_id: &Uuid, args_json: &str) -> Result<()> {
+        self.conn.execute("UPDATE runs SET args_json=? WHERE run_id=?", params![args_json, run_id.to_string()])?;
+        Ok(())
+    }
+
     pub fn finish_run(&self, run_id: &Uuid, finished_at: i64, host_count: i64, error_count: i64) -> Result<()> {
         self.conn.execute(
             "UPDATE runs SET finished_at=?, host_count=?, error_count=? WHERE run_id=?",
@@ -20,6 +26,12 @@ impl Db {
         Ok(())
     }
 
+    /// Record how a run ended (`complete` or `interrupted`).
+    pub fn set_run_status(&self, run_id: &Uuid, status: &str) -> Result<()> {
+        self.conn.execute("UPDATE runs SET status=? WHERE run_id=?", params![status, run_id.to_string()])?;
+        Ok(())
+    }
+
     pub fn upsert_host(&self, run_id: &Uuid, addr: &str, hostname: Option<&str>) -> Result<HostId> {
         self.conn.execute(
             "INSERT INTO hosts(run_id,address,hostname) VALUES (?,?,?) ON CONFLICT(run_id,address) DO UPDATE SET hostname=COALESCE(excluded.hostname,hosts.hostname)",
@@ -33,11 +45,32 @@ impl Db {
         Ok(id)
     }
 
+    /// Record the PTR name of a host; a forward-confirmed name also fills an empty hostname.
+    pub fn set_host_ptr(&self, host_id: HostId, ptr: &str, confirmed: bool) -> Result<()> {
+        self.conn.execute(
+            "UPDATE hosts SET ptr=?1, ptr_confirmed=?2, hostname=COALESCE(hostname, CASE WHEN ?2 THEN ?1 END) WHERE host_id=?3",
+            params![ptr, confirmed, host_id],
+        )?;
+        Ok(())
+    }
+
+    /// Record the hardware address of a host (from ARP/NDP) and its OUI vendor when known.
+    pub fn set_host_mac(&self, host_id: HostId, mac: &str, vendor: Option<&str>) -> Result<()> {
+        self.conn.execute("UPDATE hosts SET mac=?1, mac_vendor=COALESCE(?2, mac_vendor) WHERE host_id=?3", params![mac, vendor, host_id])?;
+        Ok(())
+    }
+
+    /// Record how discovery found a host live: probe method, answer and, for TCP, the port.
+    pub fn set_host_liveness(&self, host_id: HostId, method: &str, reason: Option<&str>, port: Option<u16>) -> Result<()> {
+        self.conn.execute("UPDATE hosts SET live_method=?1, live_reason=?2, live_port=?3 WHERE host_id=?4", params![method, reason, port.map(|p| p as i64), host_id])?;
+        Ok(())
+    }
+
     pub fn upsert_port(&self, host_id: HostId, spec: &PortSpec) -> Result<PortId> {
         self.conn.execute(
-            "INSERT INTO ports(host_id,transport,port,state,reason,service_name,confidence,first_seen_ms,last_seen_ms) VALUES (?,?,?,?,?,?,?,?,?)
-             ON CONFLICT(host_id,transport,port) DO UPDATE SET state=excluded.state, reason=excluded.reason, service_name=excluded.service_name, confidence=excluded.confidence, last_seen_ms=excluded.last_seen_ms",
-            params![host_id, spec.transport, spec.port as i64, spec.state, spec.reason, spec.service_name, spec.confidence as f64, spec.first_seen_ms, spec.last_seen_ms],
+            "INSERT INTO ports(host_id,transport,port,state,reason,service_name,confidence,first_seen_ms,last_seen_ms,rtt_us) VALUES (?,?,?,?,?,?,?,?,?,?)
+             ON CONFLICT(host_id,transport,port) DO UPDATE SET state=excluded.state, reason=excluded.reason, service_name=excluded.service_name, confidence=excluded.confidence, last_seen_ms=excluded.last_seen_ms, rtt_us=COALESCE(excluded.rtt_us,ports.rtt_us)",
+            params![host_id, spec.transport, spec.port as i64, spec.state, spec.reason, spec.service_name, spec.confidence as f64, spec.first_seen_ms, spec.last_seen_ms, spec.rtt_us],
         )?;
         let id: PortId = self.conn.query_row(
             "SELECT port_id FROM ports WHERE host_id=? AND transport=? AND port=?",
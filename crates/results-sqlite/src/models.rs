@@ -23,6 +23,8 @@ pub struct PortSpec {
     pub confidence: f32,
     pub first_seen_ms: i64,
     pub last_seen_ms: i64,
+    /// Connect round-trip time observed for the port, in microseconds.
+    pub rtt_us: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,4 +1,4 @@
-use crate::schema::MIG_0001_INIT;
+use crate::schema::{MIG_0001_INIT, MIG_0002_PORT_RTT, MIG_0003_HOST_PTR, MIG_0004_HOST_MAC, MIG_0005_HOST_LIVENESS, MIG_0006_RUN_STATUS};
 use anyhow::Result;
 use rusqlite::{Connection, params};
 
@@ -35,6 +35,23 @@ fn migrate(conn: &Connection) -> Result<()> {
     if exists == 0 {
         conn.execute_batch(MIG_0001_INIT)?;
     }
+    // later migrations are tracked in user_version
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+    if version < 2 {
+        conn.execute_batch(MIG_0002_PORT_RTT)?;
+    }
+    if version < 3 {
+        conn.execute_batch(MIG_0003_HOST_PTR)?;
+    }
+    if version < 4 {
+        conn.execute_batch(MIG_0004_HOST_MAC)?;
+    }
+    if version < 5 {
+        conn.execute_batch(MIG_0005_HOST_LIVENESS)?;
+    }
+    if version < 6 {
+        conn.execute_batch(MIG_0006_RUN_STATUS)?;
+    }
     Ok(())
 }
 
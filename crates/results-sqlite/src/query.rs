@@ -1,5 +1,6 @@
 use crate::Db;
 use anyhow::Result;
+use uuid::Uuid;
 
 impl Db {
     pub fn table_exists(&self, name: &str) -> Result<bool> {
@@ -10,5 +11,11 @@ impl Db {
         )?;
         Ok(cnt > 0)
     }
-}
 
+    /// Addresses of the run's hosts that have no PTR name recorded.
+    pub fn hosts_without_ptr(&self, run_id: &Uuid) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT address FROM hosts WHERE run_id=? AND ptr IS NULL")?;
+        let rows = stmt.query_map([run_id.to_string()], |r| r.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+}
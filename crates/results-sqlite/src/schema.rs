@@ -83,3 +83,41 @@ COMMIT;
 "#
 ;
 
+pub const MIG_0002_PORT_RTT: &str = r#"
+BEGIN;
+ALTER TABLE ports ADD COLUMN rtt_us INTEGER;
+PRAGMA user_version = 2;
+COMMIT;
+"#;
+
+pub const MIG_0003_HOST_PTR: &str = r#"
+BEGIN;
+ALTER TABLE hosts ADD COLUMN ptr TEXT;
+ALTER TABLE hosts ADD COLUMN ptr_confirmed INTEGER CHECK (ptr_confirmed IN (0,1));
+PRAGMA user_version = 3;
+COMMIT;
+"#;
+
+pub const MIG_0004_HOST_MAC: &str = r#"
+BEGIN;
+ALTER TABLE hosts ADD COLUMN mac TEXT;
+ALTER TABLE hosts ADD COLUMN mac_vendor TEXT;
+PRAGMA user_version = 4;
+COMMIT;
+"#;
+
+pub const MIG_0005_HOST_LIVENESS: &str = r#"
+BEGIN;
+ALTER TABLE hosts ADD COLUMN live_method TEXT;
+ALTER TABLE hosts ADD COLUMN live_reason TEXT;
+ALTER TABLE hosts ADD COLUMN live_port INTEGER;
+PRAGMA user_version = 5;
+COMMIT;
+"#;
+
+pub const MIG_0006_RUN_STATUS: &str = r#"
+BEGIN;
+ALTER TABLE runs ADD COLUMN status TEXT;
+PRAGMA user_version = 6;
+COMMIT;
+"#;
@@ -2,15 +2,24 @@
 
 use anyhow::Result;
 use rustls::ClientConfig;
-use std::net::ToSocketAddrs;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio::time::timeout;
 use tokio_rustls::TlsConnector;
+use toolbox_core::policy::{Capability, Level};
+use toolbox_core::connect::{self, Dest};
+use toolbox_core::{audit, Family, Host, Target};
 use url::Url;
 
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "banners.http", level: Level::SafeActive, summary: "HTTP HEAD banner, optionally following one redirect" },
+    Capability { name: "banners.https", level: Level::SafeActive, summary: "TLS handshake with ALPN and certificate details" },
+    Capability { name: "banners.ssh", level: Level::SafeActive, summary: "SSH version banner" },
+];
+
 #[derive(Debug, Clone)]
 pub struct Banner {
     pub protocol: String,
@@ -18,9 +27,32 @@ pub struct Banner {
     pub summary: String,
 }
 
-async fn http_head_raw(host: &str, port: u16, path: &str, timeout_ms: u64) -> Result<String> {
-    let addr = resolve_first(host, port)?;
-    let mut stream = timeout(Duration::from_millis(timeout_ms), TcpStream::connect(addr)).await??;
+/// Grab a banner from every in-scope address of a target in `family`, one after another. The port
+/// is the target's explicit port or `default_port`; the protocol is `protocol` if given, else the
+/// URL scheme, else inferred from the port. Fails only when the target does not resolve or no
+/// address is in scope; each address has its own outcome.
+pub async fn grab_target(target: &Target, default_port: u16, protocol: Option<&str>, family: Family, follow: bool, timeout_ms: u64, cn_only: bool) -> Result<Vec<(Dest, Result<Banner>)>> {
+    let host = target.host.to_string();
+    let port = target.port.unwrap_or(default_port);
+    let scheme = target.url.as_deref().and_then(|u| u.split_once("://")).map(|(s, _)| s);
+    let proto = protocol.or(scheme).unwrap_or(match port { 443 => "https", 22 => "ssh", _ => "http" });
+    let dests = connect::resolve_all(&target.host, port, family).await?;
+    if dests.is_empty() { return Err(no_address(&host, family)); }
+    let mut out = Vec::with_capacity(dests.len());
+    for dest in dests {
+        audit::record("banners", "tcp-connect", &dest);
+        let banner = match proto {
+            "https" => if follow { https_follow_at(&host, &dest, timeout_ms, cn_only).await } else { https_at(&host, &dest, timeout_ms, cn_only).await },
+            "ssh" => ssh_at(&dest, timeout_ms).await,
+            _ => if follow { http_follow_at(&host, &dest, timeout_ms).await } else { http_at(&host, &dest, timeout_ms).await },
+        };
+        out.push((dest, banner));
+    }
+    Ok(out)
+}
+
+async fn http_head_raw(host: &str, dest: &Dest, path: &str, timeout_ms: u64) -> Result<String> {
+    let mut stream = open(dest, timeout_ms).await?;
     let req = format!("HEAD {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: toolbox/0.1\r\nConnection: close\r\n\r\n", path, host);
     timeout(Duration::from_millis(timeout_ms), stream.write_all(req.as_bytes())).await??;
     let mut buf = vec![0u8; 4096];
@@ -29,7 +61,13 @@ async fn http_head_raw(host: &str, port: u16, path: &str, timeout_ms: u64) -> Re
 }
 
 pub async fn grab_http(host: &str, port: u16, timeout_ms: u64) -> Result<Banner> {
-    let text = http_head_raw(host, port, "/", timeout_ms).await?;
+    http_at(host, &resolve_first(host, port).await?, timeout_ms).await
+}
+
+/// HTTP banner from `dest`, sending `host` as the Host header.
+async fn http_at(host: &str, dest: &Dest, timeout_ms: u64) -> Result<Banner> {
+    let port = dest.port();
+    let text = http_head_raw(host, dest, "/", timeout_ms).await?;
     let mut first = String::new();
     let mut server = String::new();
     let mut location = String::new();
@@ -45,7 +83,12 @@ pub async fn grab_http(host: &str, port: u16, timeout_ms: u64) -> Result<Banner>
 }
 
 pub async fn grab_http_follow_one(host: &str, port: u16, timeout_ms: u64) -> Result<Banner> {
-    let text = http_head_raw(host, port, "/", timeout_ms).await?;
+    http_follow_at(host, &resolve_first(host, port).await?, timeout_ms).await
+}
+
+/// [`http_at`], following one redirect hop (resolved anew).
+async fn http_follow_at(host: &str, dest: &Dest, timeout_ms: u64) -> Result<Banner> {
+    let text = http_head_raw(host, dest, "/", timeout_ms).await?;
     let mut first = String::new();
     let mut location = String::new();
     for (i, line) in text.lines().enumerate() {
@@ -63,14 +106,19 @@ pub async fn grab_http_follow_one(host: &str, port: u16, timeout_ms: u64) -> Res
             return Ok(Banner { protocol: b.protocol, port: b.port, summary: format!("{} -> {}", first, b.summary) });
         }
     }
-    grab_http(host, port, timeout_ms).await
+    http_at(host, dest, timeout_ms).await
 }
 
 pub async fn grab_https(host: &str, port: u16, timeout_ms: u64, cn_only: bool) -> Result<Banner> {
+    https_at(host, &resolve_first(host, port).await?, timeout_ms, cn_only).await
+}
+
+/// HTTPS banner from `dest`, with `host` as the server name and Host header.
+async fn https_at(host: &str, dest: &Dest, timeout_ms: u64, cn_only: bool) -> Result<Banner> {
     // Ensure a crypto provider is installed (ring)
     let _ = rustls::crypto::CryptoProvider::install_default(rustls::crypto::ring::default_provider());
-    let addr = resolve_first(host, port)?;
-    let stream = timeout(Duration::from_millis(timeout_ms), TcpStream::connect(addr)).await??;
+    let port = dest.port();
+    let stream = open(dest, timeout_ms).await?;
 
     let mut root_store = rustls::RootCertStore::empty();
     root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
@@ -129,8 +177,13 @@ pub async fn grab_https(host: &str, port: u16, timeout_ms: u64, cn_only: bool) -
 }
 
 pub async fn grab_https_follow_one(host: &str, port: u16, timeout_ms: u64, cn_only: bool) -> Result<Banner> {
+    https_follow_at(host, &resolve_first(host, port).await?, timeout_ms, cn_only).await
+}
+
+/// [`https_at`], following one redirect hop (resolved anew).
+async fn https_follow_at(host: &str, dest: &Dest, timeout_ms: u64, cn_only: bool) -> Result<Banner> {
     // Reuse https logic, and follow one hop if present
-    let b = grab_https(host, port, timeout_ms, cn_only).await?;
+    let b = https_at(host, dest, timeout_ms, cn_only).await?;
     if let Some(loc_start) = b.summary.find("redirect-> ") {
         let loc = b.summary[loc_start + 11..].trim();
         if let Ok(url) = Url::parse(loc) {
@@ -146,8 +199,12 @@ pub async fn grab_https_follow_one(host: &str, port: u16, timeout_ms: u64, cn_on
 }
 
 pub async fn grab_ssh(host: &str, port: u16, timeout_ms: u64) -> Result<Banner> {
-    let addr = resolve_first(host, port)?;
-    let mut stream = timeout(Duration::from_millis(timeout_ms), TcpStream::connect(addr)).await??;
+    ssh_at(&resolve_first(host, port).await?, timeout_ms).await
+}
+
+async fn ssh_at(dest: &Dest, timeout_ms: u64) -> Result<Banner> {
+    let port = dest.port();
+    let mut stream = open(dest, timeout_ms).await?;
     let mut buf = vec![0u8; 256];
     let n = timeout(Duration::from_millis(timeout_ms), stream.read(&mut buf)).await??;
     let mut line = String::from_utf8_lossy(&buf[..n]).to_string();
@@ -155,15 +212,34 @@ pub async fn grab_ssh(host: &str, port: u16, timeout_ms: u64) -> Result<Banner>
     Ok(Banner { protocol: "ssh".into(), port, summary: line })
 }
 
-fn resolve_first(host: &str, port: u16) -> Result<std::net::SocketAddr> {
-    let mut it = (host, port).to_socket_addrs()?;
-    it.next().ok_or_else(|| anyhow::anyhow!("failed to resolve: {}", host))
+/// First in-scope address of `host` from the shared resolver (or the name itself when the proxy
+/// resolves it, see [`connect::resolve`]), recorded in the audit log. Every connection not made by
+/// [`grab_target`] (redirect hops included) goes through here, so out-of-scope destinations are
+/// refused before connecting.
+async fn resolve_first(host: &str, port: u16) -> Result<Dest> {
+    let h: Host = host.parse().map_err(|_| anyhow::anyhow!("invalid host: {}", host))?;
+    let dest = connect::resolve(&h, port).await?.ok_or_else(|| anyhow::anyhow!("failed to resolve: {}", host))?;
+    audit::record("banners", "tcp-connect", &dest);
+    Ok(dest)
+}
+
+/// Error for a target with nothing to connect to in `family`.
+fn no_address(host: &str, family: Family) -> anyhow::Error {
+    match family {
+        Family::Any => anyhow::anyhow!("failed to resolve: {}", host),
+        f => anyhow::anyhow!("no {} address for {}", f, host),
+    }
+}
+
+/// Connect through the installed connector within `timeout_ms`.
+async fn open(dest: &Dest, timeout_ms: u64) -> Result<TcpStream> {
+    Ok(timeout(Duration::from_millis(timeout_ms), connect::global().connect(dest)).await??)
 }
 
 /// Extract TLS certificate subject/issuer (best effort) from HTTPS handshake.
 pub async fn tls_cert_subject_issuer(host: &str, port: u16, timeout_ms: u64) -> Result<Option<(String, String)>> {
-    let addr = resolve_first(host, port)?;
-    let stream = timeout(Duration::from_millis(timeout_ms), TcpStream::connect(addr)).await??;
+    let dest = resolve_first(host, port).await?;
+    let stream = open(&dest, timeout_ms).await?;
     let mut root_store = rustls::RootCertStore::empty();
     root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
     let config = ClientConfig::builder().with_root_certificates(root_store).with_no_client_auth();
@@ -198,3 +274,30 @@ pub async fn tls_cert_subject_issuer(host: &str, port: u16, timeout_ms: u64) ->
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn grabs_every_address_in_the_family() {
+        // local.lab has both 127.0.0.1 and ::1
+        toolbox_core::dns::testing::install();
+        let listener = TcpListener::bind("[::1]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut s, _)) = listener.accept().await { let _ = s.write_all(b"SSH-2.0-test\n").await; }
+        });
+        let target: Target = format!("local.lab:{}", port).parse().unwrap();
+        let grabs = grab_target(&target, 22, Some("ssh"), Family::V6, false, 2000, true).await.unwrap();
+        assert_eq!(grabs.len(), 1);
+        assert_eq!(grabs[0].0, Dest::Addr(SocketAddr::new("::1".parse().unwrap(), port)));
+        assert_eq!(grabs[0].1.as_ref().unwrap().summary, "SSH-2.0-test");
+
+        let grabs = grab_target(&target, 22, Some("ssh"), Family::Any, false, 2000, true).await.unwrap();
+        let v6 = grabs.iter().find(|(d, _)| matches!(d, Dest::Addr(a) if a.is_ipv6())).unwrap();
+        assert!(v6.1.is_ok() && grabs.iter().any(|(d, _)| matches!(d, Dest::Addr(a) if a.is_ipv4())));
+    }
+}
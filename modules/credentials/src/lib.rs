@@ -1,4 +1,15 @@
 use regex::Regex;
+use toolbox_core::policy::{Capability, Level};
+
+/// Running an external cracker against captured hashes.
+pub const CRACK: Capability = Capability { name: "credentials.crack", level: Level::Intrusive, summary: "run hashcat/john against a hash file" };
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "credentials.detect", level: Level::Passive, summary: "identify hash formats" },
+    Capability { name: "credentials.wordlist", level: Level::Passive, summary: "wordlist statistics" },
+    CRACK,
+];
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HashKind {
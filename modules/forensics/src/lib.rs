@@ -2,6 +2,13 @@ use anyhow::Result;
 use sha2::{Sha256, Digest};
 use std::fs::File;
 use std::io::{Read, BufReader};
+use toolbox_core::policy::{Capability, Level};
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "forensics.hash", level: Level::Passive, summary: "hash local files" },
+    Capability { name: "forensics.identify", level: Level::Passive, summary: "identify local file types" },
+];
 
 #[derive(Debug, Clone)]
 pub struct FileInfo {
@@ -0,0 +1,276 @@
+//! ICMP echo and timestamp probes (Linux, IPv4 and IPv6).
+//!
+//! Echo requests go out over unprivileged ICMP datagram sockets ("ping sockets", allowed for the
+//! groups in `net.ipv4.ping_group_range`) when the kernel permits them, otherwise over raw sockets,
+//! which need CAP_NET_RAW. Timestamp requests exist for IPv4 only and always need a raw socket,
+//! since ping sockets carry echo requests only; when one is open both are sent and the first reply
+//! wins.
+
+/// Which request a host answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpReply {
+    Echo,
+    Timestamp,
+}
+
+#[cfg(target_os = "linux")]
+pub use imp::Pinger;
+
+#[cfg(not(target_os = "linux"))]
+use std::{io, net::IpAddr, time::Duration};
+
+#[cfg(not(target_os = "linux"))]
+pub struct Pinger(());
+
+#[cfg(not(target_os = "linux"))]
+impl Pinger {
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ICMP discovery needs Linux"))
+    }
+
+    pub fn supports(&self, _ip: IpAddr) -> bool {
+        false
+    }
+
+    pub async fn probe(&self, _ip: IpAddr, _wait: Duration) -> io::Result<Option<IcmpReply>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ICMP discovery needs Linux"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::IcmpReply;
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use std::collections::HashMap;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::net::{IpAddr, SocketAddr};
+    use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tokio::sync::oneshot;
+
+    const ECHO_REQUEST_V4: u8 = 8;
+    const ECHO_REPLY_V4: u8 = 0;
+    const TIMESTAMP_REQUEST: u8 = 13;
+    const TIMESTAMP_REPLY: u8 = 14;
+    const ECHO_REQUEST_V6: u8 = 128;
+    const ECHO_REPLY_V6: u8 = 129;
+
+    /// An ICMP socket; raw IPv4 sockets deliver the IP header and see every host's ICMP traffic.
+    struct Endpoint {
+        sock: Socket,
+        raw: bool,
+        v6: bool,
+    }
+
+    struct Shared {
+        echo4: Option<Arc<Endpoint>>,
+        echo6: Option<Arc<Endpoint>>,
+        stamp4: Option<Arc<Endpoint>>,
+        /// Identifier of our raw-socket requests (ping sockets get theirs from the kernel).
+        ident: u16,
+        seq: AtomicU16,
+        pending: Mutex<HashMap<(IpAddr, u16), oneshot::Sender<IcmpReply>>>,
+        stop: AtomicBool,
+    }
+
+    /// Shared ICMP prober; receive threads run until it is dropped.
+    pub struct Pinger {
+        shared: Arc<Shared>,
+    }
+
+    impl Pinger {
+        /// Open ICMP sockets for both families, preferring ping sockets. Fails if neither family
+        /// has a usable socket (ping sockets disabled and no CAP_NET_RAW).
+        pub fn new() -> io::Result<Self> {
+            let echo4 = Endpoint::open(false);
+            let echo6 = Endpoint::open(true);
+            let (echo4, echo6) = match (echo4, echo6) {
+                (Err(e), Err(_)) => return Err(e),
+                (a, b) => (a.ok().map(Arc::new), b.ok().map(Arc::new)),
+            };
+            let stamp4 = match &echo4 {
+                Some(e) if e.raw => Some(e.clone()),
+                _ => Endpoint::raw(false).ok().map(Arc::new),
+            };
+            let shared = Arc::new(Shared { echo4, echo6, stamp4, ident: rand_ident(), seq: AtomicU16::new(0), pending: Mutex::new(HashMap::new()), stop: AtomicBool::new(false) });
+            let mut socks: Vec<Arc<Endpoint>> = Vec::new();
+            for ep in [&shared.echo4, &shared.echo6, &shared.stamp4].into_iter().flatten() {
+                if !socks.iter().any(|s| Arc::ptr_eq(s, ep)) { socks.push(ep.clone()); }
+            }
+            for ep in socks {
+                let shared = shared.clone();
+                std::thread::spawn(move || recv_loop(&shared, &ep));
+            }
+            Ok(Pinger { shared })
+        }
+
+        /// True if echo requests can be sent to `ip`'s family.
+        pub fn supports(&self, ip: IpAddr) -> bool {
+            match ip { IpAddr::V4(_) => self.shared.echo4.is_some() || self.shared.stamp4.is_some(), IpAddr::V6(_) => self.shared.echo6.is_some() }
+        }
+
+        /// Send an echo request (and a timestamp request where possible) to `ip` and wait up to
+        /// `wait` for the first reply (`None` on timeout).
+        pub async fn probe(&self, ip: IpAddr, wait: Duration) -> io::Result<Option<IcmpReply>> {
+            let s = &self.shared;
+            let seq = s.seq.fetch_add(1, Ordering::Relaxed);
+            let (tx, rx) = oneshot::channel();
+            s.pending.lock().unwrap().insert((ip, seq), tx);
+            let requests = match ip {
+                IpAddr::V4(_) => [(s.echo4.as_ref(), ECHO_REQUEST_V4), (s.stamp4.as_ref(), TIMESTAMP_REQUEST)],
+                IpAddr::V6(_) => [(s.echo6.as_ref(), ECHO_REQUEST_V6), (None, 0)],
+            };
+            let (mut sent, mut error) = (0, None);
+            for (ep, kind) in requests {
+                let Some(ep) = ep else { continue };
+                match ep.sock.send_to(&build_request(kind, s.ident, seq), &SockAddr::from(SocketAddr::new(ip, 0))) {
+                    Ok(_) => sent += 1,
+                    Err(e) => error = Some(e),
+                }
+            }
+            if sent == 0 {
+                s.pending.lock().unwrap().remove(&(ip, seq));
+                return Err(error.unwrap_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no ICMP socket for address family")));
+            }
+            let reply = tokio::time::timeout(wait, rx).await;
+            s.pending.lock().unwrap().remove(&(ip, seq));
+            Ok(reply.ok().and_then(|r| r.ok()))
+        }
+    }
+
+    impl Drop for Pinger {
+        fn drop(&mut self) {
+            self.shared.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl Endpoint {
+        /// Ping socket, or a raw socket if ping sockets are not permitted.
+        fn open(v6: bool) -> io::Result<Endpoint> {
+            let (domain, proto) = family(v6);
+            match Socket::new(domain, Type::DGRAM, Some(proto)) {
+                Ok(sock) => {
+                    sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+                    toolbox_core::socket::global().bind_raw(&sock, v6)?;
+                    Ok(Endpoint { sock, raw: false, v6 })
+                }
+                Err(_) => Endpoint::raw(v6),
+            }
+        }
+
+        fn raw(v6: bool) -> io::Result<Endpoint> {
+            let (domain, proto) = family(v6);
+            let sock = Socket::new(domain, Type::RAW, Some(proto))?;
+            sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+            toolbox_core::socket::global().bind_raw(&sock, v6)?;
+            Ok(Endpoint { sock, raw: true, v6 })
+        }
+    }
+
+    fn family(v6: bool) -> (Domain, Protocol) {
+        if v6 { (Domain::IPV6, Protocol::ICMPV6) } else { (Domain::IPV4, Protocol::ICMPV4) }
+    }
+
+    fn rand_ident() -> u16 {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        (std::process::id() as u16) ^ (nanos as u16)
+    }
+
+    fn recv_loop(shared: &Shared, ep: &Endpoint) {
+        let mut buf = [MaybeUninit::<u8>::uninit(); 4096];
+        while !shared.stop.load(Ordering::Relaxed) {
+            match ep.sock.recv_from(&mut buf) {
+                Ok((n, from)) => {
+                    // SAFETY: recv_from initialized the first n bytes.
+                    let data = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, n) };
+                    if let Some(from) = from.as_socket() { on_packet(shared, ep, data, from.ip()); }
+                }
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn on_packet(shared: &Shared, ep: &Endpoint, data: &[u8], from: IpAddr) {
+        let icmp = if ep.raw && !ep.v6 {
+            if data.is_empty() { return; }
+            let ihl = (data[0] & 0x0f) as usize * 4;
+            if data.len() < ihl { return; }
+            &data[ihl..]
+        } else {
+            data
+        };
+        if icmp.len() < 8 { return; }
+        let reply = match (ep.v6, icmp[0]) {
+            (false, ECHO_REPLY_V4) | (true, ECHO_REPLY_V6) => IcmpReply::Echo,
+            (false, TIMESTAMP_REPLY) => IcmpReply::Timestamp,
+            _ => return,
+        };
+        // Raw sockets see every ICMP message on the host; ping sockets only their own replies.
+        if ep.raw && u16::from_be_bytes([icmp[4], icmp[5]]) != shared.ident { return; }
+        let seq = u16::from_be_bytes([icmp[6], icmp[7]]);
+        if let Some(tx) = shared.pending.lock().unwrap().remove(&(from, seq)) { let _ = tx.send(reply); }
+    }
+
+    /// Echo (v4 or v6) or timestamp request. The ICMPv6 checksum is filled in by the kernel.
+    pub(super) fn build_request(kind: u8, ident: u16, seq: u16) -> Vec<u8> {
+        let mut p = vec![kind, 0, 0, 0];
+        p.extend_from_slice(&ident.to_be_bytes());
+        p.extend_from_slice(&seq.to_be_bytes());
+        if kind == TIMESTAMP_REQUEST {
+            // Originate timestamp: ms since midnight UT; receive/transmit are left for the host.
+            let ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| (d.as_millis() % 86_400_000) as u32).unwrap_or(0);
+            p.extend_from_slice(&ms.to_be_bytes());
+            p.extend_from_slice(&[0; 8]);
+        } else {
+            p.extend_from_slice(b"toolbox\0");
+        }
+        if kind != ECHO_REQUEST_V6 {
+            let sum = checksum(&p);
+            p[2..4].copy_from_slice(&sum.to_be_bytes());
+        }
+        p
+    }
+
+    pub(super) fn checksum(data: &[u8]) -> u16 {
+        let mut sum: u32 = 0;
+        for chunk in data.chunks(2) {
+            let word = if chunk.len() == 2 { u16::from_be_bytes([chunk[0], chunk[1]]) } else { u16::from_be_bytes([chunk[0], 0]) };
+            sum += word as u32;
+        }
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn request_checksums_verify() {
+        let echo = imp::build_request(8, 0x1234, 7);
+        assert_eq!((echo[0], echo.len()), (8, 16));
+        assert_eq!(imp::checksum(&echo), 0);
+        let stamp = imp::build_request(13, 0x1234, 7);
+        assert_eq!((stamp[0], stamp.len()), (13, 20));
+        assert_eq!(imp::checksum(&stamp), 0);
+    }
+
+    /// Needs ping sockets (`net.ipv4.ping_group_range`) or CAP_NET_RAW; run it unprivileged in a
+    /// throwaway namespace: `unshare -rn sh -c 'ip link set lo up && cargo test -p host-discovery icmp -- --ignored'`.
+    #[tokio::test]
+    #[ignore = "needs CAP_NET_RAW or ping sockets"]
+    async fn pings_loopback() {
+        let pinger = Pinger::new().unwrap();
+        let lo = "127.0.0.1".parse().unwrap();
+        if !pinger.supports(lo) { return; }
+        let reply = pinger.probe(lo, Duration::from_secs(2)).await.unwrap();
+        assert!(reply.is_some());
+    }
+}
@@ -1,62 +1,388 @@
-//! Host discovery via TCP connect sweep with timeouts and pacing.
+//! Host discovery via TCP connect, ICMP and ARP/NDP sweeps with timeouts and pacing, streaming
+//! over lazily expanded target ranges.
 
-use anyhow::Result;
-use ipnet::IpNet;
-use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc;
-use tokio::time::{interval, timeout, MissedTickBehavior};
+use tokio::time::timeout;
+use toolbox_core::cancel::Cancel;
+use toolbox_core::policy::{Capability, Level};
+use toolbox_core::progress::Progress;
+use toolbox_core::ratelimiter::RateLimiter;
+use toolbox_core::{audit, connect, scope, Exclusions, Family, Host, Target, TargetSpec};
 
-/// Expand a CIDR into IP addresses.
-pub fn expand_cidr(cidr: &str) -> Result<Vec<IpAddr>> {
-    let net: IpNet = cidr.parse()?;
-    let mut ips = Vec::new();
-    for ip in net.hosts() {
-        ips.push(ip);
+mod icmp;
+mod neigh;
+mod oui;
+
+pub use icmp::{IcmpReply, Pinger};
+pub use neigh::Neighbors;
+pub use oui::MacAddr;
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "host-discovery.tcp", level: Level::SafeActive, summary: "TCP connect liveness sweep" },
+    Capability { name: "host-discovery.icmp", level: Level::SafeActive, summary: "ICMP echo and timestamp requests" },
+    Capability { name: "host-discovery.arp", level: Level::SafeActive, summary: "ARP requests and IPv6 neighbor solicitations" },
+];
+
+/// Lazily expand a CIDR into its host addresses. IPv6 blocks wider than
+/// [`toolbox_core::target::MAX_V6_SPAN`] addresses are rejected.
+pub fn expand_cidr(cidr: &str) -> Result<impl Iterator<Item = IpAddr> + Send> {
+    match cidr.parse::<TargetSpec>()? {
+        spec @ TargetSpec::Cidr(_) => Ok(spec.expand().filter_map(|t| t.host.ip())),
+        _ => Err(anyhow!("not a CIDR block: {}", cidr)),
+    }
+}
+
+/// Resolve a hostname to every address of `family` through the shared resolver (best-effort;
+/// empty if it does not resolve).
+pub async fn resolve_host_all(host: &str, family: Family) -> Vec<IpAddr> {
+    match host.parse::<Host>() {
+        Ok(h) => toolbox_core::dns::global().resolve(&h, family).await,
+        Err(_) => Vec::new(),
     }
-    Ok(ips)
 }
 
-/// Resolve a hostname to a single IP address (best-effort).
-pub fn resolve_host_best_effort(host: &str) -> IpAddr {
-    if let Ok(mut it) = (host, 0u16).to_socket_addrs() { if let Some(sa) = it.next() { return sa.ip(); } }
-    host.parse().unwrap_or_else(|_| IpAddr::from([0,0,0,0]))
+/// Outcome of one TCP connect attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpOutcome {
+    /// Handshake completed.
+    Accepted,
+    /// Connection actively refused (RST).
+    Refused,
+    /// No answer within the timeout.
+    TimedOut,
+    /// Host or network unreachable (ICMP or local routing error).
+    Unreachable,
 }
 
-/// TCP-based liveness check: attempt to connect to given ports; if any succeed within timeout, host is live.
-pub async fn is_host_live(ip: IpAddr, ports: &[u16], per_attempt: Duration) -> bool {
+impl TcpOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TcpOutcome::Accepted => "accepted",
+            TcpOutcome::Refused => "refused",
+            TcpOutcome::TimedOut => "timeout",
+            TcpOutcome::Unreachable => "unreachable",
+        }
+    }
+
+    /// Whether the host answered: a refusal proves it is up as much as an accepted connection.
+    pub fn is_live(&self) -> bool {
+        matches!(self, TcpOutcome::Accepted | TcpOutcome::Refused)
+    }
+}
+
+impl From<port_scan::Reason> for TcpOutcome {
+    fn from(r: port_scan::Reason) -> Self {
+        match r {
+            port_scan::Reason::SynAck => TcpOutcome::Accepted,
+            port_scan::Reason::ConnRefused => TcpOutcome::Refused,
+            port_scan::Reason::Timeout => TcpOutcome::TimedOut,
+            port_scan::Reason::HostUnreach => TcpOutcome::Unreachable,
+        }
+    }
+}
+
+/// Connect to `ip:port` once and classify the result.
+pub async fn probe_tcp(ip: IpAddr, port: u16, per_attempt: Duration) -> TcpOutcome {
+    audit::record("host-discovery", "tcp-connect", SocketAddr::new(ip, port));
+    match timeout(per_attempt, connect::connect(SocketAddr::new(ip, port))).await {
+        Ok(Ok(_)) => TcpOutcome::Accepted,
+        Ok(Err(e)) => port_scan::classify_error(&e).into(),
+        Err(_) => TcpOutcome::TimedOut,
+    }
+}
+
+/// TCP liveness: connect to `ports` in turn and return the first port that was accepted or
+/// refused, with its outcome.
+pub async fn tcp_liveness(ip: IpAddr, ports: &[u16], per_attempt: Duration) -> Option<(u16, TcpOutcome)> {
     for &p in ports {
-        let addr = SocketAddr::new(ip, p);
-        if let Ok(Ok(_)) = timeout(per_attempt, TcpStream::connect(addr)).await { return true; }
+        let outcome = probe_tcp(ip, p, per_attempt).await;
+        if outcome.is_live() { return Some((p, outcome)); }
+    }
+    None
+}
+
+/// TCP-based liveness check: the host is live if any of `ports` accepts or refuses a connection
+/// within the timeout.
+pub async fn is_host_live(ip: IpAddr, ports: &[u16], per_attempt: Duration) -> bool {
+    tcp_liveness(ip, ports, per_attempt).await.is_some()
+}
+
+/// Liveness technique; several can be combined and are tried in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// TCP connect to the liveness ports.
+    Tcp,
+    /// ICMP echo, plus timestamp on IPv4 when a raw socket is available.
+    Icmp,
+    /// ARP request (IPv4) or neighbor solicitation (IPv6); on-link addresses only.
+    Arp,
+}
+
+/// The probe that showed a host to be live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    TcpConnect,
+    IcmpEcho,
+    IcmpTimestamp,
+    ArpReply,
+    NeighborAdvert,
+}
+
+impl Probe {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Probe::TcpConnect => "tcp-connect",
+            Probe::IcmpEcho => "icmp-echo",
+            Probe::IcmpTimestamp => "icmp-timestamp",
+            Probe::ArpReply => "arp",
+            Probe::NeighborAdvert => "ndp",
+        }
     }
-    false
 }
 
-/// Discover live hosts among a set of IPs using TCP connect attempts with concurrency and QPS pacing.
+/// Why a host was judged live: the answer its probe got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    /// A TCP connection was accepted.
+    Accepted,
+    /// A TCP connection was refused (RST).
+    Refused,
+    EchoReply,
+    TimestampReply,
+    ArpReply,
+    NeighborAdvert,
+}
+
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reason::Accepted => "accepted",
+            Reason::Refused => "refused",
+            Reason::EchoReply => "echo-reply",
+            Reason::TimestampReply => "timestamp-reply",
+            Reason::ArpReply => "arp-reply",
+            Reason::NeighborAdvert => "neighbor-advert",
+        }
+    }
+}
+
+/// A live host and how it was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Live {
+    pub addr: IpAddr,
+    pub probe: Probe,
+    pub reason: Reason,
+    /// Port that answered, for TCP probes.
+    pub port: Option<u16>,
+    /// Hardware address, known when the host answered ARP or NDP.
+    pub mac: Option<MacAddr>,
+}
+
+/// Check one address with each of `methods` in turn, stopping at the first that gets an answer.
+async fn probe_host(ip: IpAddr, methods: &[Method], ports: &[u16], per_attempt: Duration, pinger: Option<&Pinger>, neighbors: Option<&Neighbors>) -> Option<Live> {
+    let live = |probe, reason, port, mac| Some(Live { addr: ip, probe, reason, port, mac });
+    for m in methods {
+        match m {
+            Method::Tcp => match tcp_liveness(ip, ports, per_attempt).await {
+                Some((port, TcpOutcome::Accepted)) => return live(Probe::TcpConnect, Reason::Accepted, Some(port), None),
+                Some((port, _)) => return live(Probe::TcpConnect, Reason::Refused, Some(port), None),
+                None => {}
+            },
+            Method::Icmp => {
+                let Some(p) = pinger.filter(|p| p.supports(ip)) else { continue };
+                audit::record("host-discovery", "icmp", ip);
+                match p.probe(ip, per_attempt).await {
+                    Ok(Some(IcmpReply::Echo)) => return live(Probe::IcmpEcho, Reason::EchoReply, None, None),
+                    Ok(Some(IcmpReply::Timestamp)) => return live(Probe::IcmpTimestamp, Reason::TimestampReply, None, None),
+                    _ => {}
+                }
+            }
+            Method::Arp => {
+                let Some(n) = neighbors.filter(|n| n.supports(ip)) else { continue };
+                audit::record("host-discovery", if ip.is_ipv4() { "arp" } else { "ndp" }, ip);
+                if let Ok(Some(mac)) = n.probe(ip, per_attempt).await {
+                    return if ip.is_ipv4() { live(Probe::ArpReply, Reason::ArpReply, None, Some(mac)) } else { live(Probe::NeighborAdvert, Reason::NeighborAdvert, None, Some(mac)) };
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Settings for [`discover`].
+#[derive(Debug, Clone)]
+pub struct DiscoverOptions {
+    /// TCP liveness ports (targets with an explicit port use that port only).
+    pub ports: Vec<u16>,
+    /// Per-attempt timeout.
+    pub timeout: Duration,
+    /// Addresses probed at once.
+    pub concurrency: usize,
+    /// Cap on probe launches per second.
+    pub qps: Option<u32>,
+    pub family: Family,
+    /// Techniques tried in order until one answers.
+    pub methods: Vec<Method>,
+    /// Addresses never probed, including those a name resolves to.
+    pub exclude: Exclusions,
+    /// Once cancelled no new addresses are probed; probes still running at the drain deadline are
+    /// dropped.
+    pub cancel: Cancel,
+    /// Counts addresses probed (sent, and as hosts once finished), live hosts and names that did
+    /// not resolve; work is counted in targets taken from the input.
+    pub progress: Progress,
+}
+
+impl Default for DiscoverOptions {
+    fn default() -> Self {
+        DiscoverOptions { ports: vec![80, 443, 22], timeout: Duration::from_millis(300), concurrency: 256, qps: None, family: Family::Any, methods: vec![Method::Tcp], exclude: Exclusions::default(), cancel: Cancel::default(), progress: Progress::default() }
+    }
+}
+
+/// Discover live hosts among a set of targets, sending each to `out` as soon as it is found.
+/// Targets are consumed lazily and at most `concurrency` probes are in flight, so memory stays
+/// constant however large the ranges; a full `out` channel holds probing back. Names are resolved
+/// best-effort to every address of `family` (each is checked) and skipped if unresolvable;
+/// addresses outside `family`, in `exclude` or out of the installed [`scope`] are skipped, as are
+/// TCP ports the scope does not allow. A TCP connection that is refused counts
+/// as live, like one that is accepted, and ARP/NDP skips addresses that are not on a local
+/// Ethernet segment. Returns once every probe has finished, or, after `opts.cancel` fires, once
+/// the probes in flight have finished or been dropped at its drain deadline. Fails if ICMP or ARP was requested but
+/// the sockets for it cannot be opened.
+pub async fn discover(
+    targets: impl IntoIterator<Item = Target>,
+    opts: &DiscoverOptions,
+    out: mpsc::Sender<Live>,
+) -> Result<()> {
+    let pinger = if opts.methods.contains(&Method::Icmp) {
+        Some(Arc::new(Pinger::new().map_err(|e| anyhow!("ICMP probes unavailable: {}", e))?))
+    } else { None };
+    let neighbors = if opts.methods.contains(&Method::Arp) {
+        Some(Arc::new(Neighbors::new().map_err(|e| anyhow!("ARP/NDP probes unavailable: {}", e))?))
+    } else { None };
+    let methods: Arc<[Method]> = opts.methods.as_slice().into();
+    let concurrency = opts.concurrency.max(1);
+    let sem = Arc::new(tokio::sync::Semaphore::new(concurrency));
+    let pacer = opts.qps.map(RateLimiter::new);
+    let dns = toolbox_core::dns::global();
+    let timeout_per_attempt = opts.timeout;
+
+    'targets: for target in targets {
+        if opts.cancel.is_cancelled() { break; }
+        if opts.exclude.excludes(&target) { opts.progress.done(1); continue; }
+        let name = match &target.host { Host::Name(n) => Some(n.as_str()), Host::Addr(_) => None };
+        let addrs = dns.resolve(&target.host, opts.family).await;
+        if name.is_some() && addrs.is_empty() { opts.progress.error(); }
+        for ip in addrs {
+            if opts.exclude.contains(&ip) || scope::check(name, Some(ip), None).is_err() { continue; }
+            if let Some(p) = &pacer { p.acquire().await; }
+            let txc = out.clone();
+            let permit = sem.clone().acquire_owned().await.unwrap();
+            if opts.cancel.is_cancelled() { break 'targets; }
+            let p: Vec<u16> = target.ports_or(&opts.ports).into_iter().filter(|&port| scope::check(name, Some(ip), Some(port)).is_ok()).collect();
+            let (methods, pinger, neighbors, progress, cancel) = (methods.clone(), pinger.clone(), neighbors.clone(), opts.progress.clone(), opts.cancel.clone());
+            progress.sent(1);
+            tokio::spawn(async move {
+                let probe = probe_host(ip, &methods, &p, timeout_per_attempt, pinger.as_deref(), neighbors.as_deref());
+                let Some(found) = cancel.drain(probe).await else { return };
+                progress.host_done();
+                if found.is_some() { progress.found(); }
+                if let Some(l) = found { let _ = txc.send(l).await; }
+                drop(permit);
+            });
+        }
+        opts.progress.done(1);
+    }
+    // Wait for the probes still in flight.
+    let _ = sem.acquire_many(concurrency as u32).await;
+    Ok(())
+}
+
+/// Discover live hosts among a set of targets with the given methods, concurrency and QPS pacing
+/// and collect them; see [`discover`].
 pub async fn discover_hosts(
-    ips: Vec<IpAddr>,
+    targets: impl IntoIterator<Item = Target>,
     ports: &[u16],
     timeout_per_attempt: Duration,
     concurrency: usize,
     qps: Option<u32>,
-) -> Vec<IpAddr> {
-    let (tx, mut rx) = mpsc::channel::<IpAddr>(ips.len());
-    let sem = std::sync::Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
-    let mut ticker = if let Some(q) = qps { let mut it = interval(Duration::from_millis((1000u32 / q.max(1)) as u64)); it.set_missed_tick_behavior(MissedTickBehavior::Delay); Some(it) } else { None };
-
-    for ip in ips {
-        if let Some(t) = ticker.as_mut() { t.tick().await; }
-        let txc = tx.clone();
-        let permit = sem.clone().acquire_owned().await.unwrap();
-        let p = ports.to_vec();
-        tokio::spawn(async move {
-            if is_host_live(ip, &p, timeout_per_attempt).await { let _ = txc.send(ip).await; }
-            drop(permit);
-        });
-    }
-    drop(tx);
-    let mut live = Vec::new();
-    while let Some(ip) = rx.recv().await { live.push(ip); }
-    live
+    family: Family,
+    methods: &[Method],
+) -> Result<Vec<Live>> {
+    let opts = DiscoverOptions { ports: ports.to_vec(), timeout: timeout_per_attempt, concurrency, qps, family, methods: methods.to_vec(), exclude: Exclusions::default(), cancel: Cancel::default(), progress: Progress::default() };
+    let (tx, mut rx) = mpsc::channel::<Live>(1024);
+    let collect = async {
+        let mut live = Vec::new();
+        while let Some(l) = rx.recv().await { live.push(l); }
+        live
+    };
+    let (done, live) = tokio::join!(discover(targets, &opts, tx), collect);
+    done.map(|_| live)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn methods_are_tried_in_order() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let lo: Target = "127.0.0.1".parse().unwrap();
+        let addr: IpAddr = "127.0.0.1".parse().unwrap();
+        let t = Duration::from_secs(1);
+        let live = discover_hosts([lo.clone()], &[open, closed], t, 4, None, Family::Any, &[Method::Tcp]).await.unwrap();
+        assert_eq!(live, vec![Live { addr, probe: Probe::TcpConnect, reason: Reason::Accepted, port: Some(open), mac: None }]);
+        // A refusal proves the host is up.
+        let live = discover_hosts([lo], &[closed, open], t, 4, None, Family::Any, &[Method::Tcp]).await.unwrap();
+        assert_eq!(live, vec![Live { addr, probe: Probe::TcpConnect, reason: Reason::Refused, port: Some(closed), mac: None }]);
+    }
+
+    /// Run it like `icmp::tests::pings_loopback`.
+    #[tokio::test]
+    #[ignore = "needs CAP_NET_RAW or ping sockets"]
+    async fn icmp_is_tried_first() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let lo: Target = "127.0.0.1".parse().unwrap();
+        let live = discover_hosts([lo], &[closed], Duration::from_secs(1), 4, None, Family::Any, &[Method::Icmp, Method::Tcp]).await.unwrap();
+        assert!(matches!(live[..], [Live { probe: Probe::IcmpEcho | Probe::IcmpTimestamp, port: None, .. }]));
+    }
+
+    #[tokio::test]
+    async fn streams_and_skips_exclusions() {
+        assert_eq!(expand_cidr("10.0.0.0/8").unwrap().nth(5), Some("10.0.0.6".parse().unwrap()));
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let set = toolbox_core::TargetSet::parse(["127.0.0.1-4"]).unwrap();
+        let opts = DiscoverOptions { ports: vec![closed], timeout: Duration::from_secs(1), concurrency: 2, exclude: Exclusions::parse(["127.0.0.2-3"]).unwrap(), ..Default::default() };
+        // A one-slot channel: probes wait for the receiver instead of queueing.
+        let (tx, mut rx) = mpsc::channel(1);
+        let run = discover(set.expand(), &opts, tx);
+        let collect = async {
+            let mut v = Vec::new();
+            while let Some(l) = rx.recv().await { v.push(l.addr.to_string()); }
+            v.sort();
+            v
+        };
+        let (done, live) = tokio::join!(run, collect);
+        done.unwrap();
+        assert_eq!(live, vec!["127.0.0.1", "127.0.0.4"]);
+        let p = opts.progress.snapshot();
+        assert_eq!((p.done, p.sent, p.hosts, p.found), (4, 2, 2, 2));
+    }
+
+    #[tokio::test]
+    async fn classifies_connect_outcomes() {
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let lo = "127.0.0.1".parse().unwrap();
+        assert_eq!(probe_tcp(lo, closed, Duration::from_secs(1)).await, TcpOutcome::Refused);
+        assert!(TcpOutcome::Refused.is_live() && !TcpOutcome::TimedOut.is_live() && !TcpOutcome::Unreachable.is_live());
+        #[cfg(unix)]
+        assert_eq!(TcpOutcome::from(port_scan::classify_error(&std::io::Error::from_raw_os_error(libc::EHOSTUNREACH))), TcpOutcome::Unreachable);
+    }
 }
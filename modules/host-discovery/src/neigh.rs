@@ -0,0 +1,382 @@
+//! ARP (IPv4) and NDP neighbor solicitation (IPv6) probes for on-link addresses (Linux).
+//!
+//! An address is on-link when it falls inside a prefix configured on an up, non-loopback Ethernet
+//! interface; the request goes out on that interface (longest prefix wins) and the answer carries
+//! the host's MAC address. ARP uses a packet socket and NDP a raw ICMPv6 socket, both of which
+//! need CAP_NET_RAW. A bound interface or source address (see [`toolbox_core::socket`]) limits
+//! the interfaces and prefixes considered.
+
+#[cfg(target_os = "linux")]
+pub use imp::Neighbors;
+
+#[cfg(not(target_os = "linux"))]
+use crate::MacAddr;
+#[cfg(not(target_os = "linux"))]
+use std::{io, net::IpAddr, time::Duration};
+
+#[cfg(not(target_os = "linux"))]
+pub struct Neighbors(());
+
+#[cfg(not(target_os = "linux"))]
+impl Neighbors {
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ARP/NDP discovery needs Linux"))
+    }
+
+    pub fn supports(&self, _ip: IpAddr) -> bool {
+        false
+    }
+
+    pub fn interface_for(&self, _ip: IpAddr) -> Option<&str> {
+        None
+    }
+
+    pub async fn probe(&self, _ip: IpAddr, _wait: Duration) -> io::Result<Option<MacAddr>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ARP/NDP discovery needs Linux"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use crate::MacAddr;
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use std::collections::HashMap;
+    use std::ffi::CStr;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV6};
+    use std::os::fd::AsRawFd;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    const ETH_P_ARP: u16 = 0x0806;
+    const ETH_P_IP: u16 = 0x0800;
+    const ARPHRD_ETHER: u16 = 1;
+    const ARP_REQUEST: u16 = 1;
+    const ARP_REPLY: u16 = 2;
+    const NEIGHBOR_SOLICITATION: u8 = 135;
+    const NEIGHBOR_ADVERTISEMENT: u8 = 136;
+    const OPT_SOURCE_LL: u8 = 1;
+    const OPT_TARGET_LL: u8 = 2;
+    /// `ICMPV6_FILTER` from linux/icmpv6.h (not exported by libc).
+    const ICMPV6_FILTER: libc::c_int = 1;
+
+    /// An up Ethernet interface and the prefixes configured on it.
+    struct Iface {
+        index: u32,
+        name: String,
+        mac: Option<MacAddr>,
+        v4: Vec<(Ipv4Addr, u32)>,
+        v6: Vec<(Ipv6Addr, u32)>,
+    }
+
+    struct Shared {
+        ifaces: Vec<Iface>,
+        arp: Option<Socket>,
+        ndp: Option<Socket>,
+        pending: Mutex<HashMap<IpAddr, oneshot::Sender<MacAddr>>>,
+        stop: AtomicBool,
+    }
+
+    /// Shared ARP/NDP prober; receive threads run until it is dropped.
+    pub struct Neighbors {
+        shared: Arc<Shared>,
+    }
+
+    impl Neighbors {
+        /// List local Ethernet interfaces and open the ARP and NDP sockets. Fails if neither can be
+        /// opened (no CAP_NET_RAW).
+        pub fn new() -> io::Result<Self> {
+            let binding = toolbox_core::socket::global();
+            let mut ifaces = interfaces()?;
+            if let Some(name) = &binding.interface { ifaces.retain(|i| i.name == *name); }
+            for iface in &mut ifaces {
+                if let Some(src) = binding.source_v4 { iface.v4.retain(|(a, _)| *a == src); }
+                if let Some(src) = binding.source_v6 { iface.v6.retain(|(a, _)| *a == src); }
+            }
+            let ndp = ndp_socket().and_then(|s| binding.bind_raw(&s, true).map(|_| s));
+            let (arp, ndp) = match (arp_socket(), ndp) {
+                (Err(e), Err(_)) => return Err(e),
+                (a, b) => (a.ok(), b.ok()),
+            };
+            let shared = Arc::new(Shared { ifaces, arp, ndp, pending: Mutex::new(HashMap::new()), stop: AtomicBool::new(false) });
+            for v6 in [false, true] {
+                let has = if v6 { shared.ndp.is_some() } else { shared.arp.is_some() };
+                if has {
+                    let shared = shared.clone();
+                    std::thread::spawn(move || recv_loop(&shared, v6));
+                }
+            }
+            Ok(Neighbors { shared })
+        }
+
+        /// True if `ip` is on a local Ethernet segment and its family's socket is open.
+        pub fn supports(&self, ip: IpAddr) -> bool {
+            let open = match ip { IpAddr::V4(_) => self.shared.arp.is_some(), IpAddr::V6(_) => self.shared.ndp.is_some() };
+            open && self.route(ip).is_some()
+        }
+
+        /// Name of the interface `ip` would be probed on, if it is on-link.
+        pub fn interface_for(&self, ip: IpAddr) -> Option<&str> {
+            self.route(ip).map(|(i, _)| i.name.as_str())
+        }
+
+        /// Interface whose longest matching prefix covers `ip`, with our address on it. Our own
+        /// addresses are not neighbors.
+        fn route(&self, ip: IpAddr) -> Option<(&Iface, IpAddr)> {
+            let mut best: Option<(&Iface, IpAddr, u32)> = None;
+            for iface in self.shared.ifaces.iter().filter(|i| i.mac.is_some()) {
+                let hits: Vec<(IpAddr, u32)> = match ip {
+                    IpAddr::V4(t) => iface.v4.iter().filter(|(a, p)| *a != t && same_prefix(u32::from(*a).into(), u32::from(t).into(), 32, *p)).map(|(a, p)| (IpAddr::V4(*a), *p)).collect(),
+                    IpAddr::V6(t) => iface.v6.iter().filter(|(a, p)| *a != t && same_prefix(u128::from(*a), u128::from(t), 128, *p)).map(|(a, p)| (IpAddr::V6(*a), *p)).collect(),
+                };
+                for (src, prefix) in hits {
+                    if !matches!(best, Some((_, _, b)) if b >= prefix) { best = Some((iface, src, prefix)); }
+                }
+            }
+            best.map(|(i, src, _)| (i, src))
+        }
+
+        /// Send an ARP request or neighbor solicitation for `ip` and wait up to `wait` for the
+        /// answer (`None` on timeout or if `ip` is not on-link).
+        pub async fn probe(&self, ip: IpAddr, wait: Duration) -> io::Result<Option<MacAddr>> {
+            let Some((iface, src)) = self.route(ip) else { return Ok(None) };
+            let mac = iface.mac.expect("routes only use interfaces with a MAC");
+            let (tx, rx) = oneshot::channel();
+            self.shared.pending.lock().unwrap().insert(ip, tx);
+            let sent = match (ip, src) {
+                (IpAddr::V4(t), IpAddr::V4(s)) => self.send_arp(iface.index, mac, s, t),
+                (IpAddr::V6(t), _) => self.send_solicitation(iface.index, mac, t),
+                _ => unreachable!("route matches the target's family"),
+            };
+            if let Err(e) = sent {
+                self.shared.pending.lock().unwrap().remove(&ip);
+                return Err(e);
+            }
+            let reply = tokio::time::timeout(wait, rx).await;
+            self.shared.pending.lock().unwrap().remove(&ip);
+            Ok(reply.ok().and_then(|r| r.ok()))
+        }
+
+        fn send_arp(&self, index: u32, mac: MacAddr, src: Ipv4Addr, target: Ipv4Addr) -> io::Result<()> {
+            let sock = self.shared.arp.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no ARP socket"))?;
+            let pkt = arp_request(mac, src, target);
+            // SAFETY: sockaddr_ll is plain data; all-zero is a valid value.
+            let mut to: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+            to.sll_family = libc::AF_PACKET as u16;
+            to.sll_protocol = ETH_P_ARP.to_be();
+            to.sll_ifindex = index as i32;
+            to.sll_halen = 6;
+            to.sll_addr[..6].copy_from_slice(&[0xff; 6]);
+            // SAFETY: the buffer and address outlive the call and their lengths are passed along.
+            let n = unsafe {
+                libc::sendto(sock.as_raw_fd(), pkt.as_ptr().cast(), pkt.len(), 0, (&to as *const libc::sockaddr_ll).cast(), std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t)
+            };
+            if n < 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+        }
+
+        fn send_solicitation(&self, index: u32, mac: MacAddr, target: Ipv6Addr) -> io::Result<()> {
+            let sock = self.shared.ndp.as_ref().ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no NDP socket"))?;
+            // The scope id picks the interface for the link-local multicast destination.
+            let to = SocketAddrV6::new(solicited_node(target), 0, 0, index);
+            sock.send_to(&solicitation(target, mac), &SockAddr::from(to)).map(|_| ())
+        }
+    }
+
+    impl Drop for Neighbors {
+        fn drop(&mut self) {
+            self.shared.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn same_prefix(a: u128, b: u128, bits: u32, prefix: u32) -> bool {
+        (a ^ b).checked_shr(bits - prefix.min(bits)).unwrap_or(0) == 0
+    }
+
+    fn arp_socket() -> io::Result<Socket> {
+        let sock = Socket::new(Domain::from(libc::AF_PACKET), Type::DGRAM, Some(Protocol::from(ETH_P_ARP.to_be() as i32)))?;
+        sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+        Ok(sock)
+    }
+
+    fn ndp_socket() -> io::Result<Socket> {
+        let sock = Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))?;
+        // Neighbor discovery messages must carry hop limit 255 or receivers drop them.
+        sock.set_multicast_hops_v6(255)?;
+        sock.set_unicast_hops_v6(255)?;
+        // Only advertisements are of interest; a set bit blocks that ICMPv6 type.
+        let mut filter = [u32::MAX; 8];
+        filter[(NEIGHBOR_ADVERTISEMENT >> 5) as usize] &= !(1 << (NEIGHBOR_ADVERTISEMENT & 31));
+        // SAFETY: the filter array outlives the call and its size is passed along.
+        let rc = unsafe {
+            libc::setsockopt(sock.as_raw_fd(), libc::IPPROTO_ICMPV6, ICMPV6_FILTER, filter.as_ptr().cast(), std::mem::size_of_val(&filter) as libc::socklen_t)
+        };
+        if rc != 0 { return Err(io::Error::last_os_error()); }
+        sock.set_read_timeout(Some(Duration::from_millis(100)))?;
+        Ok(sock)
+    }
+
+    fn recv_loop(shared: &Shared, v6: bool) {
+        let sock = if v6 { shared.ndp.as_ref() } else { shared.arp.as_ref() };
+        let Some(sock) = sock else { return };
+        let mut buf = [MaybeUninit::<u8>::uninit(); 1500];
+        while !shared.stop.load(Ordering::Relaxed) {
+            match sock.recv(&mut buf) {
+                Ok(n) => {
+                    // SAFETY: recv initialized the first n bytes.
+                    let data = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, n) };
+                    let answer = if v6 { parse_advertisement(data).map(|(ip, mac)| (IpAddr::V6(ip), mac)) } else { parse_arp_reply(data).map(|(ip, mac)| (IpAddr::V4(ip), mac)) };
+                    if let Some((ip, mac)) = answer {
+                        if let Some(tx) = shared.pending.lock().unwrap().remove(&ip) { let _ = tx.send(mac); }
+                    }
+                }
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Up, non-loopback interfaces with their hardware address (Ethernet only) and prefixes.
+    fn interfaces() -> io::Result<Vec<Iface>> {
+        let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+        // SAFETY: getifaddrs fills `head` with a list we free below.
+        if unsafe { libc::getifaddrs(&mut head) } != 0 { return Err(io::Error::last_os_error()); }
+        let mut out: Vec<Iface> = Vec::new();
+        let mut cur = head;
+        while !cur.is_null() {
+            // SAFETY: `cur` is a live node of the getifaddrs list; the address pointers are
+            // checked for null and point at the sockaddr type their family names.
+            unsafe {
+                let ifa = &*cur;
+                cur = ifa.ifa_next;
+                let flags = ifa.ifa_flags as libc::c_int;
+                if flags & libc::IFF_UP == 0 || flags & libc::IFF_LOOPBACK != 0 || ifa.ifa_addr.is_null() { continue; }
+                let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+                let pos = match out.iter().position(|i| i.name == name) {
+                    Some(p) => p,
+                    None => {
+                        out.push(Iface { index: 0, name, mac: None, v4: Vec::new(), v6: Vec::new() });
+                        out.len() - 1
+                    }
+                };
+                let iface = &mut out[pos];
+                let mask_bits = |len: usize| -> u32 {
+                    if ifa.ifa_netmask.is_null() { return (len * 8) as u32; }
+                    match len {
+                        4 => (*(ifa.ifa_netmask as *const libc::sockaddr_in)).sin_addr.s_addr.count_ones(),
+                        _ => (*(ifa.ifa_netmask as *const libc::sockaddr_in6)).sin6_addr.s6_addr.iter().map(|b| b.count_ones()).sum(),
+                    }
+                };
+                match (*ifa.ifa_addr).sa_family as libc::c_int {
+                    libc::AF_PACKET => {
+                        let ll = &*(ifa.ifa_addr as *const libc::sockaddr_ll);
+                        iface.index = ll.sll_ifindex as u32;
+                        if ll.sll_hatype == ARPHRD_ETHER && ll.sll_halen == 6 {
+                            let mut mac = [0u8; 6];
+                            mac.copy_from_slice(&ll.sll_addr[..6]);
+                            iface.mac = Some(MacAddr(mac));
+                        }
+                    }
+                    libc::AF_INET => {
+                        let sin = &*(ifa.ifa_addr as *const libc::sockaddr_in);
+                        iface.v4.push((Ipv4Addr::from(sin.sin_addr.s_addr.to_ne_bytes()), mask_bits(4)));
+                    }
+                    libc::AF_INET6 => {
+                        let sin6 = &*(ifa.ifa_addr as *const libc::sockaddr_in6);
+                        iface.v6.push((Ipv6Addr::from(sin6.sin6_addr.s6_addr), mask_bits(16)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        // SAFETY: `head` came from getifaddrs and nothing borrows from the list any more.
+        unsafe { libc::freeifaddrs(head) };
+        Ok(out)
+    }
+
+    /// ARP request body for a cooked packet socket (the kernel adds the Ethernet header).
+    pub(super) fn arp_request(sha: MacAddr, spa: Ipv4Addr, tpa: Ipv4Addr) -> [u8; 28] {
+        let mut p = [0u8; 28];
+        p[0..2].copy_from_slice(&ARPHRD_ETHER.to_be_bytes());
+        p[2..4].copy_from_slice(&ETH_P_IP.to_be_bytes());
+        p[4] = 6;
+        p[5] = 4;
+        p[6..8].copy_from_slice(&ARP_REQUEST.to_be_bytes());
+        p[8..14].copy_from_slice(&sha.0);
+        p[14..18].copy_from_slice(&spa.octets());
+        p[24..28].copy_from_slice(&tpa.octets());
+        p
+    }
+
+    /// Sender of an Ethernet/IPv4 ARP reply.
+    pub(super) fn parse_arp_reply(p: &[u8]) -> Option<(Ipv4Addr, MacAddr)> {
+        if p.len() < 28 || p[0..2] != ARPHRD_ETHER.to_be_bytes() || p[2..4] != ETH_P_IP.to_be_bytes() || p[4] != 6 || p[5] != 4 { return None; }
+        if p[6..8] != ARP_REPLY.to_be_bytes() { return None; }
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&p[8..14]);
+        Some((Ipv4Addr::new(p[14], p[15], p[16], p[17]), MacAddr(mac)))
+    }
+
+    /// ff02::1:ffXX:XXXX, the multicast group `target` listens on for solicitations.
+    pub(super) fn solicited_node(target: Ipv6Addr) -> Ipv6Addr {
+        let o = target.octets();
+        Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xff00 | o[13] as u16, u16::from_be_bytes([o[14], o[15]]))
+    }
+
+    /// Neighbor solicitation with a source link-layer option. The kernel fills in the checksum.
+    pub(super) fn solicitation(target: Ipv6Addr, mac: MacAddr) -> [u8; 32] {
+        let mut p = [0u8; 32];
+        p[0] = NEIGHBOR_SOLICITATION;
+        p[8..24].copy_from_slice(&target.octets());
+        p[24] = OPT_SOURCE_LL;
+        p[25] = 1;
+        p[26..32].copy_from_slice(&mac.0);
+        p
+    }
+
+    /// Target and target link-layer address of a neighbor advertisement. Advertisements without
+    /// the option are ignored (answers to multicast solicitations must carry it).
+    pub(super) fn parse_advertisement(p: &[u8]) -> Option<(Ipv6Addr, MacAddr)> {
+        if p.len() < 24 || p[0] != NEIGHBOR_ADVERTISEMENT { return None; }
+        let mut target = [0u8; 16];
+        target.copy_from_slice(&p[8..24]);
+        let mut opts = &p[24..];
+        while opts.len() >= 8 {
+            let len = opts[1] as usize * 8;
+            if len == 0 || len > opts.len() { return None; }
+            if opts[0] == OPT_TARGET_LL {
+                let mut mac = [0u8; 6];
+                mac.copy_from_slice(&opts[2..8]);
+                return Some((Ipv6Addr::from(target), MacAddr(mac)));
+            }
+            opts = &opts[len..];
+        }
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::imp::*;
+    use crate::MacAddr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn builds_and_parses_neighbor_messages() {
+        let mac = MacAddr([0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+        let mut reply = arp_request(mac, Ipv4Addr::new(10, 0, 0, 7), Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(parse_arp_reply(&reply), None);
+        reply[7] = 2;
+        assert_eq!(parse_arp_reply(&reply), Some((Ipv4Addr::new(10, 0, 0, 7), mac)));
+
+        let target: Ipv6Addr = "2001:db8::1:2345:6789".parse().unwrap();
+        assert_eq!(solicited_node(target), "ff02::1:ff45:6789".parse::<Ipv6Addr>().unwrap());
+        let mut adv = solicitation(target, mac);
+        assert_eq!(parse_advertisement(&adv), None);
+        adv[0] = 136;
+        adv[24] = 2;
+        assert_eq!(parse_advertisement(&adv), Some((target, mac)));
+    }
+}
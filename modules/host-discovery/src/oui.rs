@@ -0,0 +1,55 @@
+//! MAC addresses and vendor lookup from the bundled OUI table (`data/oui.txt`). The table in the
+//! repository is a sample of common vendors; `make oui` regenerates it from the IEEE registry.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+const TABLE: &str = include_str!("../data/oui.txt");
+
+/// An Ethernet hardware address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    /// Vendor registered for the address's OUI, if it is in the bundled table.
+    pub fn vendor(&self) -> Option<&'static str> {
+        static VENDORS: OnceLock<HashMap<[u8; 3], &'static str>> = OnceLock::new();
+        let vendors = VENDORS.get_or_init(|| TABLE.lines().filter_map(parse_line).collect());
+        vendors.get(&[self.0[0], self.0[1], self.0[2]]).copied()
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let m = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+}
+
+fn parse_line(line: &str) -> Option<([u8; 3], &str)> {
+    if line.starts_with('#') { return None; }
+    let (prefix, vendor) = line.split_once('\t')?;
+    let mut oui = [0u8; 3];
+    let mut parts = prefix.split(':');
+    for b in &mut oui { *b = u8::from_str_radix(parts.next()?, 16).ok()?; }
+    if parts.next().is_some() { return None; }
+    Some((oui, vendor.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_vendors() {
+        let vm = MacAddr([0x00, 0x50, 0x56, 0xab, 0x01, 0x02]);
+        assert_eq!(vm.to_string(), "00:50:56:ab:01:02");
+        // Prefix only: the full registry (`make oui`) has the registered name, "VMware, Inc.".
+        assert!(vm.vendor().is_some_and(|v| v.starts_with("VMware")), "{:?}", vm.vendor());
+        assert_eq!(MacAddr([0x02, 0, 0, 0, 0, 1]).vendor(), None);
+        // Every data line parses.
+        let entries = TABLE.lines().filter(|l| !l.starts_with('#')).count();
+        assert_eq!(TABLE.lines().filter_map(parse_line).count(), entries);
+    }
+}
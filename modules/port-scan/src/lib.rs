@@ -1,16 +1,210 @@
-//! TCP connect scan with timeouts and concurrency.
+//! TCP port scanning (connect, or raw SYN on Linux) with timeouts and concurrency.
 
 use anyhow::{anyhow, Result};
-use std::net::ToSocketAddrs;
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use tokio::sync::Semaphore;
-use tokio::time::timeout;
-use toolbox_core::Target;
+use toolbox_core::{Family, Host, Target, TargetSet};
+use toolbox_core::cancel::Cancel;
+use toolbox_core::congestion::Aimd;
+use toolbox_core::policy::{Capability, Level};
 use toolbox_core::ratelimiter::RateLimiter;
-use rand::{thread_rng, Rng};
+
+mod permute;
+mod pool;
+mod resume;
+mod rtt;
+mod syn;
+pub use permute::Permutation;
+pub use pool::{HostDone, ScanEvent};
+pub use resume::{Progress, Resume};
+pub use rtt::{RttBounds, RttEstimator};
+pub use syn::{SynReply, SynScanner};
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "port-scan.connect", level: Level::SafeActive, summary: "TCP connect scan" },
+    Capability { name: "port-scan.syn", level: Level::SafeActive, summary: "half-open SYN scan with raw sockets" },
+];
+
+/// Port state as stored in the results schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortState {
+    Open,
+    Closed,
+    Filtered,
+}
+
+impl PortState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        }
+    }
+}
+
+/// Why a port was given its state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// Handshake completed.
+    SynAck,
+    /// Connection actively refused (RST).
+    ConnRefused,
+    /// No answer within the timeout.
+    Timeout,
+    /// Host or network unreachable (ICMP or local routing error).
+    HostUnreach,
+}
+
+impl Reason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Reason::SynAck => "syn-ack",
+            Reason::ConnRefused => "conn-refused",
+            Reason::Timeout => "timeout",
+            Reason::HostUnreach => "host-unreach",
+        }
+    }
+
+    pub fn state(&self) -> PortState {
+        match self {
+            Reason::SynAck => PortState::Open,
+            Reason::ConnRefused => PortState::Closed,
+            Reason::Timeout | Reason::HostUnreach => PortState::Filtered,
+        }
+    }
+}
+
+impl std::str::FromStr for Reason {
+    type Err = anyhow::Error;
+
+    /// Inverse of [`Reason::as_str`].
+    fn from_str(s: &str) -> Result<Self> {
+        [Reason::SynAck, Reason::ConnRefused, Reason::Timeout, Reason::HostUnreach]
+            .into_iter()
+            .find(|r| r.as_str() == s)
+            .ok_or_else(|| anyhow!("unknown reason: {}", s))
+    }
+}
+
+/// Outcome for one scanned port. `rtt` is set when the target answered (open or closed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortResult {
+    pub port: u16,
+    pub state: PortState,
+    pub reason: Reason,
+    pub rtt: Option<Duration>,
+}
+
+impl PortResult {
+    pub fn is_open(&self) -> bool {
+        self.state == PortState::Open
+    }
+}
+
+/// A finished port, streamed as soon as its last attempt completes.
+#[derive(Debug, Clone)]
+pub struct PortEvent {
+    /// Index of the target in scan order.
+    pub host: usize,
+    /// The target's name, or the name an address target was resolved from earlier in the run.
+    pub hostname: Option<String>,
+    /// Address that was probed.
+    pub addr: String,
+    pub result: PortResult,
+    /// Unix time (ms) of the first attempt's start and the last attempt's end.
+    pub t_first: i64,
+    pub t_last: i64,
+    /// Position in the order of a permuted scan; `None` for sequential scans.
+    pub seq: Option<u64>,
+}
+
+/// How ports are probed.
+#[derive(Clone, Default)]
+pub enum ScanMode {
+    /// Full handshake with `connect()`.
+    #[default]
+    Connect,
+    /// Raw SYN without completing the handshake; families without a raw socket use `connect()`.
+    Syn(Arc<SynScanner>),
+}
+
+/// Limits and pacing shared by every port of a scan.
+#[derive(Clone)]
+pub struct ScanOptions {
+    pub timeouts: RttBounds,
+    pub per_host_concurrency: usize,
+    pub dns_retries: u32,
+    pub dns_retry_delay: Duration,
+    pub global_qps: Option<Arc<RateLimiter>>,
+    pub retries: u32,
+    pub retry_delay: Duration,
+    pub global_limit: Option<Arc<Semaphore>>,
+    pub adaptive: Option<Aimd>,
+    /// Probes in flight across all targets.
+    pub workers: usize,
+    /// Targets open at once.
+    pub max_hosts: usize,
+    pub mode: ScanMode,
+    /// Address family to probe; names are scanned on every address of it.
+    pub family: Family,
+    /// Work already done by an earlier run, which is skipped.
+    pub resume: Option<Arc<Resume>>,
+    /// Once cancelled no new probes start; probes still in flight at the drain deadline are
+    /// dropped without an event.
+    pub cancel: Cancel,
+    /// Counts probes sent, targets done, open ports and unreachable or unresolvable targets. Work
+    /// is counted in target×port pairs (skipped and resumed ones included), so a total of targets
+    /// times ports is reached when the scan ends.
+    pub progress: toolbox_core::progress::Progress,
+}
+
+impl ScanOptions {
+    /// One target at a time with `per_host_concurrency` workers; no retries, pacing or global
+    /// limits.
+    pub fn new(timeouts: RttBounds, per_host_concurrency: usize) -> Self {
+        ScanOptions {
+            timeouts,
+            per_host_concurrency,
+            dns_retries: 0,
+            dns_retry_delay: Duration::ZERO,
+            global_qps: None,
+            retries: 0,
+            retry_delay: Duration::ZERO,
+            global_limit: None,
+            adaptive: None,
+            workers: per_host_concurrency.max(1),
+            max_hosts: 1,
+            mode: ScanMode::Connect,
+            family: Family::Any,
+            resume: None,
+            cancel: Cancel::default(),
+            progress: Default::default(),
+        }
+    }
+}
+
+pub(crate) fn unix_ms() -> i64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+/// Classify a connect error.
+pub fn classify_error(e: &std::io::Error) -> Reason {
+    if e.kind() == std::io::ErrorKind::ConnectionRefused { return Reason::ConnRefused; }
+    #[cfg(unix)]
+    if matches!(e.raw_os_error(), Some(libc::EHOSTUNREACH) | Some(libc::ENETUNREACH)) {
+        return Reason::HostUnreach;
+    }
+    #[cfg(windows)]
+    if matches!(e.raw_os_error(), Some(10065) | Some(10051)) {
+        return Reason::HostUnreach;
+    }
+    Reason::Timeout
+}
 
 /// Parse a comma-separated list of ports/ranges (e.g., "22,80,443", "1-1024,8080").
 pub fn parse_ports(spec: &str) -> Result<Vec<u16>> {
@@ -52,92 +246,107 @@ pub fn top_ports(n: usize) -> Vec<u16> {
     CURATED[..take].to_vec()
 }
 
-/// Asynchronously scan the given ports on a target using TCP connect with a timeout.
-/// Returns the list of open ports (sorted ascending).
-pub async fn scan_connect_with_limits(
-    target: &str,
-    ports: &[u16],
-    timeout_per_port: Duration,
-    per_host_concurrency: usize,
-    dns_retries: u32,
-    dns_retry_delay: Duration,
-    global_qps: Option<Arc<RateLimiter>>,
-    retries: u32,
-    retry_delay: Duration,
-    global_limit: Option<Arc<Semaphore>>,
-) -> Vec<u16> {
-    let t: Target = target.into();
-
-    let host = resolve_best_effort(&t.0, dns_retries, dns_retry_delay);
-
-    let host_sem = Arc::new(Semaphore::new(per_host_concurrency.max(1)));
-    let (tx, mut rx) = mpsc::channel::<u16>(ports.len());
-
-    for &port in ports {
-        let tx = tx.clone();
-        let host = host.clone();
-        let host_sem = host_sem.clone();
-        let global = global_limit.clone();
-        let qps_rl = global_qps.clone();
-        tokio::spawn(async move {
-            let _host_permit = host_sem.acquire_owned().await.unwrap();
-            let _global_permit = match global {
-                Some(g) => Some(g.acquire_owned().await.unwrap()),
-                None => None,
-            };
-            if let Some(q) = qps_rl { q.acquire().await; }
-            let addr = (host.as_str(), port);
-            let mut attempts = 0;
-            let mut opened = false;
-            while attempts <= retries {
-                let result = timeout(timeout_per_port, TcpStream::connect(addr)).await;
-                if let Ok(Ok(_stream)) = result { opened = true; break; }
-                attempts += 1;
-                if attempts <= retries {
-                    let base = retry_delay.as_millis() as u64;
-                    let exp = base.saturating_mul(1u64 << (attempts.min(6))); // cap growth
-                    let jitter = thread_rng().gen_range(0..(exp / 4 + 1));
-                    tokio::time::sleep(Duration::from_millis(exp + jitter)).await;
-                }
+/// Scan many targets with a fixed pool of `opts.workers` workers, drawing targets lazily from
+/// `targets` and keeping at most `opts.max_hosts` of them open at once. Open targets are served
+/// round-robin. Each port is sent as [`ScanEvent::Port`] as soon as it is decided, followed by
+/// [`ScanEvent::HostDone`] once a target is finished. Returns when every target is done (or the
+/// receiver is dropped).
+/// A target with an explicit port (`host:port`, URL) is scanned on that port only. Names are
+/// probed on every address of `opts.family` (one event per address and port); a target with no
+/// such address gets a `HostDone` with no addresses.
+/// Connect RTTs (including refusals) feed a per-host estimator, and each attempt's timeout is
+/// derived from it within `opts.timeouts`.
+/// With `opts.adaptive`, each attempt feeds the AIMD controller (refusals count as responses)
+/// and the per-host concurrency follows its window instead of `opts.per_host_concurrency`.
+/// Refusals are final; timeouts and unreachable errors are retried up to `opts.retries` times.
+/// Once `opts.cancel` is cancelled, no new probes or retries start and targets left unfinished get
+/// no `HostDone`.
+pub async fn scan_many<I>(targets: I, ports: &[u16], opts: &ScanOptions, events: mpsc::Sender<ScanEvent>)
+where
+    I: IntoIterator<Item = Target>,
+    I::IntoIter: Send + 'static,
+{
+    pool::Pool::new(Box::new(targets.into_iter()), ports, opts.clone(), events).run().await
+}
+
+/// Like [`scan_many`], but visits every (host, port) pair of `targets` in a pseudo-random order
+/// derived from `seed`, spreading load evenly across targets. The order is reproducible for a
+/// given seed, target set and port list. Only [`ScanEvent::Port`], [`ScanEvent::NoAddress`] and
+/// [`ScanEvent::Position`] events are sent, the last once a position is finished; RTTs are
+/// estimated across all hosts and `opts.per_host_concurrency`/`opts.max_hosts` do not apply.
+pub async fn scan_permuted(targets: &TargetSet, ports: &[u16], seed: u64, opts: &ScanOptions, events: mpsc::Sender<ScanEvent>) -> Result<()> {
+    let index = targets.index()?;
+    let pool = pool::Pool::permuted(index, ports, seed, opts.clone(), events).ok_or_else(|| anyhow!("scan space too large to permute"))?;
+    pool.run().await;
+    Ok(())
+}
+
+/// Scan a single target, sending a [`PortEvent`] for each port as soon as it is decided.
+/// See [`scan_many`].
+pub async fn scan_connect_stream(target: &Target, ports: &[u16], opts: &ScanOptions, events: mpsc::Sender<PortEvent>) {
+    let opts = ScanOptions { max_hosts: 1, ..opts.clone() };
+    let (tx, mut rx) = mpsc::channel(256);
+    let forward = async {
+        while let Some(ev) = rx.recv().await {
+            if let ScanEvent::Port(p) = ev {
+                if events.send(p).await.is_err() { break; }
             }
-            if opened { let _ = tx.send(port).await; }
-        });
-    }
-    drop(tx);
+        }
+    };
+    tokio::join!(scan_many(std::iter::once(target.clone()), ports, &opts, tx), forward);
+}
 
-    let mut open = Vec::new();
-    while let Some(p) = rx.recv().await {
-        open.push(p);
-    }
-    open.sort_unstable();
-    open
+/// Scan a target and collect one result per port (sorted by port); for a name with several
+/// addresses the most responsive state wins. Always a connect scan; with `opts.adaptive` the
+/// workers follow the AIMD window instead of `opts.workers`. See [`scan_connect_stream`].
+pub async fn scan_connect_with_limits(target: &Target, ports: &[u16], opts: &ScanOptions) -> Vec<PortResult> {
+    let workers = opts.adaptive.as_ref().map(|a| a.max_window()).unwrap_or(opts.workers);
+    let opts = ScanOptions { workers, mode: ScanMode::Connect, resume: None, ..opts.clone() };
+    let (tx, mut rx) = mpsc::channel::<PortEvent>(256);
+    let collect = async {
+        let mut results = Vec::new();
+        while let Some(ev) = rx.recv().await {
+            results.push(ev.result);
+        }
+        results
+    };
+    let ((), mut results) = tokio::join!(scan_connect_stream(target, ports, &opts, tx), collect);
+    results.sort_unstable_by_key(|r| (r.port, r.state as u8));
+    results.dedup_by_key(|r| r.port);
+    results
 }
 
 /// Backwards-compatible wrapper: scan with only per-host concurrency, no DNS retries.
+/// Returns the open ports.
 pub async fn scan_connect(
-    target: &str,
+    target: &Target,
     ports: &[u16],
     timeout_per_port: Duration,
     concurrency: usize,
 ) -> Vec<u16> {
-    scan_connect_with_limits(target, ports, timeout_per_port, concurrency, 0, Duration::from_millis(0), None, 0, Duration::from_millis(0), None).await
+    scan_connect_with_limits(target, ports, &ScanOptions::new(RttBounds::fixed(timeout_per_port), concurrency))
+        .await
+        .into_iter()
+        .filter(|r| r.is_open())
+        .map(|r| r.port)
+        .collect()
 }
 
-/// Resolve a host once with limited retries. Returns an IP string on success, or the original
-/// input if resolution fails.
-pub fn resolve_best_effort(host: &str, dns_retries: u32, retry_delay: Duration) -> String {
+/// Every address of `family` for a host via the shared resolver ([`toolbox_core::dns`]), retrying
+/// an empty lookup up to `dns_retries` times.
+pub async fn resolve_all(host: &Host, family: Family, dns_retries: u32, retry_delay: Duration) -> Vec<IpAddr> {
+    let dns = toolbox_core::dns::global();
     let attempts = dns_retries.saturating_add(1);
     for i in 0..attempts {
-        if let Ok(mut iter) = (host, 0u16).to_socket_addrs() {
-            if let Some(sock) = iter.next() {
-                return sock.ip().to_string();
-            }
-        }
-        if i + 1 < attempts && retry_delay.as_millis() > 0 {
-            std::thread::sleep(retry_delay);
+        let addrs = dns.resolve(host, family).await;
+        let Host::Name(name) = host else { return addrs };
+        if !addrs.is_empty() { return addrs; }
+        if i + 1 < attempts {
+            dns.forget(name);
+            tokio::time::sleep(retry_delay).await;
         }
     }
-    host.to_string()
+    Vec::new()
 }
 
 #[cfg(test)]
@@ -161,4 +370,37 @@ mod tests {
         assert!(parse_ports("0").is_err());
         assert!(parse_ports("10-5").is_err());
     }
+
+    #[tokio::test]
+    async fn open_and_closed_states() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = {
+            let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            l.local_addr().unwrap().port()
+        };
+        let target: Target = "127.0.0.1".parse().unwrap();
+        let res = scan_connect_with_limits(&target, &[open, closed], &ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 4)).await;
+        let by_port = |p| res.iter().find(|r| r.port == p).unwrap();
+        assert_eq!((by_port(open).state, by_port(open).reason), (PortState::Open, Reason::SynAck));
+        assert_eq!((by_port(closed).state, by_port(closed).reason), (PortState::Closed, Reason::ConnRefused));
+        assert!(by_port(closed).rtt.is_some());
+    }
+
+    #[tokio::test]
+    async fn stream_emits_each_port_before_returning() {
+        // Not 127.0.0.1: other tests resolve `localhost`, which names that address from then on.
+        let listener = tokio::net::TcpListener::bind("127.0.0.2:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let target: Target = "127.0.0.2".parse().unwrap();
+        let opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 2);
+        let (tx, mut rx) = mpsc::channel(1);
+        let scan = tokio::spawn(async move { scan_connect_stream(&target, &[open, open.wrapping_add(1).max(1)], &opts, tx).await });
+        let first = rx.recv().await.unwrap();
+        assert_eq!(first.addr, "127.0.0.2");
+        assert!(first.hostname.is_none() && first.t_last >= first.t_first);
+        assert!(rx.recv().await.is_some());
+        scan.await.unwrap();
+        assert!(rx.recv().await.is_none());
+    }
 }
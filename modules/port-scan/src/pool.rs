@@ -0,0 +1,681 @@
+//! Fixed-size worker pool that draws (host, port) pairs lazily.
+//!
+//! In sequential order at most `max_hosts` targets are open at a time and a target is only pulled
+//! from the iterator when a slot frees up. In permuted order every pair is drawn from a seeded
+//! [`Permutation`] over hosts×ports and targets are looked up by index, with no per-host state.
+//! Either way memory stays flat regardless of how many targets or ports are scanned.
+//!
+//! With [`ScanOptions::resume`] set, work recorded as finished by an earlier run is skipped. When
+//! [`ScanOptions::cancel`] fires, workers stop drawing work and a probe not decided by the drain
+//! deadline is dropped, so the work left over is exactly what a resumed run would still do.
+//! [`ScanOptions::progress`] counts each (target, port) pair as done once decided or skipped.
+
+use crate::permute::Permutation;
+use crate::rtt::RttEstimator;
+use crate::syn::SynReply;
+use crate::{classify_error, resolve_all, unix_ms, PortEvent, PortResult, Reason, ScanMode, ScanOptions};
+use rand::{thread_rng, Rng};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Notify, OnceCell, Semaphore};
+use tokio::time::timeout;
+use toolbox_core::congestion::Feedback;
+use toolbox_core::{audit, connect, scope, Host, Target, TargetIndex};
+
+/// Event from [`crate::scan_many`]. A target's `HostDone` follows all of its `Port` events.
+/// Permuted scans ([`crate::scan_permuted`]) emit `Port`, `NoAddress` and `Position` events only;
+/// every position gets exactly one `NoAddress` or `Position` unless the scan is cancelled first.
+#[derive(Debug, Clone)]
+pub enum ScanEvent {
+    Port(PortEvent),
+    HostDone(HostDone),
+    /// A permuted-order position that was skipped because its target has no address of the
+    /// requested family (or did not resolve).
+    NoAddress { host: usize, target: Target, seq: u64 },
+    /// A permuted-order position that is finished: every address of its target has been probed
+    /// (its `Port` events came first), or it had nothing to probe, like the unused port slots of a
+    /// target with its own port or a target whose addresses are all out of scope.
+    Position { seq: u64 },
+}
+
+/// A target whose ports have all been decided.
+#[derive(Debug, Clone)]
+pub struct HostDone {
+    /// Index of the target in iteration order (matches [`PortEvent::host`]).
+    pub host: usize,
+    pub target: Target,
+    /// Addresses that were probed; empty if the target had none of the requested family.
+    pub addrs: Vec<IpAddr>,
+    /// Ports per address.
+    pub scanned: usize,
+    /// Time from the target being opened to its last port finishing.
+    pub elapsed: Duration,
+}
+
+/// Per-host semaphore whose size follows the AIMD window.
+struct HostWindow {
+    sem: Arc<Semaphore>,
+    size: Mutex<usize>,
+}
+
+impl HostWindow {
+    fn new(size: usize) -> Self {
+        let size = size.max(1);
+        HostWindow { sem: Arc::new(Semaphore::new(size)), size: Mutex::new(size) }
+    }
+
+    fn resize(&self, target: usize) {
+        let target = target.max(1);
+        let mut size = self.size.lock().unwrap();
+        if target > *size {
+            self.sem.add_permits(target - *size);
+        } else if target < *size {
+            // Shrink what is idle now; the rest is taken back as in-flight probes finish.
+            let owed = (*size - target) - self.sem.forget_permits(*size - target);
+            if owed > 0 {
+                let sem = self.sem.clone();
+                tokio::spawn(async move {
+                    if let Ok(p) = sem.acquire_many_owned(owed as u32).await { p.forget(); }
+                });
+            }
+        }
+        *size = target;
+    }
+}
+
+/// State of one open target.
+struct HostScan {
+    index: usize,
+    target: Target,
+    /// Ports left to probe (fewer than requested when resuming).
+    ports: Arc<[u16]>,
+    /// Ports requested, including those decided by an earlier run.
+    scanned: usize,
+    /// Next port index to hand out.
+    next: AtomicUsize,
+    /// Ports not yet decided.
+    remaining: AtomicUsize,
+    window: HostWindow,
+    rtt: Mutex<RttEstimator>,
+    addrs: OnceCell<Arc<[IpAddr]>>,
+    opened: Instant,
+}
+
+impl HostScan {
+    async fn addrs(&self, opts: &ScanOptions) -> Arc<[IpAddr]> {
+        self.addrs.get_or_init(|| resolve(&self.target.host, opts)).await.clone()
+    }
+}
+
+/// Every address of the host in `opts.family` that is in scope (each one is probed).
+async fn resolve(host: &Host, opts: &ScanOptions) -> Arc<[IpAddr]> {
+    let name = match host { Host::Name(n) => Some(n.as_str()), Host::Addr(_) => None };
+    let addrs = resolve_all(host, opts.family, opts.dns_retries, opts.dns_retry_delay).await;
+    addrs.into_iter().filter(|ip| scope::check(name, Some(*ip), None).is_ok()).collect()
+}
+
+/// The target's name, or for a literal the name it was resolved from earlier in this process.
+fn hostname(target: &Target) -> Option<String> {
+    match &target.host {
+        Host::Name(n) => Some(n.clone()),
+        Host::Addr(ip) => toolbox_core::dns::global().hostname(ip),
+    }
+}
+
+struct Source {
+    targets: Box<dyn Iterator<Item = Target> + Send>,
+    exhausted: bool,
+    next_index: usize,
+    /// Open hosts that still have ports to hand out, served round-robin.
+    active: VecDeque<Arc<HostScan>>,
+    /// Hosts opened and not yet done (including ones with only in-flight ports left).
+    open_hosts: usize,
+}
+
+/// Seeded walk over the hosts×ports index space.
+struct Permuted {
+    index: TargetIndex,
+    perm: Permutation,
+    next: u64,
+}
+
+enum Order {
+    Sequential(Source),
+    Permuted(Permuted),
+}
+
+/// One probe to run.
+enum Job {
+    /// Port of an open host; counts towards its `HostDone`.
+    Host(Arc<HostScan>, u16),
+    /// Resumed host whose ports were all decided by the earlier run.
+    Finish(Arc<HostScan>),
+    /// Stand-alone probe from a permuted walk.
+    Spread { index: u64, seq: u64, target: Target, port: u16 },
+    /// Permuted position with nothing to probe.
+    Skip(u64),
+}
+
+pub(crate) struct Pool {
+    order: Mutex<Order>,
+    host_done: Notify,
+    ports: Arc<[u16]>,
+    opts: ScanOptions,
+    events: mpsc::Sender<ScanEvent>,
+    /// Permuted order: RTT estimate shared by all hosts, and resolved names (kept for the whole
+    /// scan so every position of a target sees the same addresses).
+    spread_rtt: Mutex<RttEstimator>,
+    names: Mutex<HashMap<String, Arc<[IpAddr]>>>,
+}
+
+impl Pool {
+    pub(crate) fn new(targets: Box<dyn Iterator<Item = Target> + Send>, ports: &[u16], opts: ScanOptions, events: mpsc::Sender<ScanEvent>) -> Self {
+        let source = Source { targets, exhausted: false, next_index: 0, active: VecDeque::new(), open_hosts: 0 };
+        Self::with_order(Order::Sequential(source), ports, opts, events)
+    }
+
+    /// Pool visiting hosts×ports in the order given by `seed`. Fails if the space exceeds u64.
+    pub(crate) fn permuted(index: TargetIndex, ports: &[u16], seed: u64, opts: ScanOptions, events: mpsc::Sender<ScanEvent>) -> Option<Self> {
+        let n = index.len().checked_mul(ports.len().max(1) as u64)?;
+        let next = opts.resume.as_ref().map(|r| r.done.watermark()).unwrap_or(0);
+        let order = Order::Permuted(Permuted { index, perm: Permutation::new(n, seed), next });
+        Some(Self::with_order(order, ports, opts, events))
+    }
+
+    fn with_order(order: Order, ports: &[u16], opts: ScanOptions, events: mpsc::Sender<ScanEvent>) -> Self {
+        let spread_rtt = Mutex::new(RttEstimator::new(opts.timeouts));
+        Pool { order: Mutex::new(order), host_done: Notify::new(), ports: ports.into(), opts, events, spread_rtt, names: Mutex::new(HashMap::new()) }
+    }
+
+    /// Run `opts.workers` workers until every target is done.
+    pub(crate) async fn run(self) {
+        let pool = Arc::new(self);
+        let workers = pool.opts.workers.max(1);
+        let handles: Vec<_> = (0..workers).map(|_| tokio::spawn(pool.clone().worker())).collect();
+        for h in handles { let _ = h.await; }
+    }
+
+    fn open_host(&self, index: usize, target: Target) -> Arc<HostScan> {
+        let mut ports: Arc<[u16]> = match target.port { Some(p) => Arc::from([p]), None => self.ports.clone() };
+        let scanned = ports.len();
+        if let Some(done) = self.opts.resume.as_ref().and_then(|r| r.partial.get(&index)) {
+            ports = ports.iter().copied().filter(|p| !done.contains(p)).collect();
+            self.opts.progress.done((scanned - ports.len()) as u64);
+        }
+        let window = self.opts.adaptive.as_ref().map(|a| a.window()).unwrap_or(self.opts.per_host_concurrency);
+        Arc::new(HostScan {
+            index,
+            target,
+            remaining: AtomicUsize::new(ports.len()),
+            ports,
+            scanned,
+            next: AtomicUsize::new(0),
+            window: HostWindow::new(window),
+            rtt: Mutex::new(RttEstimator::new(self.opts.timeouts)),
+            addrs: OnceCell::new(),
+            opened: Instant::now(),
+        })
+    }
+
+    /// Next probe; in sequential order waits for a host slot when all open hosts are handed out.
+    /// None once cancelled or once the receiver is gone.
+    async fn next(&self) -> Option<Job> {
+        loop {
+            if self.opts.cancel.is_cancelled() || self.events.is_closed() { return None; }
+            let notified = self.host_done.notified();
+            {
+                let mut order = self.order.lock().unwrap();
+                let src = match &mut *order {
+                    Order::Sequential(src) => src,
+                    Order::Permuted(p) => return self.next_permuted(p),
+                };
+                while src.open_hosts < self.opts.max_hosts.max(1) && !src.exhausted {
+                    match src.targets.next() {
+                        Some(t) => {
+                            let index = src.next_index;
+                            src.next_index += 1;
+                            let pairs = if t.port.is_some() { 1 } else { self.ports.len() as u64 };
+                            if self.opts.resume.as_ref().is_some_and(|r| r.done.contains(index as u64)) { self.opts.progress.done(pairs); continue; }
+                            // Only one pair for a target with its own port; the total counts every port
+                            self.opts.progress.done((self.ports.len() as u64).saturating_sub(pairs));
+                            let host = self.open_host(index, t);
+                            if host.scanned == 0 { continue; }
+                            src.open_hosts += 1;
+                            if host.ports.is_empty() { return Some(Job::Finish(host)); }
+                            src.active.push_back(host);
+                        }
+                        None => src.exhausted = true,
+                    }
+                }
+                if let Some(host) = src.active.pop_front() {
+                    let i = host.next.fetch_add(1, Ordering::Relaxed);
+                    if i + 1 < host.ports.len() { src.active.push_back(host.clone()); }
+                    let port = host.ports[i];
+                    return Some(Job::Host(host, port));
+                }
+                if src.exhausted { return None; }
+            }
+            tokio::select! {
+                _ = notified => {}
+                _ = self.opts.cancel.cancelled() => {}
+            }
+        }
+    }
+
+    fn next_permuted(&self, p: &mut Permuted) -> Option<Job> {
+        let hosts = p.index.len();
+        while p.next < p.perm.len() {
+            let seq = p.next;
+            p.next += 1;
+            if self.opts.resume.as_ref().is_some_and(|r| r.done.contains(seq)) { self.opts.progress.done(1); continue; }
+            let j = p.perm.get(seq);
+            let (index, k) = (j % hosts, (j / hosts) as usize);
+            let target = p.index.get(index)?;
+            // Targets with an explicit port only use the first port slot.
+            let port = match target.port {
+                Some(port) if k == 0 => port,
+                Some(_) => { self.opts.progress.done(1); return Some(Job::Skip(seq)) }
+                None => self.ports[k],
+            };
+            return Some(Job::Spread { index, seq, target, port });
+        }
+        None
+    }
+
+    async fn resolve_cached(&self, host: &Host) -> Arc<[IpAddr]> {
+        if let Host::Name(name) = host {
+            if let Some(addrs) = self.names.lock().unwrap().get(name) { return addrs.clone(); }
+            let addrs = resolve(host, &self.opts).await;
+            self.names.lock().unwrap().insert(name.clone(), addrs.clone());
+            return addrs;
+        }
+        resolve(host, &self.opts).await
+    }
+
+    async fn worker(self: Arc<Self>) {
+        self.work().await;
+        // A worker leaving early (receiver gone, cancelled mid-job) may hold a host slot that is
+        // never freed; wake the workers waiting for one so they notice and return as well.
+        self.host_done.notify_waiters();
+    }
+
+    async fn work(&self) {
+        while let Some(job) = self.next().await {
+            let (host, target, port) = match &job {
+                Job::Host(h, port) => (Some(h), &h.target, *port),
+                Job::Finish(h) => {
+                    let addrs = h.addrs(&self.opts).await;
+                    if !self.finish(h, addrs.to_vec()).await { return; }
+                    continue;
+                }
+                Job::Spread { target, port, .. } => (None, target, *port),
+                Job::Skip(seq) => {
+                    if self.events.send(ScanEvent::Position { seq: *seq }).await.is_err() { return; }
+                    continue;
+                }
+            };
+            let addrs = match host {
+                Some(h) => h.addrs(&self.opts).await,
+                None => self.resolve_cached(&target.host).await,
+            };
+            let (index, seq) = match &job {
+                Job::Spread { index, seq, .. } => (*index as usize, Some(*seq)),
+                Job::Host(h, _) | Job::Finish(h) => (h.index, None),
+                Job::Skip(_) => unreachable!("skips are sent above"),
+            };
+            if let (Some(seq), true) = (seq, addrs.is_empty()) {
+                self.opts.progress.error();
+                self.opts.progress.done(1);
+                if self.events.send(ScanEvent::NoAddress { host: index, target: target.clone(), seq }).await.is_err() { return; }
+                continue;
+            }
+            let name = match &target.host { Host::Name(n) => Some(n.as_str()), Host::Addr(_) => None };
+            for &ip in addrs.iter() {
+                if scope::check(name, Some(ip), Some(port)).is_err() { continue; }
+                let permit = match host {
+                    Some(h) => Some(h.window.sem.clone().acquire_owned().await.unwrap()),
+                    None => None,
+                };
+                let global = match &self.opts.global_limit {
+                    Some(g) => Some(g.clone().acquire_owned().await.unwrap()),
+                    None => None,
+                };
+                if let Some(q) = &self.opts.global_qps { q.acquire().await; }
+                // A job interrupted part-way is left undone rather than reported.
+                if self.opts.cancel.is_cancelled() { return; }
+                let t_first = unix_ms();
+                let (rtt, window) = match host {
+                    Some(h) => (&h.rtt, Some(&h.window)),
+                    None => (&self.spread_rtt, None),
+                };
+                let Some((reason, rtt)) = self.opts.cancel.drain(self.probe(rtt, window, ip, port)).await else { return };
+                drop(global);
+                drop(permit);
+
+                match reason {
+                    Reason::SynAck => self.opts.progress.found(),
+                    Reason::HostUnreach => self.opts.progress.error(),
+                    Reason::ConnRefused | Reason::Timeout => {}
+                }
+                let result = PortResult { port, state: reason.state(), reason, rtt };
+                let ev = PortEvent { host: index, hostname: hostname(target), addr: ip.to_string(), result, t_first, t_last: unix_ms(), seq };
+                if self.events.send(ScanEvent::Port(ev)).await.is_err() { return; }
+            }
+            self.opts.progress.done(1);
+            if let Some(seq) = seq {
+                if self.events.send(ScanEvent::Position { seq }).await.is_err() { return; }
+            }
+            let Job::Host(host, _) = job else { continue };
+            if host.remaining.fetch_sub(1, Ordering::AcqRel) == 1 && !self.finish(&host, addrs.to_vec()).await { return; }
+        }
+    }
+
+    /// Send `HostDone` and free the host's slot. Returns false once the receiver is gone.
+    async fn finish(&self, host: &HostScan, addrs: Vec<IpAddr>) -> bool {
+        if addrs.is_empty() { self.opts.progress.error(); }
+        self.opts.progress.host_done();
+        let done = HostDone { host: host.index, target: host.target.clone(), addrs, scanned: host.scanned, elapsed: host.opened.elapsed() };
+        let sent = self.events.send(ScanEvent::HostDone(done)).await;
+        if let Order::Sequential(src) = &mut *self.order.lock().unwrap() { src.open_hosts -= 1; }
+        self.host_done.notify_waiters();
+        sent.is_ok()
+    }
+
+    /// Probe with retries; refusals are final, timeouts and unreachable errors are retried.
+    async fn probe(&self, rtt: &Mutex<RttEstimator>, window: Option<&HostWindow>, ip: IpAddr, port: u16) -> (Reason, Option<Duration>) {
+        let opts = &self.opts;
+        let mut attempts = 0;
+        let mut outcome = (Reason::Timeout, None);
+        while attempts <= opts.retries {
+            let limit = rtt.lock().unwrap().timeout();
+            let started = Instant::now();
+            opts.progress.sent(1);
+            let reason = attempt(&opts.mode, SocketAddr::new(ip, port), limit).await;
+            let elapsed = started.elapsed();
+            let responded = matches!(reason, Reason::SynAck | Reason::ConnRefused);
+            if responded { rtt.lock().unwrap().sample(elapsed); }
+            if let Some(a) = &opts.adaptive {
+                a.record(if responded { Feedback::Responded } else { Feedback::Timeout });
+                if let Some(w) = window { w.resize(a.window()); }
+            }
+            outcome = (reason, responded.then_some(elapsed));
+            if responded || opts.cancel.is_cancelled() { break; }
+            attempts += 1;
+            if attempts <= opts.retries {
+                let base = opts.retry_delay.as_millis() as u64;
+                let exp = base.saturating_mul(1u64 << (attempts.min(6))); // cap growth
+                let jitter = thread_rng().gen_range(0..(exp / 4 + 1));
+                tokio::time::sleep(Duration::from_millis(exp + jitter)).await;
+            }
+        }
+        outcome
+    }
+}
+
+/// One probe: a raw SYN when the mode and address family allow it, otherwise `connect()` (through
+/// the installed [`connect`]or's proxies, if any).
+async fn attempt(mode: &ScanMode, addr: SocketAddr, limit: Duration) -> Reason {
+    if let ScanMode::Syn(syn) = mode {
+        if syn.supports(addr.ip()) {
+            audit::record("port-scan", "tcp-syn", addr);
+            return match syn.probe(addr, limit).await {
+                Ok(Some(SynReply::SynAck)) => Reason::SynAck,
+                Ok(Some(SynReply::Rst)) => Reason::ConnRefused,
+                Ok(Some(SynReply::Unreachable)) => Reason::HostUnreach,
+                Ok(None) => Reason::Timeout,
+                Err(e) => classify_error(&e),
+            };
+        }
+    }
+    audit::record("port-scan", "tcp-connect", addr);
+    match timeout(limit, connect::connect(addr)).await {
+        Ok(Ok(_)) => Reason::SynAck,
+        Ok(Err(e)) => classify_error(&e),
+        Err(_) => Reason::Timeout,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan_many, RttBounds};
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn host_done_follows_its_ports_and_hosts_stay_bounded() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-12"]).unwrap();
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 2);
+        opts.workers = 4;
+        opts.max_hosts = 3;
+        let (tx, mut rx) = mpsc::channel(8);
+        let scan = tokio::spawn(async move { scan_many(targets.expand(), &[open, 1], &opts, tx).await });
+
+        let mut seen: HashMap<usize, usize> = HashMap::new();
+        let (mut open_now, mut max_open, mut done) = (0usize, 0usize, 0usize);
+        while let Some(ev) = rx.recv().await {
+            match ev {
+                ScanEvent::Port(p) => {
+                    let n = seen.entry(p.host).or_default();
+                    if *n == 0 { open_now += 1; max_open = max_open.max(open_now); }
+                    *n += 1;
+                    if p.host == 0 && p.result.port == open { assert!(p.result.is_open()); }
+                }
+                ScanEvent::HostDone(h) => {
+                    assert_eq!(seen.get(&h.host), Some(&2));
+                    open_now -= 1;
+                    done += 1;
+                }
+                ScanEvent::NoAddress { .. } | ScanEvent::Position { .. } => unreachable!(),
+            }
+        }
+        scan.await.unwrap();
+        assert_eq!(done, 12);
+        assert!(max_open <= 3, "{}", max_open);
+    }
+
+    #[tokio::test]
+    async fn returns_once_the_receiver_is_dropped() {
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-3"]).unwrap();
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 1);
+        opts.workers = 4;
+        opts.max_hosts = 1;
+        let (tx, mut rx) = mpsc::channel(1);
+        let scan = tokio::spawn(async move { scan_many(targets.expand(), &[1, 2, 3], &opts, tx).await });
+        rx.recv().await.unwrap();
+        drop(rx);
+        // Workers waiting for a host slot must not outlive the one that saw the send fail.
+        tokio::time::timeout(Duration::from_secs(5), scan).await.expect("scan_many hung").unwrap();
+    }
+
+    #[tokio::test]
+    async fn cancel_stops_drawing_work_and_leaves_hosts_unfinished() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-200"]).unwrap();
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 1);
+        opts.workers = 1;
+        let cancel = opts.cancel.clone();
+        let (tx, mut rx) = mpsc::channel(1);
+        let scan = tokio::spawn(async move { scan_many(targets.expand(), &[open, 1], &opts, tx).await });
+        let mut ports = 0;
+        while let Some(ev) = rx.recv().await {
+            match ev {
+                ScanEvent::Port(_) => ports += 1,
+                ScanEvent::HostDone(h) => assert!(h.host < 3),
+                ScanEvent::NoAddress { .. } | ScanEvent::Position { .. } => unreachable!(),
+            }
+            if ports == 5 { cancel.cancel(); }
+        }
+        scan.await.unwrap();
+        // At most the probe in flight and the one waiting to send finish after cancelling.
+        assert!(ports <= 7, "{}", ports);
+    }
+
+    #[tokio::test]
+    async fn permuted_covers_space_once_and_interleaves_hosts() {
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-4", "127.0.0.9:1"]).unwrap();
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 1);
+        opts.workers = 1;
+        let ports = [1, 2, 3, 4, 5];
+        let run = |seed| {
+            let (tx, mut rx) = mpsc::channel(64);
+            let (targets, opts) = (targets.clone(), opts.clone());
+            async move {
+                crate::scan_permuted(&targets, &ports, seed, &opts, tx).await.unwrap();
+                let mut order = Vec::new();
+                while let Some(ev) = rx.recv().await {
+                    if let ScanEvent::Port(p) = ev { order.push((p.host, p.result.port)); }
+                }
+                order
+            }
+        };
+        let a = run(1).await;
+        assert_eq!(a.len(), 4 * 5 + 1);
+        // The slots the single-port target does not use count as done too.
+        assert_eq!(opts.progress.snapshot().done, 5 * 5);
+        let mut uniq = a.clone();
+        uniq.sort_unstable();
+        uniq.dedup();
+        assert_eq!(uniq.len(), a.len());
+        assert!(a.contains(&(4, 1)));
+        // not host-by-host
+        assert!(a[..5].iter().any(|(h, _)| *h != a[0].0));
+        assert_eq!(a, run(1).await);
+        assert_ne!(a, run(2).await);
+    }
+
+    #[tokio::test]
+    async fn resume_skips_finished_work() {
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-4"]).unwrap();
+        let ports = [1, 2, 3];
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 2);
+        let mut resume = crate::Resume::default();
+        resume.done.insert(0);
+        resume.done.insert(2);
+        resume.partial.insert(1, vec![1, 3]);
+        resume.partial.insert(3, vec![1, 2, 3]);
+        opts.resume = Some(Arc::new(resume));
+        let (tx, mut rx) = mpsc::channel(64);
+        scan_many(targets.expand(), &ports, &opts, tx).await;
+        let (mut probed, mut done) = (Vec::new(), Vec::new());
+        while let Some(ev) = rx.recv().await {
+            match ev {
+                ScanEvent::Port(p) => probed.push((p.host, p.result.port)),
+                ScanEvent::HostDone(h) => done.push((h.host, h.scanned)),
+                ScanEvent::NoAddress { .. } | ScanEvent::Position { .. } => unreachable!(),
+            }
+        }
+        done.sort_unstable();
+        assert_eq!(probed, vec![(1, 2)]);
+        assert_eq!(done, vec![(1, 3), (3, 3)]);
+        // Resumed work still counts towards the 4×3 pairs.
+        let p = opts.progress.snapshot();
+        assert_eq!((p.done, p.sent, p.hosts), (12, 1, 2));
+
+        // Permuted: a second run over the unfinished positions completes the space exactly once.
+        let targets = toolbox_core::TargetSet::parse(["127.0.0.1-3"]).unwrap();
+        let run = |resume: Option<crate::Resume>| {
+            let (tx, mut rx) = mpsc::channel(64);
+            let (targets, mut opts) = (targets.clone(), ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 1));
+            opts.workers = 1;
+            opts.resume = resume.map(Arc::new);
+            async move {
+                crate::scan_permuted(&targets, &ports, 9, &opts, tx).await.unwrap();
+                let mut seen = Vec::new();
+                while let Some(ev) = rx.recv().await {
+                    if let ScanEvent::Port(p) = ev { seen.push((p.seq.unwrap(), p.host, p.result.port)); }
+                }
+                seen
+            }
+        };
+        let all = run(None).await;
+        let mut resume = crate::Resume::default();
+        for &(seq, _, _) in all.iter().step_by(2) { resume.done.insert(seq); }
+        let rest = run(Some(resume)).await;
+        let mut both: Vec<_> = all.iter().step_by(2).chain(rest.iter()).copied().collect();
+        both.sort_unstable();
+        let mut expected = all.clone();
+        expected.sort_unstable();
+        assert_eq!(both, expected);
+    }
+
+    #[tokio::test]
+    async fn permuted_positions_finish_after_every_address() {
+        // `localhost` resolves to both 127.0.0.1 and ::1; the second target only uses its first slot.
+        let targets = toolbox_core::TargetSet::parse(["localhost", "127.0.0.9:1"]).unwrap();
+        let ports = [1, 2, 3];
+        let run = |resume: Option<crate::Resume>| {
+            let (tx, mut rx) = mpsc::channel(64);
+            let (targets, mut opts) = (targets.clone(), ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 1));
+            opts.workers = 2;
+            opts.resume = resume.map(Arc::new);
+            async move {
+                crate::scan_permuted(&targets, &ports, 5, &opts, tx).await.unwrap();
+                let (mut probes, mut finished) = (Vec::new(), Vec::new());
+                while let Some(ev) = rx.recv().await {
+                    match ev {
+                        ScanEvent::Port(p) => {
+                            let seq = p.seq.unwrap();
+                            assert!(!finished.contains(&seq), "probe of {} after it finished", seq);
+                            probes.push((seq, p.host, p.addr));
+                        }
+                        ScanEvent::Position { seq } => {
+                            let hosts: Vec<_> = probes.iter().filter(|(s, ..)| *s == seq).map(|(_, h, _)| *h).collect();
+                            assert!(hosts.is_empty() || hosts.len() == if hosts[0] == 0 { 2 } else { 1 }, "{}: {:?}", seq, hosts);
+                            finished.push(seq);
+                        }
+                        ScanEvent::NoAddress { .. } | ScanEvent::HostDone(_) => unreachable!(),
+                    }
+                }
+                (probes, finished)
+            }
+        };
+        let (probes, finished) = run(None).await;
+        // 3 slots of `localhost` on both addresses, and one probe of the explicit port.
+        assert_eq!(probes.len(), 3 * 2 + 1);
+        let mut all = finished.clone();
+        all.sort_unstable();
+        assert_eq!(all, (0..6).collect::<Vec<_>>());
+
+        // Resuming from every other finished position redoes exactly the rest, skips included.
+        let mut resume = crate::Resume::default();
+        for &seq in finished.iter().step_by(2) { resume.done.insert(seq); }
+        let mut done = resume.done.clone();
+        let (mut reprobed, refinished) = run(Some(resume)).await;
+        for &seq in &refinished { done.insert(seq); }
+        assert_eq!((done.watermark(), done.count()), (6, 6));
+        let mut expected: Vec<_> = probes.iter().filter(|(s, ..)| !finished.iter().step_by(2).any(|f| f == s)).cloned().collect();
+        expected.sort_unstable();
+        reprobed.sort_unstable();
+        assert_eq!(reprobed, expected);
+    }
+
+    #[tokio::test]
+    async fn family_filters_addresses() {
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let targets = toolbox_core::TargetSet::parse(["::1", "127.0.0.1"]).unwrap();
+        let mut opts = ScanOptions::new(RttBounds::fixed(Duration::from_secs(2)), 2);
+        opts.family = toolbox_core::Family::V6;
+        let (tx, mut rx) = mpsc::channel(16);
+        scan_many(targets.expand(), &[open], &opts, tx).await;
+        let mut events = Vec::new();
+        while let Some(ev) = rx.recv().await { events.push(ev); }
+        assert!(matches!(&events[0], ScanEvent::Port(p) if p.addr == "::1" && p.result.is_open()));
+        assert!(events.iter().any(|e| matches!(e, ScanEvent::HostDone(h) if h.host == 1 && h.addrs.is_empty())));
+        assert_eq!(events.iter().filter(|e| matches!(e, ScanEvent::Port(_))).count(), 1);
+
+        let (tx, mut rx) = mpsc::channel(16);
+        crate::scan_permuted(&targets, &[open], 3, &opts, tx).await.unwrap();
+        let mut skipped = Vec::new();
+        while let Some(ev) = rx.recv().await {
+            if let ScanEvent::NoAddress { host, .. } = ev { skipped.push(host); }
+        }
+        assert_eq!(skipped, vec![1]);
+    }
+}
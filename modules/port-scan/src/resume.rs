@@ -0,0 +1,75 @@
+//! Bookkeeping for resuming an interrupted scan without re-probing finished work.
+
+use std::collections::{BTreeSet, HashMap};
+
+/// Set of finished positions in a scan order, kept compact as a low watermark plus the finished
+/// positions above it. Work finishes roughly in order, so the tail stays about as small as the
+/// number of probes in flight.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Progress {
+    below: u64,
+    above: BTreeSet<u64>,
+}
+
+impl Progress {
+    /// Rebuild from a watermark and the finished positions above it.
+    pub fn from_parts(below: u64, above: impl IntoIterator<Item = u64>) -> Self {
+        let mut p = Progress { below, above: BTreeSet::new() };
+        for pos in above { p.insert(pos); }
+        p
+    }
+
+    /// Every position below this is finished.
+    pub fn watermark(&self) -> u64 {
+        self.below
+    }
+
+    /// Finished positions at or above the watermark, ascending.
+    pub fn above(&self) -> impl Iterator<Item = u64> + '_ {
+        self.above.iter().copied()
+    }
+
+    pub fn contains(&self, pos: u64) -> bool {
+        pos < self.below || self.above.contains(&pos)
+    }
+
+    pub fn insert(&mut self, pos: u64) {
+        if pos < self.below { return; }
+        self.above.insert(pos);
+        while self.above.remove(&self.below) {
+            self.below += 1;
+        }
+    }
+
+    /// Number of finished positions.
+    pub fn count(&self) -> u64 {
+        self.below + self.above.len() as u64
+    }
+}
+
+/// Work finished by an earlier run of the same scan (same targets, ports and seed).
+///
+/// Sequential scans ([`crate::scan_many`]) track finished targets by index, plus the ports already
+/// decided on targets that were part-way through. Permuted scans ([`crate::scan_permuted`]) track
+/// positions in the permutation ([`crate::ScanEvent::Position`]) and ignore `partial`.
+#[derive(Debug, Clone, Default)]
+pub struct Resume {
+    pub done: Progress,
+    pub partial: HashMap<usize, Vec<u16>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watermark_advances_over_contiguous_work() {
+        let mut p = Progress::default();
+        for pos in [2, 0, 5, 1] { p.insert(pos); }
+        assert_eq!(p.watermark(), 3);
+        assert_eq!(p.above().collect::<Vec<_>>(), vec![5]);
+        assert!(p.contains(1) && p.contains(5) && !p.contains(3));
+        assert_eq!(p.count(), 4);
+        assert_eq!(Progress::from_parts(3, [3, 5]), Progress::from_parts(4, [5]));
+    }
+}
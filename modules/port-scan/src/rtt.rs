@@ -0,0 +1,81 @@
+//! Per-host round-trip-time estimation (SRTT/RTTVAR as in RFC 6298) and derived timeouts.
+
+use std::time::Duration;
+
+const ALPHA: f64 = 1.0 / 8.0;
+const BETA: f64 = 1.0 / 4.0;
+
+/// Timeout bounds: `initial` is used until the first sample, afterwards the estimate is clamped
+/// to `[min, max]`.
+#[derive(Debug, Clone, Copy)]
+pub struct RttBounds {
+    pub initial: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl RttBounds {
+    /// Fixed timeout (no adaptation).
+    pub fn fixed(timeout: Duration) -> Self {
+        RttBounds { initial: timeout, min: timeout, max: timeout }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RttEstimator {
+    bounds: RttBounds,
+    srtt: Option<f64>,
+    rttvar: f64,
+}
+
+impl RttEstimator {
+    pub fn new(bounds: RttBounds) -> Self {
+        RttEstimator { bounds, srtt: None, rttvar: 0.0 }
+    }
+
+    /// Feed one measured connect RTT (successful or refused connect).
+    pub fn sample(&mut self, rtt: Duration) {
+        let r = rtt.as_secs_f64();
+        match self.srtt {
+            None => {
+                self.srtt = Some(r);
+                self.rttvar = r / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar = (1.0 - BETA) * self.rttvar + BETA * (srtt - r).abs();
+                self.srtt = Some((1.0 - ALPHA) * srtt + ALPHA * r);
+            }
+        }
+    }
+
+    pub fn srtt(&self) -> Option<Duration> {
+        self.srtt.map(Duration::from_secs_f64)
+    }
+
+    /// Current per-attempt timeout: SRTT + 4*RTTVAR within bounds, or the initial timeout.
+    pub fn timeout(&self) -> Duration {
+        match self.srtt {
+            None => self.bounds.initial,
+            Some(srtt) => Duration::from_secs_f64(srtt + 4.0 * self.rttvar).clamp(self.bounds.min, self.bounds.max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converges_and_clamps() {
+        let b = RttBounds { initial: Duration::from_millis(500), min: Duration::from_millis(20), max: Duration::from_secs(2) };
+        let mut e = RttEstimator::new(b);
+        assert_eq!(e.timeout(), Duration::from_millis(500));
+        e.sample(Duration::from_millis(10));
+        // 10 + 4*5 = 30 ms
+        assert_eq!(e.timeout().as_millis(), 30);
+        for _ in 0..50 { e.sample(Duration::from_millis(1)); }
+        assert_eq!(e.timeout(), Duration::from_millis(20));
+        for _ in 0..50 { e.sample(Duration::from_secs(3)); }
+        assert_eq!(e.timeout(), Duration::from_secs(2));
+    }
+}
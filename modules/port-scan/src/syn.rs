@@ -0,0 +1,345 @@
+//! Raw-socket SYN scanning (Linux, IPv4 and IPv6).
+//!
+//! A bare SYN is sent from a reserved source port and the reply is matched in a receive loop:
+//! SYN-ACK means open, RST closed, ICMP unreachable filtered. The handshake is never completed:
+//! no socket is listening on the source port, so the kernel answers the SYN-ACK with a RST.
+//! Opening the raw sockets requires CAP_NET_RAW (root, or a user+network namespace).
+
+/// What came back for a SYN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SynReply {
+    SynAck,
+    Rst,
+    Unreachable,
+}
+
+#[cfg(target_os = "linux")]
+pub use imp::SynScanner;
+
+#[cfg(not(target_os = "linux"))]
+use std::{io, net::{IpAddr, SocketAddr}, time::Duration};
+
+#[cfg(not(target_os = "linux"))]
+pub struct SynScanner(());
+
+#[cfg(not(target_os = "linux"))]
+impl SynScanner {
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "SYN scan needs Linux raw sockets"))
+    }
+
+    pub fn supports(&self, _ip: IpAddr) -> bool {
+        false
+    }
+
+    pub async fn probe(&self, _dst: SocketAddr, _wait: Duration) -> io::Result<Option<SynReply>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "SYN scan needs Linux raw sockets"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::SynReply;
+    use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+    use std::collections::HashMap;
+    use std::io;
+    use std::mem::MaybeUninit;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    const SYN: u8 = 0x02;
+    const RST: u8 = 0x04;
+    const ACK: u8 = 0x10;
+
+    struct Pending {
+        seq: u32,
+        tx: oneshot::Sender<SynReply>,
+    }
+
+    /// Raw TCP socket for one family plus the bound (never listening) socket reserving its port.
+    struct Family {
+        raw: Socket,
+        sport: u16,
+        _reserve: Socket,
+    }
+
+    struct Shared {
+        v4: Option<Family>,
+        v6: Option<Family>,
+        pending: Mutex<HashMap<(IpAddr, u16), Pending>>,
+        stop: AtomicBool,
+    }
+
+    /// Shared SYN prober; receive threads run until it is dropped.
+    pub struct SynScanner {
+        shared: Arc<Shared>,
+    }
+
+    impl SynScanner {
+        /// Open raw sockets for both families. Fails with `PermissionDenied` without CAP_NET_RAW.
+        pub fn new() -> io::Result<Self> {
+            let v4 = Family::open(Domain::IPV4);
+            let v6 = Family::open(Domain::IPV6);
+            let (v4, v6) = match (v4, v6) {
+                (Err(e), Err(_)) => return Err(e),
+                (a, b) => (a.ok(), b.ok()),
+            };
+            let shared = Arc::new(Shared { v4, v6, pending: Mutex::new(HashMap::new()), stop: AtomicBool::new(false) });
+            if shared.v4.is_some() {
+                spawn_loop(&shared, |s| &s.v4.as_ref().unwrap().raw, parse_tcp4);
+                if let Ok(icmp) = raw_socket(Domain::IPV4, Protocol::ICMPV4) { spawn_icmp(&shared, icmp, parse_icmp4); }
+            }
+            if shared.v6.is_some() {
+                spawn_loop(&shared, |s| &s.v6.as_ref().unwrap().raw, parse_tcp6);
+                if let Ok(icmp) = raw_socket(Domain::IPV6, Protocol::ICMPV6) { spawn_icmp(&shared, icmp, parse_icmp6); }
+            }
+            Ok(SynScanner { shared })
+        }
+
+        /// True if a raw socket for `ip`'s family is open.
+        pub fn supports(&self, ip: IpAddr) -> bool {
+            self.shared.family(ip).is_some()
+        }
+
+        /// Send one SYN to `dst` and wait up to `wait` for the reply (`None` on timeout).
+        pub async fn probe(&self, dst: SocketAddr, wait: Duration) -> io::Result<Option<SynReply>> {
+            let fam = self.shared.family(dst.ip()).ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no raw socket for address family"))?;
+            let src = toolbox_core::socket::global().source_for(dst.ip())?;
+            let seq: u32 = rand::random();
+            let packet = build_syn(src, dst.ip(), fam.sport, dst.port(), seq);
+            let key = (dst.ip(), dst.port());
+            let (tx, rx) = oneshot::channel();
+            self.shared.pending.lock().unwrap().insert(key, Pending { seq, tx });
+            // Raw IPv6 sockets reject a non-zero port in the destination.
+            if let Err(e) = fam.raw.send_to(&packet, &SockAddr::from(SocketAddr::new(dst.ip(), 0))) {
+                self.shared.pending.lock().unwrap().remove(&key);
+                return Err(e);
+            }
+            let reply = tokio::time::timeout(wait, rx).await;
+            let mut pending = self.shared.pending.lock().unwrap();
+            if pending.get(&key).map(|p| p.seq) == Some(seq) { pending.remove(&key); }
+            Ok(reply.ok().and_then(|r| r.ok()))
+        }
+    }
+
+    impl Drop for SynScanner {
+        fn drop(&mut self) {
+            self.shared.stop.store(true, Ordering::Relaxed);
+        }
+    }
+
+    impl Family {
+        fn open(domain: Domain) -> io::Result<Family> {
+            let binding = toolbox_core::socket::global();
+            let v6 = domain == Domain::IPV6;
+            let raw = raw_socket(domain, Protocol::TCP)?;
+            binding.bind_raw(&raw, v6)?;
+            // The source port comes from the configured range, if any.
+            let reserve = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+            binding.bind(&reserve, v6)?;
+            let sport = reserve.local_addr()?.as_socket().map(|a| a.port()).unwrap_or(0);
+            Ok(Family { raw, sport, _reserve: reserve })
+        }
+    }
+
+    impl Shared {
+        fn family(&self, ip: IpAddr) -> Option<&Family> {
+            match ip { IpAddr::V4(_) => self.v4.as_ref(), IpAddr::V6(_) => self.v6.as_ref() }
+        }
+
+        fn sport(&self, ip: IpAddr) -> Option<u16> {
+            self.family(ip).map(|f| f.sport)
+        }
+
+        /// Deliver a reply from `src:sport` to our port `dport`, checking the ACK against our seq.
+        fn deliver(&self, src: IpAddr, sport: u16, dport: u16, ack: Option<u32>, reply: SynReply) {
+            if self.sport(src) != Some(dport) { return; }
+            let mut pending = self.pending.lock().unwrap();
+            let Some(p) = pending.get(&(src, sport)) else { return };
+            if let Some(ack) = ack { if ack != p.seq.wrapping_add(1) { return; } }
+            if let Some(p) = pending.remove(&(src, sport)) { let _ = p.tx.send(reply); }
+        }
+    }
+
+    fn raw_socket(domain: Domain, proto: Protocol) -> io::Result<Socket> {
+        let s = Socket::new(domain, Type::RAW, Some(proto))?;
+        s.set_read_timeout(Some(Duration::from_millis(100)))?;
+        Ok(s)
+    }
+
+    type Parser = fn(&Shared, &[u8], IpAddr);
+
+    fn spawn_loop(shared: &Arc<Shared>, sock: fn(&Shared) -> &Socket, parse: Parser) {
+        let shared = shared.clone();
+        std::thread::spawn(move || recv_loop(&shared, sock(&shared), parse));
+    }
+
+    fn spawn_icmp(shared: &Arc<Shared>, icmp: Socket, parse: Parser) {
+        let shared = shared.clone();
+        std::thread::spawn(move || recv_loop(&shared, &icmp, parse));
+    }
+
+    fn recv_loop(shared: &Shared, sock: &Socket, parse: Parser) {
+        let mut buf = [MaybeUninit::<u8>::uninit(); 65536];
+        while !shared.stop.load(Ordering::Relaxed) {
+            match sock.recv_from(&mut buf) {
+                Ok((n, from)) => {
+                    // SAFETY: recv_from initialized the first n bytes.
+                    let data = unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, n) };
+                    if let Some(from) = from.as_socket() { parse(shared, data, from.ip()); }
+                }
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn be16(b: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes([b[at], b[at + 1]])
+    }
+
+    fn be32(b: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+    }
+
+    fn on_tcp(shared: &Shared, src: IpAddr, tcp: &[u8]) {
+        if tcp.len() < 14 { return; }
+        let flags = tcp[13];
+        let ack = (flags & ACK != 0).then(|| be32(tcp, 8));
+        let reply = if flags & (SYN | ACK) == SYN | ACK {
+            SynReply::SynAck
+        } else if flags & RST != 0 {
+            SynReply::Rst
+        } else {
+            return;
+        };
+        shared.deliver(src, be16(tcp, 0), be16(tcp, 2), ack, reply);
+    }
+
+    /// Raw IPv4 sockets deliver the IP header.
+    fn parse_tcp4(shared: &Shared, pkt: &[u8], _from: IpAddr) {
+        if pkt.len() < 20 || pkt[0] >> 4 != 4 || pkt[9] != 6 { return; }
+        let ihl = (pkt[0] & 0x0f) as usize * 4;
+        if pkt.len() < ihl { return; }
+        let src = IpAddr::V4(Ipv4Addr::new(pkt[12], pkt[13], pkt[14], pkt[15]));
+        on_tcp(shared, src, &pkt[ihl..]);
+    }
+
+    /// Raw IPv6 sockets deliver only the TCP segment.
+    fn parse_tcp6(shared: &Shared, seg: &[u8], from: IpAddr) {
+        on_tcp(shared, from, seg);
+    }
+
+    /// ICMP destination unreachable quoting one of our SYNs.
+    fn parse_icmp4(shared: &Shared, pkt: &[u8], _from: IpAddr) {
+        if pkt.len() < 20 { return; }
+        let icmp = &pkt[(pkt[0] & 0x0f) as usize * 4..];
+        if icmp.len() < 8 + 20 || icmp[0] != 3 { return; }
+        let inner = &icmp[8..];
+        let ihl = (inner[0] & 0x0f) as usize * 4;
+        if inner[9] != 6 || inner.len() < ihl + 4 { return; }
+        let dst = IpAddr::V4(Ipv4Addr::new(inner[16], inner[17], inner[18], inner[19]));
+        let tcp = &inner[ihl..];
+        shared.deliver(dst, be16(tcp, 2), be16(tcp, 0), None, SynReply::Unreachable);
+    }
+
+    fn parse_icmp6(shared: &Shared, icmp: &[u8], _from: IpAddr) {
+        if icmp.len() < 8 + 40 + 4 || icmp[0] != 1 { return; }
+        let inner = &icmp[8..];
+        if inner[6] != 6 { return; }
+        let mut dst = [0u8; 16];
+        dst.copy_from_slice(&inner[24..40]);
+        let tcp = &inner[40..];
+        shared.deliver(IpAddr::V6(Ipv6Addr::from(dst)), be16(tcp, 2), be16(tcp, 0), None, SynReply::Unreachable);
+    }
+
+    /// TCP SYN with an MSS option and the checksum filled in.
+    pub(super) fn build_syn(src: IpAddr, dst: IpAddr, sport: u16, dport: u16, seq: u32) -> Vec<u8> {
+        let mut tcp = Vec::with_capacity(24);
+        tcp.extend_from_slice(&sport.to_be_bytes());
+        tcp.extend_from_slice(&dport.to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&0u32.to_be_bytes());
+        tcp.push(6 << 4); // data offset: 6 words
+        tcp.push(SYN);
+        tcp.extend_from_slice(&1024u16.to_be_bytes()); // window
+        tcp.extend_from_slice(&[0, 0, 0, 0]); // checksum, urgent pointer
+        tcp.extend_from_slice(&[2, 4, 0x05, 0xb4]); // MSS 1460
+        let sum = checksum(&pseudo_header(src, dst, tcp.len()), &tcp);
+        tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+        tcp
+    }
+
+    pub(super) fn pseudo_header(src: IpAddr, dst: IpAddr, len: usize) -> Vec<u8> {
+        let mut p = Vec::with_capacity(40);
+        match (src, dst) {
+            (IpAddr::V4(s), IpAddr::V4(d)) => {
+                p.extend_from_slice(&s.octets());
+                p.extend_from_slice(&d.octets());
+                p.extend_from_slice(&[0, 6]);
+                p.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            (s, d) => {
+                let v6 = |ip: IpAddr| match ip { IpAddr::V6(a) => a, IpAddr::V4(a) => a.to_ipv6_mapped() };
+                p.extend_from_slice(&v6(s).octets());
+                p.extend_from_slice(&v6(d).octets());
+                p.extend_from_slice(&(len as u32).to_be_bytes());
+                p.extend_from_slice(&[0, 0, 0, 6]);
+            }
+        }
+        p
+    }
+
+    /// Internet checksum over the concatenation of `a` and `b` (`a` has even length).
+    pub(super) fn checksum(a: &[u8], b: &[u8]) -> u16 {
+        let mut sum: u32 = 0;
+        for chunk in a.chunks(2).chain(b.chunks(2)) {
+            let word = if chunk.len() == 2 { u16::from_be_bytes([chunk[0], chunk[1]]) } else { u16::from_be_bytes([chunk[0], 0]) };
+            sum += word as u32;
+        }
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+        !(sum as u16)
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use std::time::Duration;
+
+    #[test]
+    fn syn_checksum_verifies() {
+        let src: IpAddr = "192.0.2.1".parse().unwrap();
+        let dst: IpAddr = "192.0.2.2".parse().unwrap();
+        let seg = imp::build_syn(src, dst, 40000, 443, 0x01020304);
+        assert_eq!(seg.len(), 24);
+        assert_eq!(seg[13], 0x02);
+        // Summing again with the checksum in place gives zero.
+        assert_eq!(imp::checksum(&imp::pseudo_header(src, dst, seg.len()), &seg), 0);
+        let dst6: IpAddr = "2001:db8::2".parse().unwrap();
+        let seg6 = imp::build_syn("2001:db8::1".parse().unwrap(), dst6, 40000, 443, 7);
+        assert_eq!(imp::checksum(&imp::pseudo_header("2001:db8::1".parse().unwrap(), dst6, seg6.len()), &seg6), 0);
+    }
+
+    /// Run it unprivileged in a throwaway namespace:
+    /// `unshare -rn sh -c 'ip link set lo up && cargo test -p port-scan syn -- --ignored'`.
+    #[tokio::test]
+    #[ignore = "needs CAP_NET_RAW"]
+    async fn syn_probe_loopback() {
+        let scanner = SynScanner::new().unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let wait = Duration::from_secs(2);
+        assert_eq!(scanner.probe(open, wait).await.unwrap(), Some(SynReply::SynAck));
+        assert_eq!(scanner.probe(closed, wait).await.unwrap(), Some(SynReply::Rst));
+        // The handshake was never completed.
+        assert!(tokio::time::timeout(Duration::from_millis(200), listener.accept()).await.is_err());
+    }
+}
@@ -1,16 +1,38 @@
 //! Minimal UDP probes: DNS and NTP.
 
 use anyhow::Result;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::net::UdpSocket;
 use tokio::time::timeout;
+use toolbox_core::policy::{self, Capability, Level};
+use toolbox_core::{audit, scope, Family, Host, Target};
 
 pub enum UdpService { Dns, Ntp, Snmp }
 
-pub async fn probe_dns(host: &str, timeout_ms: u64) -> Result<Option<String>> {
-    let addr = resolve_first(&(host.to_string() + ":53"))?;
-    let sock = UdpSocket::bind("0.0.0.0:0").await?;
+/// SNMP GET with a community string, i.e. guessing a credential.
+pub const SNMP: Capability = Capability { name: "udp-probe.snmp", level: Level::Intrusive, summary: "SNMP sysDescr GET with a community string" };
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "udp-probe.dns", level: Level::SafeActive, summary: "DNS query" },
+    Capability { name: "udp-probe.ntp", level: Level::SafeActive, summary: "NTP client request" },
+    SNMP,
+];
+
+/// Outcome of a probe at each in-scope address of a target: the service's answer, if any.
+pub type Replies = Vec<(SocketAddr, Result<Option<String>>)>;
+
+/// DNS query to every in-scope address of `target` in `family`.
+pub async fn probe_dns(target: &Target, family: Family, timeout_ms: u64) -> Result<Replies> {
+    let mut out = Vec::new();
+    for addr in resolve_all(target, 53, family).await? { out.push((addr, dns_at(addr, timeout_ms).await)); }
+    Ok(out)
+}
+
+async fn dns_at(addr: SocketAddr, timeout_ms: u64) -> Result<Option<String>> {
+    audit::record("udp-probe", "dns-query", addr);
+    let sock = bind_for(&addr).await?;
     let q = build_dns_query();
     timeout(Duration::from_millis(timeout_ms), sock.send_to(&q, addr)).await??;
     let mut buf = [0u8; 512];
@@ -19,9 +41,16 @@ pub async fn probe_dns(host: &str, timeout_ms: u64) -> Result<Option<String>> {
     if n >= 3 && (buf[2] & 0x80) != 0 { Ok(Some(format!("dns: {} bytes", n))) } else { Ok(None) }
 }
 
-pub async fn probe_ntp(host: &str, timeout_ms: u64) -> Result<Option<String>> {
-    let addr = resolve_first(&(host.to_string() + ":123"))?;
-    let sock = UdpSocket::bind("0.0.0.0:0").await?;
+/// NTP client request to every in-scope address of `target` in `family`.
+pub async fn probe_ntp(target: &Target, family: Family, timeout_ms: u64) -> Result<Replies> {
+    let mut out = Vec::new();
+    for addr in resolve_all(target, 123, family).await? { out.push((addr, ntp_at(addr, timeout_ms).await)); }
+    Ok(out)
+}
+
+async fn ntp_at(addr: SocketAddr, timeout_ms: u64) -> Result<Option<String>> {
+    audit::record("udp-probe", "ntp-query", addr);
+    let sock = bind_for(&addr).await?;
     let mut pkt = [0u8; 48];
     pkt[0] = 0b00_100_011; // LI=0, VN=4, Mode=3 (client)
     timeout(Duration::from_millis(timeout_ms), sock.send_to(&pkt, addr)).await??;
@@ -30,9 +59,26 @@ pub async fn probe_ntp(host: &str, timeout_ms: u64) -> Result<Option<String>> {
     if n >= 48 { Ok(Some("ntp: reply".into())) } else { Ok(None) }
 }
 
-fn resolve_first(addr: &str) -> Result<SocketAddr> {
-    let mut it = addr.to_socket_addrs()?;
-    it.next().ok_or_else(|| anyhow::anyhow!("failed to resolve: {}", addr))
+/// Resolve a target to every in-scope address in `family`, using the target's explicit port or the
+/// service default. Fails when nothing resolves or no address is in scope.
+async fn resolve_all(target: &Target, default_port: u16, family: Family) -> Result<Vec<SocketAddr>> {
+    let port = target.port.unwrap_or(default_port);
+    let name = match &target.host { Host::Name(n) => Some(n.as_str()), Host::Addr(_) => None };
+    let ips = toolbox_core::dns::global().resolve(&target.host, family).await;
+    let ips = scope::allowed(name, ips, Some(port))?;
+    if ips.is_empty() {
+        return Err(match family {
+            Family::Any => anyhow::anyhow!("failed to resolve: {}", target.host),
+            f => anyhow::anyhow!("no {} address for {}", f, target.host),
+        });
+    }
+    Ok(ips.into_iter().map(|ip| SocketAddr::new(ip, port)).collect())
+}
+
+/// Socket of the same family as `dest` (IPv6 targets need an IPv6 socket), bound to the
+/// installed source address, interface and port range (see [`toolbox_core::socket`]).
+async fn bind_for(dest: &SocketAddr) -> Result<UdpSocket> {
+    Ok(toolbox_core::socket::global().udp(dest)?)
 }
 
 fn build_dns_query() -> Vec<u8> {
@@ -54,10 +100,18 @@ fn build_dns_query() -> Vec<u8> {
     q
 }
 
-/// Probe SNMP v2c sysDescr.0 with community "public" and return the string if present.
-pub async fn probe_snmp_sysdescr(host: &str, community: &str, timeout_ms: u64) -> Result<Option<String>> {
-    let addr = resolve_first(&(host.to_string() + ":161"))?;
-    let sock = UdpSocket::bind("0.0.0.0:0").await?;
+/// Probe SNMP v2c sysDescr.0 with `community` at every in-scope address of `target` in `family`
+/// and return the string where present.
+pub async fn probe_snmp_sysdescr(target: &Target, family: Family, community: &str, timeout_ms: u64) -> Result<Replies> {
+    policy::require(&SNMP)?;
+    let mut out = Vec::new();
+    for addr in resolve_all(target, 161, family).await? { out.push((addr, snmp_at(addr, community, timeout_ms).await)); }
+    Ok(out)
+}
+
+async fn snmp_at(addr: SocketAddr, community: &str, timeout_ms: u64) -> Result<Option<String>> {
+    audit::record("udp-probe", "snmp-get", addr);
+    let sock = bind_for(&addr).await?;
     let pkt = build_snmp_get(community, &[1,3,6,1,2,1,1,1,0]);
     timeout(Duration::from_millis(timeout_ms), sock.send_to(&pkt, addr)).await??;
     let mut buf = [0u8; 1500];
@@ -161,3 +215,43 @@ fn parse_snmp_sysdescr(data: &[u8]) -> Option<String> {
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn probes_ipv6_targets() {
+        toolbox_core::dns::testing::install();
+        let server = UdpSocket::bind("[::1]:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 48];
+            let (_, peer) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&[0u8; 48], peer).await.unwrap();
+        });
+        let target: Target = format!("[::1]:{}", port).parse().unwrap();
+        let replies = probe_ntp(&target, Family::Any, 2000).await.unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].1.as_ref().unwrap().as_deref(), Some("ntp: reply"));
+    }
+
+    #[tokio::test]
+    async fn probes_every_address_in_the_family() {
+        // local.lab has both 127.0.0.1 and ::1
+        toolbox_core::dns::testing::install();
+        let server = UdpSocket::bind("[::1]:0").await.unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 48];
+            let (_, peer) = server.recv_from(&mut buf).await.unwrap();
+            server.send_to(&[0u8; 48], peer).await.unwrap();
+        });
+        let target: Target = format!("local.lab:{}", port).parse().unwrap();
+        let replies = probe_ntp(&target, Family::V6, 2000).await.unwrap();
+        assert_eq!(replies.iter().map(|(a, _)| *a).collect::<Vec<_>>(), [SocketAddr::new("::1".parse().unwrap(), port)]);
+        assert_eq!(replies[0].1.as_ref().unwrap().as_deref(), Some("ntp: reply"));
+        let all = resolve_all(&target, 123, Family::Any).await.unwrap();
+        assert!(all.iter().any(|a| a.is_ipv4()) && all.iter().any(|a| a.is_ipv6()), "{:?}", all);
+    }
+}
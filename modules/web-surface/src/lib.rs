@@ -1,11 +1,21 @@
 use anyhow::Result;
 use reqwest::{Client, redirect::Policy, header::HeaderMap};
+use std::net::SocketAddr;
 use std::time::Duration;
 use tokio::sync::Semaphore;
 use url::Url;
 use std::io::Cursor;
 use base64::Engine;
 use time::OffsetDateTime;
+use toolbox_core::cancel::Cancel;
+use toolbox_core::policy::{Capability, Level};
+use toolbox_core::progress::Progress;
+use toolbox_core::{audit, connect, scope, Target};
+
+/// Capabilities of this module and their intrusiveness (see [`toolbox_core::policy`]).
+pub const CAPABILITIES: &[Capability] = &[
+    Capability { name: "web-surface.probe", level: Level::SafeActive, summary: "HEAD and small GET per port, redirects and favicon" },
+];
 
 #[derive(Debug, Clone)]
 pub struct WebProbeOptions {
@@ -13,6 +23,12 @@ pub struct WebProbeOptions {
     pub redirects: usize,
     pub user_agent: String,
     pub fetch_favicon: bool,
+    /// Once cancelled no new targets are probed; probes still running at the drain deadline are
+    /// dropped from the results.
+    pub cancel: Cancel,
+    /// Counts requests started, targets whose ports are all done, answering endpoints and failed
+    /// ones; work is counted in target×port pairs.
+    pub progress: Progress,
 }
 
 #[derive(Debug, Clone)]
@@ -32,45 +48,122 @@ pub struct WebResult {
     pub error: Option<String>,
 }
 
-pub async fn probe_many(targets: Vec<String>, ports: Vec<u16>, opts: WebProbeOptions, concurrency: usize) -> Vec<WebResult> {
+/// Routes reqwest's name lookups through the shared resolver ([`toolbox_core::dns`]), keeping only
+/// addresses in [`scope`].
+struct SharedDns;
+
+impl reqwest::dns::Resolve for SharedDns {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let name = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = toolbox_core::dns::global().lookup(&name).await?;
+            let mut refused = None;
+            let allowed: Vec<SocketAddr> = addrs.iter().filter(|ip| match scope::check(Some(&name), Some(**ip), None) {
+                Ok(()) => true,
+                Err(e) => { refused.get_or_insert(e); false }
+            }).map(|ip| SocketAddr::new(*ip, 0)).collect();
+            if let (true, Some(e)) = (allowed.is_empty(), refused) { return Err(e.into()); }
+            Ok(Box::new(allowed.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Check a URL's host and port against the installed scope and record the request in the audit
+/// log. Names are checked again by address when [`SharedDns`] (or, through proxies, the
+/// connector's bridge) resolves them.
+fn admit_url(url: &Url) -> Result<(), scope::OutOfScope> {
+    let port = url.port_or_known_default();
+    match url.host() {
+        Some(url::Host::Domain(d)) => scope::check(Some(d), None, port),
+        Some(url::Host::Ipv4(ip)) => scope::check(None, Some(ip.into()), port),
+        Some(url::Host::Ipv6(ip)) => scope::check(None, Some(ip.into()), port),
+        None => Ok(()),
+    }?;
+    audit::record("web-surface", "http-request", url);
+    Ok(())
+}
+
+/// Follow up to `max` redirects, refusing hops that leave the scope.
+fn redirect_policy(max: usize) -> Policy {
+    Policy::custom(move |attempt| {
+        if attempt.previous().len() > max { return attempt.error("too many redirects"); }
+        match admit_url(attempt.url()) {
+            Ok(()) => attempt.follow(),
+            Err(e) => attempt.error(e),
+        }
+    })
+}
+
+/// Probe every target on every port. Targets with an explicit port are probed on that port only;
+/// URL targets are fetched as given. After `opts.cancel` fires, the results finished so far (and
+/// by the drain deadline) are returned. Fails only if the HTTP client (or the proxy bridge it
+/// needs) cannot be set up.
+pub async fn probe_many(targets: impl IntoIterator<Item = Target>, ports: Vec<u16>, opts: WebProbeOptions, concurrency: usize) -> Result<Vec<WebResult>> {
     let sem = std::sync::Arc::new(Semaphore::new(concurrency.max(1)));
-    let client = Client::builder()
-        .redirect(Policy::limited(opts.redirects))
+    let mut builder = Client::builder()
+        .redirect(redirect_policy(opts.redirects))
         .timeout(Duration::from_millis(opts.timeout_ms))
         .user_agent(opts.user_agent.clone())
         .brotli(true)
         .gzip(true)
         .deflate(true)
-        .build()
-        .expect("client");
+        .dns_resolver(std::sync::Arc::new(SharedDns));
+    // Through proxies or from a bound source, requests go via a local bridge that resolves and
+    // scope-checks targets itself and connects through the core connector; connections are not
+    // reused so each carries requests for one origin only.
+    if !connect::global().is_direct() || !toolbox_core::socket::global().is_default() {
+        let bridge = connect::global().bridge().await.map_err(|e| anyhow::anyhow!("starting proxy bridge: {}", e))?;
+        builder = builder.proxy(reqwest::Proxy::all(bridge.url())?).pool_max_idle_per_host(0);
+    }
+    let client = builder.build()?;
 
     let mut handles = Vec::new();
-    for t in targets {
-        for &p in &ports {
-            let permit = sem.clone().acquire_owned().await.unwrap();
+    'targets: for t in targets {
+        let target_ports = t.ports_or(&ports);
+        // The total counts every port for each target; one with its own port has one pair
+        opts.progress.done((ports.len() as u64).saturating_sub(target_ports.len() as u64));
+        let left = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(target_ports.len()));
+        for p in target_ports {
+            let permit = tokio::select! {
+                permit = sem.clone().acquire_owned() => permit.unwrap(),
+                _ = opts.cancel.cancelled() => break 'targets,
+            };
             let client = client.clone();
-            let host = t.clone();
+            let urls = candidate_urls(&t, p);
+            let label = t.host.to_string();
             let fetch_favicon = opts.fetch_favicon;
+            let (cancel, progress, left) = (opts.cancel.clone(), opts.progress.clone(), left.clone());
+            progress.sent(1);
             handles.push(tokio::spawn(async move {
-                let r = probe_one(&client, host.clone(), p, fetch_favicon).await;
+                let r = cancel.drain(probe_one(&client, label, urls, fetch_favicon)).await;
                 drop(permit);
+                if let Some(r) = &r {
+                    if r.error.is_some() { progress.error(); } else if r.status.is_some() { progress.found(); }
+                    progress.done(1);
+                    if left.fetch_sub(1, std::sync::atomic::Ordering::Relaxed) == 1 { progress.host_done(); }
+                }
                 r
             }));
         }
     }
     let mut out = Vec::new();
-    for h in handles { if let Ok(r) = h.await { out.push(r); } }
-    out
+    for h in handles { if let Ok(Some(r)) = h.await { out.push(r); } }
+    Ok(out)
 }
 
-async fn probe_one(client: &Client, host: String, port: u16, fetch_favicon: bool) -> WebResult {
+/// URLs to try for a target/port, in order. URL targets are used verbatim.
+fn candidate_urls(t: &Target, port: u16) -> Vec<String> {
+    if let Some(u) = &t.url { return vec![u.clone()]; }
     let mut schemes = Vec::new();
     if port == 443 || port == 8443 || port == 9443 { schemes.push("https"); }
     if port == 80 || port == 8080 || port == 8000 { schemes.push("http"); }
     if schemes.is_empty() { schemes = vec!["https", "http"]; }
+    schemes.into_iter().map(|scheme| format!("{}://{}:{}", scheme, t.authority_host(), port)).collect()
+}
 
-    for scheme in schemes {
-        let url = format!("{}://{}:{}", scheme, host, port);
+async fn probe_one(client: &Client, host: String, urls: Vec<String>, fetch_favicon: bool) -> WebResult {
+    let last = urls.len().saturating_sub(1);
+    for (i, url) in urls.iter().cloned().enumerate() {
         let started = std::time::Instant::now();
         let started_at = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
         match fetch_head(client, &url).await {
@@ -87,21 +180,23 @@ async fn probe_one(client: &Client, host: String, port: u16, fetch_favicon: bool
                 return WebResult { target: host, url, final_url, status: Some(status), server, title, fingerprints: fps, started_at, ended_at, duration_ms, favicon_url: fav_url, favicon_mmh3: fav_hash, error: None };
             }
             Err(e) => {
-                // Try next scheme
-                if scheme == "http" {
+                // Try next candidate; report the error (with its causes, e.g. a refused redirect) from the last one
+                if i == last {
                     let duration_ms = started.elapsed().as_millis();
                     let ended_at = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
-                    return WebResult { target: host, url: url.clone(), final_url: url.clone(), status: None, server: None, title: None, fingerprints: Vec::new(), started_at, ended_at, duration_ms, favicon_url: None, favicon_mmh3: None, error: Some(e.to_string()) };
+                    return WebResult { target: host, url: url.clone(), final_url: url.clone(), status: None, server: None, title: None, fingerprints: Vec::new(), started_at, ended_at, duration_ms, favicon_url: None, favicon_mmh3: None, error: Some(format!("{:#}", e)) };
                 }
             }
         }
     }
     let started_at = OffsetDateTime::now_utc().format(&time::format_description::well_known::Rfc3339).unwrap_or_default();
     let ended_at = started_at.clone();
-    WebResult { target: host.clone(), url: format!("https://{}:{}", host, port), final_url: format!("https://{}:{}", host, port), status: None, server: None, title: None, fingerprints: Vec::new(), started_at, ended_at, duration_ms: 0, favicon_url: None, favicon_mmh3: None, error: Some("unreachable".into()) }
+    let url = urls.first().cloned().unwrap_or_default();
+    WebResult { target: host, url: url.clone(), final_url: url, status: None, server: None, title: None, fingerprints: Vec::new(), started_at, ended_at, duration_ms: 0, favicon_url: None, favicon_mmh3: None, error: Some("unreachable".into()) }
 }
 
 async fn fetch_head(client: &Client, url: &str) -> Result<(String, u16, Option<String>)> {
+    admit_url(&Url::parse(url)?)?;
     let resp = client.head(url).send().await?;
     let status = resp.status().as_u16();
     let server = resp.headers().get(reqwest::header::SERVER).and_then(|v| v.to_str().ok()).map(|s| s.to_string());
@@ -110,6 +205,7 @@ async fn fetch_head(client: &Client, url: &str) -> Result<(String, u16, Option<S
 }
 
 async fn fetch_page_info(client: &Client, url: &str) -> Result<(Option<String>, Vec<String>)> {
+    admit_url(&Url::parse(url)?)?;
     let resp = client.get(url).send().await?;
     let headers = resp.headers().clone();
     let ct_is_html = headers
@@ -242,6 +338,7 @@ async fn fetch_favicon_hash(client: &Client, final_url: &str) -> anyhow::Result<
     origin.set_query(None);
     origin.set_fragment(None);
     let fav = origin.join("/favicon.ico")?;
+    admit_url(&fav)?;
     let resp = client.get(fav.as_str()).send().await?;
     if !resp.status().is_success() { return Ok((None, None)); }
     let bytes = resp.bytes().await?;
@@ -251,3 +348,35 @@ async fn fetch_favicon_hash(client: &Client, final_url: &str) -> anyhow::Result<
     let hash = murmur3::murmur3_32(&mut cursor, 0)? as i32; // signed like Shodan
     Ok((Some(fav.to_string()), Some(hash)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn refuses_redirects_out_of_scope() {
+        // The only test here, as the scope is process-wide
+        scope::install(scope::Scope::new(&["127.0.0.1"], &[], None).unwrap(), None).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            while let Ok((mut s, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = s.read(&mut buf).await;
+                let resp = format!("HTTP/1.1 302 Found\r\nLocation: http://127.0.0.2:{}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", port);
+                let _ = s.write_all(resp.as_bytes()).await;
+            }
+        });
+        let opts = WebProbeOptions { timeout_ms: 2000, redirects: 5, user_agent: "test".into(), fetch_favicon: false, cancel: Cancel::default(), progress: Progress::default() };
+        let target: Target = format!("127.0.0.1:{}", port).parse().unwrap();
+        let before = scope::blocked();
+        let results = probe_many([target], vec![port], opts, 1).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, None);
+        assert_eq!(scope::blocked(), before + 1);
+        let err = results[0].error.as_deref().unwrap();
+        assert!(err.contains(&format!("out of scope: 127.0.0.2:{} (not in scope)", port)), "{}", err);
+    }
+}
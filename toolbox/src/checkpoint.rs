@@ -0,0 +1,189 @@
+//! Scan checkpoints: the state `scan --resume` needs to carry on where an interrupted run stopped.
+
+use crate::{OutputFormat, ScanModeArg};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+
+/// Effective options of the scan being checkpointed (after config and defaults were applied).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanArgs {
+    pub target: Option<String>,
+    pub targets: Option<PathBuf>,
+    pub ports: Vec<u16>,
+    pub timeout_ms: u64,
+    pub min_rtt_timeout_ms: u64,
+    pub max_rtt_timeout_ms: u64,
+    pub concurrency: usize,
+    pub qps: u32,
+    pub burst: Option<u32>,
+    pub adaptive: bool,
+    pub min_qps: Option<u32>,
+    pub max_qps: Option<u32>,
+    pub retries: u32,
+    pub retry_delay_ms: u64,
+    pub host_concurrency: usize,
+    pub max_connections: Option<usize>,
+    pub mode: ScanModeArg,
+    pub seed: Option<u64>,
+    pub format: OutputFormat,
+    pub out: Option<PathBuf>,
+    pub dns_retries: u32,
+    pub dns_retry_delay_ms: u64,
+    #[serde(default)]
+    pub ipv4: bool,
+    #[serde(default)]
+    pub ipv6: bool,
+    #[serde(default)]
+    pub ptr: bool,
+}
+
+/// A port decided before the checkpoint was taken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialPort {
+    pub port: u16,
+    pub reason: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtt_us: Option<u64>,
+}
+
+/// Results gathered so far for a target address whose summary line has not been written yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialHost {
+    pub host: usize,
+    pub label: String,
+    #[serde(default)]
+    pub addr: String,
+    pub t_first: i64,
+    pub t_last: i64,
+    pub ports: Vec<PartialPort>,
+    /// Closed and filtered ports counted but not listed in `ports` (randomized scans).
+    #[serde(default)]
+    pub closed: usize,
+    #[serde(default)]
+    pub filtered: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    pub version: u32,
+    pub run_id: String,
+    pub updated_at: String,
+    /// Set once the scan ran to the end; such a checkpoint has nothing left to resume.
+    pub complete: bool,
+    pub args: ScanArgs,
+    /// Size of the target set at the start; resuming against a changed target list is refused.
+    pub targets_total: Option<u64>,
+    /// [`toolbox_core::TargetSet::fingerprint`] of the targets, so that a list of the same size
+    /// with other or reordered entries is refused too. Missing in checkpoints of older versions.
+    #[serde(default)]
+    pub targets_hash: Option<String>,
+    /// Finished targets (sequential order) or permutation positions (randomized order), as a
+    /// low watermark plus the finished positions above it.
+    pub done_below: u64,
+    pub done_above: Vec<u64>,
+    pub partial: Vec<PartialHost>,
+}
+
+impl ScanCheckpoint {
+    pub fn new(run_id: String, args: ScanArgs, targets_total: Option<u64>, targets_hash: String) -> Self {
+        ScanCheckpoint { version: VERSION, run_id, updated_at: String::new(), complete: false, args, targets_total, targets_hash: Some(targets_hash), done_below: 0, done_above: Vec::new(), partial: Vec::new() }
+    }
+
+    /// Refuse to resume against targets other than the ones the checkpoint was taken for.
+    pub fn check_targets(&self, targets_total: Option<u64>, targets_hash: &str) -> Result<()> {
+        let other_hash = matches!(self.targets_hash.as_deref(), Some(h) if h != targets_hash);
+        if self.targets_total != targets_total || other_hash { return Err(anyhow!("target list changed since the checkpoint was written")); }
+        Ok(())
+    }
+}
+
+pub fn load(path: &Path) -> Result<ScanCheckpoint> {
+    let s = fs::read_to_string(path).with_context(|| format!("reading checkpoint {}", path.display()))?;
+    let c: ScanCheckpoint = serde_json::from_str(&s).with_context(|| format!("parsing checkpoint {}", path.display()))?;
+    if c.version != VERSION { return Err(anyhow!("unsupported checkpoint version {}", c.version)); }
+    Ok(c)
+}
+
+/// Write via a temporary file and rename, so a crash mid-write keeps the previous checkpoint.
+pub fn save(path: &Path, c: &ScanCheckpoint) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, serde_json::to_vec(c)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> ScanCheckpoint {
+        let args = ScanArgs {
+            target: Some("10.0.0.0/30".into()), targets: None, ports: vec![22, 80], timeout_ms: 500, min_rtt_timeout_ms: 50, max_rtt_timeout_ms: 2000,
+            concurrency: 64, qps: 0, burst: None, adaptive: false, min_qps: None, max_qps: None, retries: 1, retry_delay_ms: 100, host_concurrency: 4,
+            max_connections: None, mode: ScanModeArg::Connect, seed: Some(7), format: OutputFormat::Jsonl, out: None, dns_retries: 0, dns_retry_delay_ms: 0,
+            ipv4: false, ipv6: false, ptr: false,
+        };
+        ScanCheckpoint::new("run".into(), args, Some(2), "abc".into())
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("toolbox-checkpoint-{}-{}.json", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = temp_path("round-trip");
+        let mut c = checkpoint();
+        c.done_below = 3;
+        c.done_above = vec![5, 8];
+        c.partial = vec![PartialHost { host: 4, label: "host".into(), addr: "10.0.0.4".into(), t_first: 1, t_last: 2, ports: vec![PartialPort { port: 22, reason: "syn-ack".into(), rtt_us: Some(900) }], closed: 3, filtered: 1 }];
+        save(&path, &c).unwrap();
+        let back = load(&path).unwrap();
+        assert_eq!(serde_json::to_value(&back).unwrap(), serde_json::to_value(&c).unwrap());
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        assert!(!Path::new(&tmp).exists());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let path = temp_path("version");
+        let mut c = checkpoint();
+        c.version = VERSION + 1;
+        save(&path, &c).unwrap();
+        let err = load(&path).unwrap_err().to_string();
+        assert!(err.contains("unsupported checkpoint version"), "{}", err);
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn rebuilds_the_watermark() {
+        let path = temp_path("watermark");
+        let mut c = checkpoint();
+        c.done_below = 3;
+        c.done_above = vec![4, 3, 9, 5];
+        save(&path, &c).unwrap();
+        let c = load(&path).unwrap();
+        let done = port_scan::Progress::from_parts(c.done_below, c.done_above.iter().copied());
+        assert_eq!((done.watermark(), done.above().collect::<Vec<_>>(), done.count()), (6, vec![9], 7));
+        assert!(done.contains(0) && done.contains(5) && !done.contains(6) && done.contains(9));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn refuses_other_targets() {
+        let c = checkpoint();
+        assert!(c.check_targets(Some(2), "abc").is_ok());
+        assert!(c.check_targets(Some(3), "abc").is_err());
+        assert!(c.check_targets(Some(2), "abd").is_err(), "same size, other targets");
+        let old = ScanCheckpoint { targets_hash: None, ..c };
+        assert!(old.check_targets(Some(2), "abd").is_ok(), "written before targets were hashed");
+    }
+}
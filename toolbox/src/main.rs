@@ -3,9 +3,9 @@ use anyhow::Result;
 use anyhow::anyhow;
 use clap::{Parser, Subcommand, ValueEnum};
 #[cfg(any(feature = "scan", feature = "discover"))]
-use std::fs::{File, OpenOptions};
+use std::fs::OpenOptions;
 #[cfg(any(feature = "scan", feature = "discover"))]
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 use std::time::Instant;
 #[cfg(any(feature = "scan", feature = "discover"))]
@@ -26,6 +26,24 @@ fn sh(cmd: &str, args: &[&str]) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Run a command to completion, sampling its peak RSS (VmHWM, Linux only) while it runs.
+fn sh_peak_rss_mb(cmd: &str, args: &[&str]) -> anyhow::Result<f32> {
+    let mut child = std::process::Command::new(cmd).args(args).spawn()?;
+    let mut peak = 0.0f32;
+    loop {
+        #[cfg(target_os="linux")]
+        if let Ok(status) = std::fs::read_to_string(format!("/proc/{}/status", child.id())) {
+            let hwm_kb = status.lines().find_map(|l| l.strip_prefix("VmHWM:")).and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<f32>().ok());
+            if let Some(kb) = hwm_kb { peak = peak.max(kb / 1024.0); }
+        }
+        if let Some(status) = child.try_wait()? {
+            if !status.success() { return Err(anyhow::anyhow!(format!("{cmd} {:?} failed with {status}", args))); }
+            return Ok(peak);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+}
+
 fn count_lines(p: &std::path::Path) -> anyhow::Result<u64> {
     use std::io::BufRead;
     let f = std::fs::File::open(p)?;
@@ -54,13 +72,332 @@ fn git_sha() -> anyhow::Result<String> {
     Ok(String::from_utf8_lossy(&out.stdout).trim().to_string())
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum OutputFormat { Text, Json, Jsonl }
 
+/// How `--progress` reports.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum ProgressMode { Line, Jsonl }
+
+/// TCP probe method for `scan`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ScanModeArg { Connect, Syn }
+
+/// Liveness technique for `discover`.
+#[cfg(feature = "discover")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+enum DiscoverMethodArg { Tcp, Icmp, Arp }
+
+#[cfg(feature = "discover")]
+impl From<DiscoverMethodArg> for host_discovery::Method {
+    fn from(m: DiscoverMethodArg) -> Self {
+        match m {
+            DiscoverMethodArg::Tcp => host_discovery::Method::Tcp,
+            DiscoverMethodArg::Icmp => host_discovery::Method::Icmp,
+            DiscoverMethodArg::Arp => host_discovery::Method::Arp,
+        }
+    }
+}
+
+#[cfg(feature = "scan")]
+mod checkpoint;
 mod config;
+mod profile;
+/// Build a target set from a positional spec and/or a `--targets` file (treated as `@FILE`).
+#[cfg(any(feature = "scan", feature = "webscan", feature = "banner", feature = "web", feature = "udp"))]
+fn target_set(target: Option<String>, targets: Option<PathBuf>) -> Result<toolbox_core::TargetSet> {
+    let mut specs = Vec::new();
+    if let Some(t) = target { specs.push(t); }
+    if let Some(p) = targets { specs.push(format!("@{}", p.display())); }
+    Ok(toolbox_core::TargetSet::parse(specs)?)
+}
+
+/// Per-port connect RTTs of open ports as `{"<port>": ms}`.
+#[cfg(feature = "scan")]
+fn rtt_ms_json(results: &[port_scan::PortResult]) -> serde_json::Value {
+    let mut m = serde_json::Map::new();
+    for r in results.iter().filter(|r| r.is_open()) {
+        if let Some(rtt) = r.rtt { m.insert(r.port.to_string(), serde_json::json!(rtt_ms(rtt))); }
+    }
+    serde_json::Value::Object(m)
+}
+
+#[cfg(feature = "scan")]
+fn rtt_ms(d: std::time::Duration) -> f64 {
+    (d.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+/// Per-port state/reason objects for JSON output.
+#[cfg(feature = "scan")]
+fn ports_json(results: &[port_scan::PortResult]) -> serde_json::Value {
+    results.iter().map(|r| {
+        let mut o = serde_json::json!({ "port": r.port, "state": r.state.as_str(), "reason": r.reason.as_str() });
+        if let Some(rtt) = r.rtt { o["rtt_ms"] = serde_json::json!(rtt_ms(rtt)); }
+        o
+    }).collect()
+}
+
+/// `scan.port` event line (see docs/results.md).
+#[cfg(feature = "scan")]
+fn port_event_json(run_id: &str, ev: &port_scan::PortEvent) -> serde_json::Value {
+    let r = &ev.result;
+    let mut o = serde_json::json!({
+        "type": "scan.port",
+        "run_id": run_id,
+        "addr": ev.addr,
+        "transport": "tcp",
+        "port": r.port,
+        "state": r.state.as_str(),
+        "reason": r.reason.as_str(),
+        "t_first": ev.t_first,
+        "t_last": ev.t_last,
+    });
+    if let Some(h) = &ev.hostname { o["hostname"] = serde_json::json!(h); }
+    if let Some(rtt) = r.rtt { o["rtt_us"] = serde_json::json!(rtt.as_micros() as u64); }
+    o
+}
+
+/// Streamed `scan.host` record for a finished address (written with `--ptr`).
+#[cfg(feature = "scan")]
+fn host_event_json(run_id: &str, addr: &std::net::IpAddr, hostname: Option<String>, ptr: Option<&toolbox_core::dns::Ptr>) -> serde_json::Value {
+    let mut o = serde_json::json!({ "type": "scan.host", "run_id": run_id, "addr": addr });
+    if let Some(h) = hostname { o["hostname"] = serde_json::json!(h); }
+    add_ptr_json(&mut o, ptr);
+    o
+}
+
+/// Text label for one address a target was probed at: the address, then the name it resolved from.
+#[cfg(any(feature = "banner", feature = "web", feature = "udp"))]
+fn dest_label(host: &toolbox_core::Host, dest: &toolbox_core::connect::Dest) -> String {
+    match (host, dest) {
+        (toolbox_core::Host::Name(n), toolbox_core::connect::Dest::Addr(_)) => format!("{} ({})", dest, n),
+        _ => dest.to_string(),
+    }
+}
+
+/// Adds the probed address to a JSON line when it is known (not left to a proxy to resolve).
+#[cfg(any(feature = "banner", feature = "web", feature = "udp"))]
+fn with_addr(mut obj: serde_json::Value, dest: &toolbox_core::connect::Dest) -> serde_json::Value {
+    if let toolbox_core::connect::Dest::Addr(a) = dest { obj["addr"] = serde_json::json!(a.ip()); }
+    obj
+}
+
+/// `run.status` line ending the output of a run cut short (see docs/results.md).
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan", feature = "banner", feature = "web", feature = "udp"))]
+fn interrupted_event(run_id: Option<&str>) -> serde_json::Value {
+    let mut o = serde_json::json!({ "type": "run.status", "status": "interrupted", "t": unix_ms() });
+    if let Some(id) = run_id { o["run_id"] = serde_json::json!(id); }
+    o
+}
+
+/// Cancel on the first SIGINT or SIGTERM so the command stops probing and writes what it has;
+/// a second signal closes the audit log and exits at once.
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan", feature = "banner", feature = "web", feature = "udp"))]
+async fn interrupt_on_signal(cancel: toolbox_core::cancel::Cancel) {
+    #[cfg(unix)]
+    let mut term = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).ok();
+    loop {
+        #[cfg(unix)]
+        let terminate = async { match term.as_mut() { Some(t) => { t.recv().await; } None => std::future::pending().await } };
+        #[cfg(not(unix))]
+        let terminate = std::future::pending::<()>();
+        tokio::select! {
+            r = tokio::signal::ctrl_c() => if r.is_err() { return; },
+            _ = terminate => {}
+        }
+        if cancel.is_cancelled() {
+            eprintln!("interrupted again; exiting without flushing results");
+            finish_audit("interrupted");
+            std::process::exit(130);
+        }
+        eprintln!("interrupted; waiting up to {} ms for probes in flight (interrupt again to exit now)", cancel.drain_time().as_millis());
+        cancel.cancel();
+    }
+}
+
+/// Run a connect scan, handing each event to `on_event` as it arrives. Returns the results
+/// sorted by port.
+#[cfg(feature = "scan")]
+async fn scan_streaming(target: &toolbox_core::Target, ports: &[u16], opts: &port_scan::ScanOptions, mut on_event: impl FnMut(&port_scan::PortEvent)) -> Vec<port_scan::PortResult> {
+    let (tx, mut rx) = mpsc::channel(256);
+    let collect = async {
+        let mut results = Vec::new();
+        while let Some(ev) = rx.recv().await {
+            on_event(&ev);
+            results.push(ev.result);
+        }
+        results
+    };
+    let ((), mut results) = tokio::join!(port_scan::scan_connect_stream(target, ports, opts, tx), collect);
+    results.sort_unstable_by_key(|r| r.port);
+    results
+}
+
+/// Port results of one target address collected for its summary line.
+#[cfg(feature = "scan")]
+struct HostResults {
+    /// Hostname, or the address for literal targets.
+    label: String,
+    addr: String,
+    results: Vec<port_scan::PortResult>,
+    /// Closed and filtered ports counted but not kept in `results` (randomized scans keep only
+    /// open ports, as every touched address stays here until the end).
+    closed: usize,
+    filtered: usize,
+    t_first: i64,
+    t_last: i64,
+    /// Set by `--ptr` before the line is written.
+    ptr: Option<toolbox_core::dns::Ptr>,
+}
+
+#[cfg(feature = "scan")]
+impl HostResults {
+    fn new(ev: &port_scan::PortEvent) -> Self {
+        HostResults { label: ev.hostname.clone().unwrap_or_else(|| ev.addr.clone()), addr: ev.addr.clone(), results: Vec::new(), closed: 0, filtered: 0, t_first: ev.t_first, t_last: ev.t_last, ptr: None }
+    }
+
+    /// Add a port; with `open_only`, closed and filtered ports are only counted.
+    fn push(&mut self, ev: &port_scan::PortEvent, open_only: bool) {
+        match ev.result.state {
+            port_scan::PortState::Closed if open_only => self.closed += 1,
+            port_scan::PortState::Filtered if open_only => self.filtered += 1,
+            _ => self.results.push(ev.result),
+        }
+        self.t_first = self.t_first.min(ev.t_first);
+        self.t_last = self.t_last.max(ev.t_last);
+    }
+
+    /// Snapshot for a scan checkpoint.
+    fn to_partial(&self, host: usize) -> checkpoint::PartialHost {
+        let ports = self.results.iter().map(|r| checkpoint::PartialPort { port: r.port, reason: r.reason.as_str().to_string(), rtt_us: r.rtt.map(|d| d.as_micros() as u64) }).collect();
+        checkpoint::PartialHost { host, label: self.label.clone(), addr: self.addr.clone(), t_first: self.t_first, t_last: self.t_last, ports, closed: self.closed, filtered: self.filtered }
+    }
+
+    fn from_partial(p: &checkpoint::PartialHost) -> Result<Self> {
+        let results = p.ports.iter().map(|pp| {
+            let reason: port_scan::Reason = pp.reason.parse()?;
+            Ok(port_scan::PortResult { port: pp.port, state: reason.state(), reason, rtt: pp.rtt_us.map(std::time::Duration::from_micros) })
+        }).collect::<Result<_>>()?;
+        let addr = if p.addr.is_empty() { p.label.clone() } else { p.addr.clone() };
+        Ok(HostResults { label: p.label.clone(), addr, results, closed: p.closed, filtered: p.filtered, t_first: p.t_first, t_last: p.t_last, ptr: None })
+    }
+
+    fn span_ms(&self) -> u128 {
+        (self.t_last - self.t_first).max(0) as u128
+    }
+
+    /// Per-address text or JSON summary; names show which of their addresses it is for.
+    fn line(mut self, target: &str, duration_ms: u128, format: OutputFormat, timeout_ms: u64, concurrency: usize) -> String {
+        self.results.sort_unstable_by_key(|r| r.port);
+        let results = &self.results;
+        match format {
+            OutputFormat::Text => {
+                let mut label = if self.label != self.addr { format!("{} ({})", target, self.addr) } else { target.to_string() };
+                if let Some(p) = &self.ptr { label = format!("{} {}", label, ptr_note(p)); }
+                scan_text_line(&label, results, (self.closed, self.filtered), duration_ms)
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                let mut o = serde_json::json!({
+                    "target": target,
+                    "addr": self.addr,
+                    "scanned": results.len() + self.closed + self.filtered,
+                    "open": results.iter().filter(|r| r.is_open()).map(|r| r.port).collect::<Vec<_>>(),
+                    "ports": ports_json(results),
+                    "rtt_ms": rtt_ms_json(results),
+                    "timeout_ms": timeout_ms,
+                    "concurrency": concurrency,
+                    "duration_ms": duration_ms,
+                });
+                if self.closed + self.filtered > 0 { (o["closed"], o["filtered"]) = (self.closed.into(), self.filtered.into()); }
+                add_ptr_json(&mut o, self.ptr.as_ref());
+                o.to_string()
+            }
+        }
+    }
+
+    /// Look up the PTR name of the address.
+    async fn lookup_ptr(&mut self, dns: &toolbox_core::dns::Resolver) {
+        if let Ok(ip) = self.addr.parse() { self.ptr = dns.reverse(ip).await; }
+    }
+}
+
+/// Message for the multi-target scan writer thread. A checkpoint is saved only after every line
+/// sent before it has been written.
+#[cfg(feature = "scan")]
+enum ScanOutput {
+    Line(String),
+    Checkpoint(Box<checkpoint::ScanCheckpoint>),
+}
+
+/// Text summary line for one scanned target; `dropped` counts closed and filtered ports left out
+/// of `results`.
+#[cfg(feature = "scan")]
+fn scan_text_line(target: &str, results: &[port_scan::PortResult], dropped: (usize, usize), duration_ms: u128) -> String {
+    let count = |st| results.iter().filter(|r| r.state == st).count();
+    let (closed, filtered) = (count(port_scan::PortState::Closed) + dropped.0, count(port_scan::PortState::Filtered) + dropped.1);
+    let scanned = results.len() + dropped.0 + dropped.1;
+    let open = results.iter().filter(|r| r.is_open()).map(|r| r.port.to_string()).collect::<Vec<_>>();
+    if open.is_empty() {
+        format!("{}: no open ports found ({} scanned, {} closed, {} filtered)", target, scanned, closed, filtered)
+    } else {
+        format!("{}: open ports [{}] ({} scanned, {} closed, {} filtered, {} ms)", target, open.join(","), scanned, closed, filtered, duration_ms)
+    }
+}
+
+#[cfg(feature = "scan")]
+fn adaptive_summary_json(s: &toolbox_core::congestion::AimdSummary) -> serde_json::Value {
+    serde_json::json!({
+        "start_qps": s.start_rate,
+        "final_qps": s.final_rate,
+        "min_qps": s.min_rate_seen,
+        "max_qps": s.max_rate_seen,
+        "start_concurrency": s.start_window,
+        "final_concurrency": s.final_window,
+        "increases": s.increases,
+        "backoffs": s.decreases,
+        "responded": s.responded,
+        "timeouts": s.timeouts,
+    })
+}
+
+/// Adaptive-rate run summary on stderr, so per-host result lines stay clean.
+#[cfg(feature = "scan")]
+fn print_adaptive_summary(s: &toolbox_core::congestion::AimdSummary, format: OutputFormat) {
+    match format {
+        OutputFormat::Text => eprintln!(
+            "adaptive: qps {:.0} -> {:.0} (range {:.0}-{:.0}), concurrency {} -> {}, {} increases / {} backoffs ({} responded, {} timeouts)",
+            s.start_rate, s.final_rate, s.min_rate_seen, s.max_rate_seen, s.start_window, s.final_window, s.increases, s.decreases, s.responded, s.timeouts
+        ),
+        OutputFormat::Json | OutputFormat::Jsonl => eprintln!("{}", serde_json::json!({ "type": "run.summary", "adaptive": adaptive_summary_json(s) })),
+    }
+}
+
 #[cfg(feature = "webscan")]
 fn modules_port_parse(spec: &str) -> anyhow::Result<Vec<u16>> { Ok(port_scan::parse_ports(spec)?) }
 
+#[derive(Debug, Subcommand)]
+enum AuditCmd {
+    /// Check an audit log's hash chain, run signatures and head file
+    Verify {
+        /// Log to check (default: the configured audit log)
+        #[arg(long)]
+        log: Option<PathBuf>,
+        /// Require every run to be signed with this public key (hex, or a file holding it)
+        #[arg(long)]
+        key: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+    /// Generate an ed25519 signing key (PKCS#8 PEM) and print its public key
+    Keygen {
+        #[arg(long)]
+        out: PathBuf,
+    },
+}
+
 #[cfg(feature = "forensics")]
 #[derive(Debug, Subcommand)]
 enum ForensicsCmd {
@@ -109,6 +446,48 @@ struct Cli {
     /// Optional config file (YAML). If omitted, loads ./toolbox.yaml if present.
     #[arg(long, global = true)]
     config: Option<PathBuf>,
+    /// Nameserver for all lookups (ip or ip:port, repeatable); overrides `dns.nameservers`
+    #[arg(long = "dns", global = true, value_delimiter = ',')]
+    dns: Vec<String>,
+    /// Scope file (YAML with include/exclude/ports); overrides the config's `scope` section
+    #[arg(long, global = true)]
+    scope: Option<PathBuf>,
+    /// Audit log recording every active action (hash-chained JSONL); overrides `audit.log`
+    #[arg(long, global = true, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+    /// ed25519 key (PKCS#8 PEM) signing each audited run; overrides `audit.key`
+    #[arg(long, global = true, value_name = "FILE")]
+    audit_key: Option<PathBuf>,
+    /// Allow intrusive capabilities (e.g. SNMP community probes, crack runs)
+    #[arg(long, global = true)]
+    active: bool,
+    /// Named profile from the config's `profiles` section; overrides `profile`
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Proxy chain for TCP connections, first hop first (socks5://, socks5h://, http://; comma
+    /// separated) or `direct`; overrides `proxy`
+    #[arg(long, global = true, value_name = "URLS")]
+    proxy: Option<String>,
+    /// Source address for outbound traffic (one per family, repeatable); overrides `source.ips`
+    #[arg(long, global = true, value_delimiter = ',', value_name = "IP")]
+    source_ip: Vec<std::net::IpAddr>,
+    /// Interface outbound traffic leaves from (SO_BINDTODEVICE, Linux); overrides `source.interface`
+    #[arg(long, global = true, value_name = "NAME")]
+    interface: Option<String>,
+    /// Source port or range for TCP connections and UDP probes, e.g. 40000-40999; overrides
+    /// `source.ports`
+    #[arg(long, global = true, value_name = "PORTS")]
+    source_port: Option<String>,
+    /// How long probes in flight may finish after Ctrl-C/SIGTERM before partial results are written
+    #[arg(long, global = true, value_name = "MS", default_value_t = 3000)]
+    drain_ms: u64,
+    /// Report progress of scan, discover and web-scan on stderr: a status line (`--progress`) or
+    /// `run.progress` JSONL events (`--progress=jsonl`)
+    #[arg(long, global = true, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "line")]
+    progress: Option<ProgressMode>,
+    /// Seconds between progress reports
+    #[arg(long, global = true, value_name = "SECS", default_value_t = 1)]
+    progress_interval: u64,
     #[command(subcommand)]
     command: Commands,
 }
@@ -120,10 +499,10 @@ enum Commands {
     /// Run a demo port scan (stub)
     #[cfg(feature = "scan")]
     Scan {
-        /// Target host or IP
+        /// Target spec: IP, host, host:port, URL, CIDR, range (10.0.0.1-50) or @FILE
         #[arg(conflicts_with = "targets")]
         target: Option<String>,
-        /// File with newline-delimited targets (comments with # and blanks ignored)
+        /// File with newline-delimited target specs (comments with # and blanks ignored)
         #[arg(long, value_name = "FILE", conflicts_with = "target")]
         targets: Option<PathBuf>,
         /// Ports: comma/range list (e.g., 22,80,443 or 1-1024,8080). Default: common ports.
@@ -132,15 +511,33 @@ enum Commands {
         /// Select top N common ports (conflicts with --ports)
         #[arg(long, conflicts_with = "ports")]
         top: Option<usize>,
-        /// Timeout per port in milliseconds
+        /// Initial timeout per port in milliseconds; adapts per host from measured RTTs
         #[arg(long, default_value_t = 500)]
         timeout_ms: u64,
+        /// Lower bound for RTT-derived per-host timeouts
+        #[arg(long, default_value_t = 100)]
+        min_rtt_timeout_ms: u64,
+        /// Upper bound for RTT-derived per-host timeouts
+        #[arg(long, default_value_t = 10000)]
+        max_rtt_timeout_ms: u64,
         /// Max concurrent connections
         #[arg(long, default_value_t = 256)]
         concurrency: usize,
         /// QPS cap for connection attempts; 0 disables pacing
         #[arg(long, default_value_t = 0)]
         qps: u32,
+        /// Token-bucket burst for --qps (default: ~10 ms worth of tokens)
+        #[arg(long)]
+        burst: Option<u32>,
+        /// Adapt QPS and per-host concurrency to timeouts/refusals (AIMD), starting from --qps (1000 if unset) and --concurrency
+        #[arg(long, default_value_t = false)]
+        adaptive: bool,
+        /// Lower QPS bound for --adaptive (default: start/10)
+        #[arg(long, requires = "adaptive")]
+        min_qps: Option<u32>,
+        /// Upper QPS bound for --adaptive (default: start*10)
+        #[arg(long, requires = "adaptive")]
+        max_qps: Option<u32>,
         /// Retries per port on failure
         #[arg(long, default_value_t = 0)]
         retries: u32,
@@ -153,6 +550,21 @@ enum Commands {
         /// Maximum total concurrent connections across all hosts (default: concurrency * host_concurrency)
         #[arg(long)]
         max_connections: Option<usize>,
+        /// Probe method: full connect() handshake, or raw SYN (Linux, needs CAP_NET_RAW; falls back to connect)
+        #[arg(long, value_enum, default_value_t = ScanModeArg::Connect)]
+        mode: ScanModeArg,
+        /// IPv4 only: skip IPv6 targets and AAAA records (default: every address of a name)
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// IPv6 only: skip IPv4 targets and A records
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
+        /// Probe host×port pairs in a pseudo-random order spread across all targets
+        #[arg(long, default_value_t = false)]
+        randomize: bool,
+        /// Seed for --randomize (implies it); the same seed reproduces the same order
+        #[arg(long)]
+        seed: Option<u64>,
         /// Output format: text, json, or jsonl
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
@@ -160,14 +572,26 @@ enum Commands {
         #[arg(long, value_name = "FILE")]
         out: Option<PathBuf>,
         /// Write CSV instead of text/json when --out is provided (single target only for now)
-        #[arg(long, default_value_t = false)]
+        #[arg(long, default_value_t = false, conflicts_with_all = ["checkpoint", "resume"])]
         csv: bool,
+        /// Periodically save progress and partial results to FILE for --resume
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+        /// Seconds between checkpoints
+        #[arg(long, default_value_t = 30, value_name = "SECS")]
+        checkpoint_interval: u64,
+        /// Continue the scan saved in a checkpoint (its targets and options are reused; output is appended)
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["target", "targets"])]
+        resume: Option<PathBuf>,
         /// DNS resolve retry attempts (best-effort resolution)
         #[arg(long, default_value_t = 0)]
         dns_retries: u32,
         /// Delay between DNS retries in milliseconds
         #[arg(long, default_value_t = 200)]
         dns_retry_delay_ms: u64,
+        /// Look up the PTR name of every scanned address (forward-confirmed)
+        #[arg(long, default_value_t = false)]
+        ptr: bool,
     },
     /// Run local benchmark suite and emit JSONL metrics
     Bench {
@@ -183,12 +607,19 @@ enum Commands {
         /// Import into results DB and export Parquet
         #[arg(long, default_value_t = false)]
         store: bool,
+        /// Target counts for the scan-scale phase (loopback /N sweeps; empty to skip)
+        #[arg(long, default_value = "256,4096,65536")]
+        scale: String,
     },
     /// Discover live hosts via TCP connect sweep
     #[cfg(feature = "discover")]
     Discover {
-        /// CIDR (e.g., 192.168.1.0/24) or hostname
-        target: String,
+        /// Target specs: CIDR (e.g., 192.168.1.0/24), range, IP, hostname or @FILE; expanded lazily
+        #[arg(required = true, num_args = 1..)]
+        targets: Vec<String>,
+        /// Addresses, CIDRs, ranges or @FILE to leave out (comma-separated or repeated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
         /// Ports to probe for liveness (default: 80,443,22)
         #[arg(long)]
         ports: Option<String>,
@@ -201,18 +632,36 @@ enum Commands {
         /// QPS cap for probe launches (across hosts); 0 disables pacing
         #[arg(long, default_value_t = 0)]
         qps: u32,
+        /// IPv4 only: skip IPv6 targets and AAAA records (default: every address of a name)
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// IPv6 only: skip IPv4 targets and A records
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
         /// Output format: text, json, or jsonl
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
         /// Output file (overwrites). JSONL writes one line per live host.
         #[arg(long, value_name = "FILE")]
         out: Option<PathBuf>,
+        /// Look up the PTR name of every live host (forward-confirmed)
+        #[arg(long, default_value_t = false)]
+        ptr: bool,
+        /// Liveness methods, tried in order until one answers: tcp (connect to --ports), icmp (echo,
+        /// plus timestamp when raw sockets are available; uses ping sockets or CAP_NET_RAW), arp (ARP
+        /// or IPv6 neighbor solicitation for on-link addresses, records MACs; needs CAP_NET_RAW)
+        #[arg(long, value_enum, value_delimiter = ',')]
+        method: Vec<DiscoverMethodArg>,
     },
     /// Grab service banners (HTTP/HTTPS/SSH)
     #[cfg(feature = "banner")]
     Banner {
-        /// Target hostname or IP
-        target: String,
+        /// Target spec: host, IP, host:port, URL, CIDR, range or @FILE
+        #[arg(conflicts_with = "targets")]
+        target: Option<String>,
+        /// File with newline-delimited target specs
+        #[arg(long, value_name = "FILE", conflicts_with = "target")]
+        targets: Option<PathBuf>,
         /// Port to probe (common: 80, 443, 22)
         #[arg(long)]
         port: Option<u16>,
@@ -228,6 +677,12 @@ enum Commands {
         /// Timeout in milliseconds
         #[arg(long, default_value_t = 500)]
         timeout_ms: u64,
+        /// IPv4 only: skip IPv6 targets and AAAA records (default: every address of a name)
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// IPv6 only: skip IPv4 targets and A records
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
         /// Output format
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
@@ -235,8 +690,12 @@ enum Commands {
     /// Quick HTTP(S) banner scan with one-hop redirect follow
     #[cfg(feature = "web")]
     Web {
-        /// Target hostname or IP
-        target: String,
+        /// Target spec: host, IP, host:port, URL, CIDR, range or @FILE
+        #[arg(conflicts_with = "targets")]
+        target: Option<String>,
+        /// File with newline-delimited target specs
+        #[arg(long, value_name = "FILE", conflicts_with = "target")]
+        targets: Option<PathBuf>,
         /// Ports to probe (default: 80,443)
         #[arg(long)]
         ports: Option<String>,
@@ -249,6 +708,12 @@ enum Commands {
         /// Timeout per port in milliseconds
         #[arg(long, default_value_t = 800)]
         timeout_ms: u64,
+        /// IPv4 only: skip IPv6 targets and AAAA records (default: every address of a name)
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// IPv6 only: skip IPv4 targets and A records
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
         /// Output format: text, json, or jsonl
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
@@ -256,10 +721,10 @@ enum Commands {
     /// Web surface scan (HTTP(S) status/title) with concurrency and JSONL output
     #[cfg(feature = "webscan")]
     WebScan {
-        /// Single host or newline-delimited file via --targets
+        /// Target spec: host, IP, host:port, URL, CIDR, range or @FILE
         #[arg(conflicts_with = "targets")]
         target: Option<String>,
-        /// File with newline-delimited targets
+        /// File with newline-delimited target specs
         #[arg(long, value_name = "FILE", conflicts_with = "target")]
         targets: Option<PathBuf>,
         /// Ports to scan
@@ -293,6 +758,13 @@ enum Commands {
         #[command(subcommand)]
         cmd: ForensicsCmd,
     },
+    /// List module capabilities, their intrusiveness and whether the current policy allows them
+    Capabilities,
+    /// Audit log tools: verify, keygen
+    Audit {
+        #[command(subcommand)]
+        cmd: AuditCmd,
+    },
     /// Credentials utilities
     #[cfg(feature = "creds")]
     Creds {
@@ -308,8 +780,12 @@ enum Commands {
     /// UDP probe for common services (dns, ntp)
     #[cfg(feature = "udp")]
     UdpProbe {
-        /// Target hostname or IP
-        target: String,
+        /// Target spec: host, IP, host:port, URL, CIDR, range or @FILE
+        #[arg(conflicts_with = "targets")]
+        target: Option<String>,
+        /// File with newline-delimited target specs
+        #[arg(long, value_name = "FILE", conflicts_with = "target")]
+        targets: Option<PathBuf>,
         /// Service: dns, ntp or snmp
         #[arg(long, value_parser=["dns","ntp","snmp"])]
         service: String,
@@ -319,6 +795,12 @@ enum Commands {
         /// Timeout in milliseconds
         #[arg(long, default_value_t = 500)]
         timeout_ms: u64,
+        /// IPv4 only: skip IPv6 targets and AAAA records (default: every address of a name)
+        #[arg(short = '4', long = "ipv4", conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// IPv6 only: skip IPv4 targets and A records
+        #[arg(short = '6', long = "ipv6")]
+        ipv6: bool,
         /// Output format
         #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
         format: OutputFormat,
@@ -328,17 +810,470 @@ enum Commands {
 #[cfg(feature = "results")]
 #[derive(Debug, Subcommand)]
 enum ResultsCmd {
-    Import { #[arg(long)] db: PathBuf, #[arg(long, value_name = "FILE")] from: PathBuf },
+    /// Import JSONL output; --ptr looks up PTR names for imported hosts that lack one
+    Import { #[arg(long)] db: PathBuf, #[arg(long, value_name = "FILE")] from: PathBuf, #[arg(long, default_value_t = false)] ptr: bool },
     Export { #[arg(long)] db: PathBuf, #[arg(long)] table: String, #[arg(long)] format: String, #[arg(long)] out: PathBuf },
     Query  { #[arg(long)] db: PathBuf, #[arg(long)] sql: String, #[arg(long, default_value="jsonl")] format: String, #[arg(long)] out: Option<PathBuf> },
 }
+
+/// JSONL record for a live host: the probe that found it and its answer (with the port for TCP),
+/// the name it was resolved from, its MAC address and PTR name when known.
+#[cfg(feature = "discover")]
+fn discover_line(live: &host_discovery::Live, hostname: Option<String>, ptr: Option<&toolbox_core::dns::Ptr>) -> serde_json::Value {
+    let mut o = serde_json::json!({"host": live.addr, "method": live.probe.as_str(), "reason": live.reason.as_str()});
+    if let Some(port) = live.port { o["port"] = serde_json::json!(port); }
+    if let Some(name) = hostname { o["hostname"] = serde_json::json!(name); }
+    if let Some(mac) = live.mac {
+        o["mac"] = serde_json::json!(mac.to_string());
+        if let Some(v) = mac.vendor() { o["vendor"] = serde_json::json!(v); }
+    }
+    add_ptr_json(&mut o, ptr);
+    o
+}
+
+/// Status line for `--progress`: work done (of the total when known), probes sent, hosts done,
+/// findings, errors, probe rate and ETA.
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+fn progress_line(command: &str, r: &toolbox_core::progress::Report) -> String {
+    let (unit, found) = match command { "scan" => ("ports", "open"), "discover" => ("targets", "live"), _ => ("endpoints", "answering") };
+    let s = &r.snapshot;
+    let of = match (s.total, r.percent) {
+        (Some(t), Some(pct)) => format!("{}/{} {} ({:.1}%)", s.done, t, unit, pct),
+        _ => format!("{} {}", s.done, unit),
+    };
+    let eta = r.eta.map(|d| format!(", ETA {}", clock(d))).unwrap_or_default();
+    format!("{}: {}, {} probes sent, {} hosts done, {} {}, {} errors, {:.0}/s{}", command, of, s.sent, s.hosts, s.found, found, s.errors, r.rate, eta)
+}
+
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+fn clock(d: std::time::Duration) -> String {
+    let s = d.as_secs();
+    if s >= 3600 { format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60) } else { format!("{}:{:02}", s / 60, s % 60) }
+}
+
+/// `run.progress` event for `--progress=jsonl` (see docs/results.md).
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+fn progress_event_json(command: &str, run_id: Option<&str>, r: &toolbox_core::progress::Report) -> serde_json::Value {
+    let s = &r.snapshot;
+    let mut o = serde_json::json!({
+        "type": "run.progress",
+        "command": command,
+        "done": s.done,
+        "total": s.total,
+        "percent": r.percent.map(|p| (p * 10.0).round() / 10.0),
+        "sent": s.sent,
+        "hosts_done": s.hosts,
+        "found": s.found,
+        "errors": s.errors,
+        "rate": (r.rate * 10.0).round() / 10.0,
+        "eta_s": r.eta.map(|d| d.as_secs()),
+        "elapsed_ms": r.elapsed.as_millis() as u64,
+        "t": unix_ms(),
+    });
+    if let Some(id) = run_id { o["run_id"] = serde_json::json!(id); }
+    o
+}
+
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan", feature = "banner", feature = "web", feature = "udp"))]
+fn unix_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Background task reporting a command's progress on stderr every `--progress-interval` seconds,
+/// with a last report once finished.
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+struct ProgressReporter {
+    stop: tokio::sync::oneshot::Sender<()>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+impl ProgressReporter {
+    /// Start reporting `progress` on `rt`; nothing without `--progress`. A status line on a
+    /// terminal is rewritten in place.
+    fn start(rt: &tokio::runtime::Runtime, mode: Option<ProgressMode>, every_secs: u64, command: &'static str, run_id: Option<String>, progress: toolbox_core::progress::Progress) -> Option<Self> {
+        use std::io::IsTerminal;
+        let mode = mode?;
+        let tty = mode == ProgressMode::Line && std::io::stderr().is_terminal();
+        let (stop, mut stopped) = tokio::sync::oneshot::channel::<()>();
+        let task = rt.spawn(async move {
+            let mut meter = toolbox_core::progress::Meter::default();
+            let mut tick = tokio::time::interval(std::time::Duration::from_secs(every_secs.max(1)));
+            tick.tick().await;
+            loop {
+                let last = tokio::select! {
+                    _ = tick.tick() => false,
+                    _ = &mut stopped => true,
+                };
+                let r = meter.sample(progress.snapshot());
+                match mode {
+                    ProgressMode::Line if tty => eprint!("\r\x1b[K{}{}", progress_line(command, &r), if last { "\n" } else { "" }),
+                    ProgressMode::Line => eprintln!("{}", progress_line(command, &r)),
+                    ProgressMode::Jsonl => eprintln!("{}", progress_event_json(command, run_id.as_deref(), &r)),
+                }
+                if last { break; }
+            }
+        });
+        Some(ProgressReporter { stop, task })
+    }
+
+    /// Write the last report and wait for it.
+    fn finish(self, rt: &tokio::runtime::Runtime) {
+        let _ = self.stop.send(());
+        let _ = rt.block_on(self.task);
+    }
+}
+
+/// Text note for a PTR name, flagged when it does not resolve back to the address.
+#[cfg(any(feature = "scan", feature = "discover"))]
+fn ptr_note(ptr: &toolbox_core::dns::Ptr) -> String {
+    if ptr.confirmed { format!("[ptr {}]", ptr.name) } else { format!("[ptr {} (unconfirmed)]", ptr.name) }
+}
+
+#[cfg(any(feature = "scan", feature = "discover"))]
+fn add_ptr_json(o: &mut serde_json::Value, ptr: Option<&toolbox_core::dns::Ptr>) {
+    if let Some(p) = ptr {
+        o["ptr"] = serde_json::json!(p.name);
+        o["ptr_confirmed"] = serde_json::json!(p.confirmed);
+    }
+}
+
+/// Install the shared resolver when nameservers or limits are configured; otherwise modules use
+/// the system configuration.
+fn setup_dns(flag: &[String], cfg: Option<&config::DnsConfig>) -> Result<()> {
+    use toolbox_core::dns;
+    if flag.is_empty() && cfg.is_none() { return Ok(()); }
+    let cfg = cfg.cloned().unwrap_or_default();
+    let servers = if flag.is_empty() { cfg.nameservers.unwrap_or_default() } else { flag.to_vec() };
+    let mut dc = dns::DnsConfig::default();
+    for s in &servers {
+        let addr = s.parse::<std::net::SocketAddr>().or_else(|_| s.parse::<std::net::IpAddr>().map(|ip| std::net::SocketAddr::new(ip, 53)));
+        dc.nameservers.push(addr.map_err(|_| anyhow::anyhow!("invalid nameserver: {}", s))?);
+    }
+    if let Some(q) = cfg.qps { dc.qps = q; }
+    if let Some(c) = cfg.concurrency { dc.concurrency = c; }
+    if let Some(t) = cfg.timeout_ms { dc.timeout = std::time::Duration::from_millis(t); }
+    if let Some(t) = cfg.max_ttl_secs { dc.max_ttl = std::time::Duration::from_secs(t); }
+    dns::install(dns::Resolver::new(dc)?)?;
+    Ok(())
+}
+
+/// Install the engagement scope from `--scope` or the config, narrowed to the profile's ports.
+/// Blocked attempts are logged to stderr (the first few individually) and counted for the
+/// end-of-run summary.
+fn setup_scope(file: Option<&std::path::Path>, cfg: Option<&config::ScopeConfig>, profile_ports: Option<&str>) -> Result<()> {
+    use toolbox_core::scope;
+    const LOGGED: u64 = 20;
+    let sc = match file {
+        Some(p) => {
+            let text = std::fs::read_to_string(p).map_err(|e| anyhow::anyhow!("scope file {}: {}", p.display(), e))?;
+            serde_yaml::from_str::<config::ScopeConfig>(&text).map_err(|e| anyhow::anyhow!("scope file {}: {}", p.display(), e))?
+        }
+        None => match (cfg, profile_ports) {
+            (Some(c), _) => c.clone(),
+            (None, Some(_)) => config::ScopeConfig::default(),
+            (None, None) => return Ok(()),
+        },
+    };
+    let mut scope = scope::Scope::new(&sc.include.unwrap_or_default(), &sc.exclude.unwrap_or_default(), sc.ports.as_deref())?;
+    if let Some(ports) = profile_ports { scope.restrict_ports(ports)?; }
+    let logged = std::sync::atomic::AtomicU64::new(0);
+    scope::install(scope, Some(Box::new(move |e: &scope::OutOfScope| {
+        let n = logged.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if n < LOGGED {
+            eprintln!("scope: blocked {} ({})", e.target, e.reason);
+        } else if n == LOGGED {
+            eprintln!("scope: further blocked attempts are counted but not shown");
+        }
+    })))?;
+    Ok(())
+}
+
+/// Install the proxy chain from `--proxy` or the config; without one, modules connect directly.
+fn setup_proxy(flag: Option<&str>, cfg: Option<&str>) -> Result<()> {
+    use toolbox_core::connect;
+    let Some(spec) = flag.or(cfg) else { return Ok(()) };
+    let connector = connect::Connector::parse(spec)?;
+    if !connector.is_direct() {
+        let chain = connector.chain().iter().map(|p| p.to_string()).collect::<Vec<_>>().join(" -> ");
+        eprintln!("proxy: TCP connections go through {}", chain);
+    }
+    connect::install(connector)?;
+    Ok(())
+}
+
+/// Install the source binding from the flags or the config's `source` section; without one the
+/// kernel picks source addresses, interfaces and ports.
+fn setup_source(cli: &Cli, cfg: Option<&config::SourceConfig>) -> Result<()> {
+    use toolbox_core::socket;
+    let cfg = cfg.cloned().unwrap_or_default();
+    let ips = if cli.source_ip.is_empty() {
+        cfg.ips.unwrap_or_default().iter().map(|s| s.parse().map_err(|_| anyhow::anyhow!("source.ips: invalid address {}", s))).collect::<Result<Vec<_>>>()?
+    } else {
+        cli.source_ip.clone()
+    };
+    let interface = cli.interface.clone().or(cfg.interface);
+    let ports = cli.source_port.clone().or(cfg.ports);
+    let binding = socket::Binding::new(&ips, interface.as_deref(), ports.as_deref())?;
+    if !binding.is_default() { socket::install(binding)?; }
+    Ok(())
+}
+
+impl Commands {
+    /// Whether the command sends traffic to targets, and so is recorded in the audit log.
+    fn is_active(&self) -> bool {
+        match self {
+            Commands::Version | Commands::Capabilities | Commands::Audit { .. } => false,
+            #[cfg(feature = "forensics")]
+            Commands::Forensics { .. } => false,
+            #[cfg(feature = "creds")]
+            Commands::Creds { .. } => false,
+            #[cfg(feature = "results")]
+            Commands::Results { .. } => false,
+            _ => true,
+        }
+    }
+
+    /// Subcommand name as written on the command line.
+    fn name(&self) -> &'static str {
+        match self {
+            Commands::Version => "version",
+            #[cfg(feature = "scan")]
+            Commands::Scan { .. } => "scan",
+            Commands::Bench { .. } => "bench",
+            #[cfg(feature = "discover")]
+            Commands::Discover { .. } => "discover",
+            #[cfg(feature = "banner")]
+            Commands::Banner { .. } => "banner",
+            #[cfg(feature = "web")]
+            Commands::Web { .. } => "web",
+            #[cfg(feature = "webscan")]
+            Commands::WebScan { .. } => "web-scan",
+            #[cfg(feature = "forensics")]
+            Commands::Forensics { .. } => "forensics",
+            Commands::Capabilities => "capabilities",
+            Commands::Audit { .. } => "audit",
+            #[cfg(feature = "creds")]
+            Commands::Creds { .. } => "creds",
+            #[cfg(feature = "results")]
+            Commands::Results { .. } => "results",
+            #[cfg(feature = "udp")]
+            Commands::UdpProbe { .. } => "udp-probe",
+        }
+    }
+
+    /// Modules the command runs.
+    fn modules(&self) -> &'static [&'static str] {
+        match self {
+            #[cfg(feature = "scan")]
+            Commands::Scan { .. } => &["port-scan"],
+            Commands::Bench { .. } => &["port-scan", "web-surface"],
+            #[cfg(feature = "discover")]
+            Commands::Discover { .. } => &["host-discovery"],
+            #[cfg(feature = "banner")]
+            Commands::Banner { .. } => &["banners"],
+            #[cfg(feature = "web")]
+            Commands::Web { .. } => &["banners"],
+            #[cfg(feature = "webscan")]
+            Commands::WebScan { .. } => &["web-surface"],
+            #[cfg(feature = "forensics")]
+            Commands::Forensics { .. } => &["forensics"],
+            #[cfg(feature = "creds")]
+            Commands::Creds { .. } => &["credentials"],
+            #[cfg(feature = "udp")]
+            Commands::UdpProbe { .. } => &["udp-probe"],
+            _ => &[],
+        }
+    }
+}
+
+/// Check the command against the active profile. Active commands must start inside one
+/// of its time windows; when the window closes the run is cancelled as on an interrupt.
+fn setup_profile(cli: &Cli, cancel: &toolbox_core::cancel::Cancel) -> Result<()> {
+    let Some(p) = profile::active() else { return Ok(()) };
+    if matches!(cli.command, Commands::Version | Commands::Capabilities | Commands::Audit { .. }) { return Ok(()); }
+    p.check_command(cli.command.name(), cli.command.modules())?;
+    if !cli.command.is_active() { return Ok(()); }
+    if let Some(left) = p.window_left()? {
+        let cancel = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(left);
+            eprintln!("profile {}: time window closed, stopping", p.name);
+            profile::close_window();
+            cancel.cancel();
+        });
+    }
+    Ok(())
+}
+
+/// Signing key for `run.end`, from `--audit-key` or `audit.key`.
+static AUDIT_KEY: std::sync::OnceLock<toolbox_core::audit::SigningKey> = std::sync::OnceLock::new();
+/// Whether `run.end` has been written; held while writing it so it is written once.
+static AUDIT_ENDED: std::sync::Mutex<bool> = std::sync::Mutex::new(false);
+
+/// Open the audit log for an active command and install it, with the signing key for `run.end`.
+/// Passive commands and runs without a configured log are not recorded.
+fn setup_audit(cli: &Cli, cfg: Option<&config::AuditConfig>) -> Result<()> {
+    use toolbox_core::audit;
+    let cfg = cfg.cloned().unwrap_or_default();
+    let Some(path) = cli.audit_log.clone().or(cfg.log) else {
+        if cli.audit_key.is_some() { anyhow::bail!("--audit-key needs an audit log (--audit-log or audit.log)"); }
+        return Ok(());
+    };
+    if !cli.command.is_active() { return Ok(()); }
+    if let Some(p) = cli.audit_key.as_ref().or(cfg.key.as_ref()) {
+        let _ = AUDIT_KEY.set(audit::SigningKey::load(p)?);
+    }
+    let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| current_uid());
+    let command = std::env::args().collect::<Vec<_>>().join(" ");
+    audit::install(audit::AuditLog::open(path, &uuid::Uuid::now_v7().to_string(), &user, &command)?)?;
+    Ok(())
+}
+
+/// Write the buffered audit records and `run.end` with `outcome`, once per run.
+fn finish_audit(outcome: &str) {
+    let Some(log) = toolbox_core::audit::global() else { return };
+    let mut ended = AUDIT_ENDED.lock().unwrap_or_else(|e| e.into_inner());
+    if std::mem::replace(&mut *ended, true) { return; }
+    if let Err(e) = log.finish(outcome, AUDIT_KEY.get()) { eprintln!("audit: {}", e); }
+}
+
+#[cfg(unix)]
+fn current_uid() -> String {
+    // SAFETY: getuid has no preconditions.
+    format!("uid {}", unsafe { libc::getuid() })
+}
+
+#[cfg(not(unix))]
+fn current_uid() -> String {
+    "unknown".to_string()
+}
+
+/// Create `path` readable by the owner only.
+fn write_private(path: &std::path::Path, contents: &str) -> Result<()> {
+    use std::io::Write;
+    let mut opts = std::fs::OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut opts, 0o600);
+    opts.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+/// Capabilities of every module built into this binary.
+fn capabilities() -> Vec<toolbox_core::policy::Capability> {
+    #[allow(unused_mut)]
+    let mut caps = Vec::new();
+    #[cfg(feature = "port-scan")]
+    caps.extend_from_slice(port_scan::CAPABILITIES);
+    #[cfg(feature = "host-discovery")]
+    caps.extend_from_slice(host_discovery::CAPABILITIES);
+    #[cfg(feature = "banners")]
+    caps.extend_from_slice(banners::CAPABILITIES);
+    #[cfg(feature = "udp")]
+    caps.extend_from_slice(udp_probe::CAPABILITIES);
+    #[cfg(feature = "webscan")]
+    caps.extend_from_slice(web_surface::CAPABILITIES);
+    #[cfg(feature = "forensics")]
+    caps.extend_from_slice(forensics::CAPABILITIES);
+    #[cfg(feature = "creds")]
+    caps.extend_from_slice(credentials::CAPABILITIES);
+    caps
+}
+
+/// Install the capability policy: safe-active by default, intrusive with `--active` or
+/// `policy.active`, plus capabilities allowed by name.
+fn setup_policy(active: bool, cfg: Option<&config::PolicyConfig>) -> Result<()> {
+    use toolbox_core::policy::{self, Level, Policy};
+    let cfg = cfg.cloned().unwrap_or_default();
+    let allow = cfg.allow.unwrap_or_default();
+    let known = capabilities();
+    for name in &allow {
+        if !known.iter().any(|c| c.name == name) { eprintln!("warning: policy.allow: unknown capability {}", name); }
+    }
+    let max = if active || cfg.active.unwrap_or(false) { Level::Intrusive } else { Level::SafeActive };
+    policy::install(Policy { max, allow })?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    #[cfg(any(feature = "scan", feature = "discover"))]
     let loaded_cfg = config::load_config(cli.config.as_deref());
-    #[cfg(not(any(feature = "scan", feature = "discover")))]
-    let _loaded_cfg: Option<config::Config> = None;
+    setup_dns(&cli.dns, loaded_cfg.as_ref().and_then(|c| c.dns.as_ref()))?;
+    let profile_ports = profile::setup(cli.profile.as_deref(), loaded_cfg.as_ref())?.and_then(|p| p.rules.ports.as_deref());
+    setup_scope(cli.scope.as_deref(), loaded_cfg.as_ref().and_then(|c| c.scope.as_ref()), profile_ports)?;
+    setup_policy(cli.active, loaded_cfg.as_ref().and_then(|c| c.policy.as_ref()))?;
+    setup_proxy(cli.proxy.as_deref(), loaded_cfg.as_ref().and_then(|c| c.proxy.as_deref()))?;
+    setup_source(&cli, loaded_cfg.as_ref().and_then(|c| c.source.as_ref()))?;
+    setup_audit(&cli, loaded_cfg.as_ref().and_then(|c| c.audit.as_ref()))?;
+    let cancel = toolbox_core::cancel::Cancel::new(std::time::Duration::from_millis(cli.drain_ms));
+    setup_profile(&cli, &cancel)?;
+    let result = run(cli, loaded_cfg, &cancel);
+    finish_audit(&match &result {
+        Ok(()) if profile::window_closed() => "stopped: outside profile window".to_string(),
+        Ok(()) if cancel.is_cancelled() => "interrupted".to_string(),
+        Ok(()) => "ok".to_string(),
+        Err(e) => format!("error: {}", e),
+    });
+    let blocked = toolbox_core::scope::blocked();
+    if blocked > 0 { eprintln!("scope: {} attempts blocked", blocked); }
+    if result.is_ok() && profile::window_closed() {
+        eprintln!("stopped at the end of the profile window: results are partial");
+        std::process::exit(3);
+    }
+    if result.is_ok() && cancel.is_cancelled() {
+        eprintln!("interrupted: results are partial");
+        std::process::exit(130);
+    }
+    result
+}
+
+#[cfg_attr(not(any(feature = "scan", feature = "discover", feature = "webscan", feature = "banner", feature = "web", feature = "udp")), allow(unused_variables))]
+fn run(cli: Cli, loaded_cfg: Option<config::Config>, cancel: &toolbox_core::cancel::Cancel) -> Result<()> {
     match cli.command {
+        Commands::Capabilities => {
+            let policy = toolbox_core::policy::global().cloned().unwrap_or_default();
+            for c in capabilities() {
+                let state = if policy.allows(&c) { "allowed" } else { "refused" };
+                println!("{:<22} {:<12} {:<8} {}", c.name, c.level, state, c.summary);
+            }
+        }
+        Commands::Audit { cmd } => match cmd {
+            AuditCmd::Verify { log, key, format } => {
+                let cfg_log = loaded_cfg.as_ref().and_then(|c| c.audit.as_ref()).and_then(|a| a.log.clone());
+                let Some(path) = log.or(cli.audit_log).or(cfg_log) else { anyhow::bail!("no audit log given (--log, --audit-log or audit.log)") };
+                let key = match key {
+                    Some(k) if std::path::Path::new(&k).is_file() => Some(std::fs::read_to_string(&k)?.trim().to_string()),
+                    other => other,
+                };
+                let report = toolbox_core::audit::verify(&path, key.as_deref())?;
+                match format {
+                    OutputFormat::Text => {
+                        println!("{}: {} records, {} runs ({} signed)", path.display(), report.records, report.runs, report.signed);
+                        if let Some(h) = &report.head { println!("head: {}", h); }
+                        if !report.head_file { println!("note: no head file; records cut off the end cannot be detected"); }
+                        for run in &report.open_runs { println!("note: run {} has no run.end (interrupted or cut off)", run); }
+                        for p in &report.problems { println!("FAIL {}", p); }
+                        if report.problems.is_empty() { println!("ok"); }
+                    }
+                    OutputFormat::Json | OutputFormat::Jsonl => {
+                        let obj = serde_json::json!({
+                            "log": path, "ok": report.problems.is_empty(), "records": report.records, "runs": report.runs, "signed": report.signed,
+                            "head": report.head.as_ref().map(|h| serde_json::json!({"seq": h.seq, "hash": h.hash})), "head_file": report.head_file,
+                            "open_runs": report.open_runs, "problems": report.problems,
+                        });
+                        println!("{}", serde_json::to_string(&obj)?);
+                    }
+                }
+                if !report.problems.is_empty() { anyhow::bail!("audit log failed verification"); }
+            }
+            AuditCmd::Keygen { out } => {
+                if out.exists() { anyhow::bail!("{} already exists", out.display()); }
+                let (key, pem) = toolbox_core::audit::SigningKey::generate()?;
+                write_private(&out, &pem)?;
+                println!("{}", key.public_hex());
+            }
+        },
         Commands::Version => {
             println!("toolbox {} (core {})", env!("CARGO_PKG_VERSION"), toolbox_core::version());
         }
@@ -364,6 +1299,7 @@ fn main() -> Result<()> {
                     println!("{}", serde_json::to_string(&obj)?);
                 }
                 CredsCmd::Crack { tool, hashes, wordlist, args } => {
+                    toolbox_core::policy::require(&credentials::CRACK)?;
                     use std::process::{Command, Stdio};
                     use std::io::BufRead;
                     let mut cmd = Command::new(&tool);
@@ -404,22 +1340,61 @@ fn main() -> Result<()> {
         #[cfg(feature = "results")]
         Commands::Results { cmd } => {
             match cmd {
-                ResultsCmd::Import { db, from } => {
+                ResultsCmd::Import { db, from, ptr } => {
                     use results_sqlite as rdb;
                     let dbh = rdb::Db::open_or_create(&db)?;
                     // Build run metadata
                     let run_id = uuid::Uuid::now_v7();
                     let started_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
-                    let meta = rdb::RunMeta { run_id, started_at, tool_version: env!("CARGO_PKG_VERSION").to_string(), args_json: serde_json::to_string(&std::env::args().collect::<Vec<_>>())?, git_sha: None };
+                    let meta = rdb::RunMeta { run_id, started_at, tool_version: env!("CARGO_PKG_VERSION").to_string(), args_json: serde_json::json!({ "argv": std::env::args().collect::<Vec<_>>() }).to_string(), git_sha: None };
                     dbh.begin_run(meta)?;
-                    // naive importer: detects web-scan or scan aggregate lines
+                    // naive importer: detects web-scan, scan aggregate/event and discover lines
+                    let set_ptr = |host_id: rdb::HostId, v: &serde_json::Value| -> Result<()> {
+                        if let Some(name) = v.get("ptr").and_then(|x| x.as_str()) {
+                            dbh.set_host_ptr(host_id, name, v.get("ptr_confirmed").and_then(|x| x.as_bool()).unwrap_or(false))?;
+                        }
+                        Ok(())
+                    };
                     let mut host_set: std::collections::HashSet<String> = std::collections::HashSet::new();
                     let mut err_count = 0i64;
+                    let mut status = "complete".to_string();
                     let s = std::fs::read_to_string(&from)?;
                     for line in s.lines() {
                         if line.trim().is_empty() { continue; }
                         let v: serde_json::Value = match serde_json::from_str(line) { Ok(v) => v, Err(e) => { err_count+=1; continue } };
-                        if v.get("final_url").is_some() {
+                        if v.get("type").and_then(|x| x.as_str()) == Some("run.profile") {
+                            // profile the run was made under
+                            dbh.set_run_args(&run_id, &serde_json::json!({ "argv": std::env::args().collect::<Vec<_>>(), "profile": v.get("profile") }).to_string())?;
+                        } else if v.get("type").and_then(|x| x.as_str()) == Some("run.status") {
+                            // how the run ended (`interrupted` when cut short)
+                            if let Some(st) = v.get("status").and_then(|x| x.as_str()) { status = st.to_string(); }
+                        } else if v.get("type").and_then(|x| x.as_str()) == Some("scan.port") {
+                            // streamed per-port event
+                            let addr = v.get("addr").and_then(|x| x.as_str()).unwrap_or("");
+                            let hostname = v.get("hostname").and_then(|x| x.as_str());
+                            let Some(port) = v.get("port").and_then(|x| x.as_i64()) else { err_count += 1; continue };
+                            let host_id = dbh.upsert_host(&run_id, addr, hostname)?;
+                            let t_first = v.get("t_first").and_then(|x| x.as_i64()).unwrap_or(0);
+                            let spec = rdb::PortSpec {
+                                transport: v.get("transport").and_then(|x| x.as_str()).unwrap_or("tcp").to_string(),
+                                port: port as u16,
+                                state: v.get("state").and_then(|x| x.as_str()).unwrap_or("open").to_string(),
+                                reason: v.get("reason").and_then(|x| x.as_str()).map(|s| s.to_string()),
+                                service_name: None,
+                                confidence: 1.0,
+                                first_seen_ms: t_first,
+                                last_seen_ms: v.get("t_last").and_then(|x| x.as_i64()).unwrap_or(t_first),
+                                rtt_us: v.get("rtt_us").and_then(|x| x.as_i64()),
+                            };
+                            let _ = dbh.upsert_port(host_id, &spec)?;
+                            host_set.insert(addr.to_string());
+                        } else if v.get("type").and_then(|x| x.as_str()) == Some("scan.host") {
+                            // streamed per-address record (PTR names)
+                            let Some(addr) = v.get("addr").and_then(|x| x.as_str()) else { err_count += 1; continue };
+                            let host_id = dbh.upsert_host(&run_id, addr, v.get("hostname").and_then(|x| x.as_str()))?;
+                            set_ptr(host_id, &v)?;
+                            host_set.insert(addr.to_string());
+                        } else if v.get("final_url").is_some() {
                             // web endpoint
                             let target = v.get("target").and_then(|x| x.as_str()).unwrap_or("");
                             let url = v.get("url").and_then(|x| x.as_str()).unwrap_or("");
@@ -433,7 +1408,7 @@ fn main() -> Result<()> {
                             // try derive port from url
                             let transport = "tcp".to_string();
                             let port = if url.starts_with("https://") { 443 } else { 80 };
-                            let p_spec = rdb::PortSpec { transport, port, state: "open".into(), reason: Some("connect".into()), service_name: None, confidence: 1.0, first_seen_ms: started_ms as i64, last_seen_ms: started_ms as i64 };
+                            let p_spec = rdb::PortSpec { transport, port, state: "open".into(), reason: Some("connect".into()), service_name: None, confidence: 1.0, first_seen_ms: started_ms as i64, last_seen_ms: started_ms as i64, rtt_us: None };
                             let port_id = dbh.upsert_port(host_id, &p_spec)?;
                             // endpoint
                             let scheme = if url.starts_with("https://") { "https" } else { "http" } .to_string();
@@ -448,22 +1423,67 @@ fn main() -> Result<()> {
                             dbh.add_http_endpoint(port_id, &http)?;
                             host_set.insert(target.to_string());
                         } else if v.get("open").is_some() {
-                            // aggregated scan result
+                            // aggregated scan result (one per address; `addr` absent in older output)
                             let target = v.get("target").and_then(|x| x.as_str()).unwrap_or("");
-                            let host_id = dbh.upsert_host(&run_id, target, None)?;
-                            if let Some(ports) = v.get("open").and_then(|x| x.as_array()) {
+                            // single-target `--format json` carries how the run ended and its profile itself
+                            if let Some(st) = v.get("status").and_then(|x| x.as_str()) { status = st.to_string(); }
+                            if let Some(profile) = v.get("profile") {
+                                dbh.set_run_args(&run_id, &serde_json::json!({ "argv": std::env::args().collect::<Vec<_>>(), "profile": profile }).to_string())?;
+                            }
+                            let addr = v.get("addr").and_then(|x| x.as_str()).unwrap_or(target);
+                            let hostname = match target.parse::<toolbox_core::Target>().map(|t| t.host) {
+                                Ok(toolbox_core::Host::Name(n)) if addr != target => Some(n),
+                                _ => None,
+                            };
+                            let host_id = dbh.upsert_host(&run_id, addr, hostname.as_deref())?;
+                            set_ptr(host_id, &v)?;
+                            let rtts = v.get("rtt_ms").and_then(|x| x.as_object());
+                            if let Some(ports) = v.get("ports").and_then(|x| x.as_array()) {
+                                // per-port states with reasons
+                                for p in ports {
+                                    let Some(port) = p.get("port").and_then(|x| x.as_i64()) else { err_count += 1; continue };
+                                    let state = p.get("state").and_then(|x| x.as_str()).unwrap_or("open").to_string();
+                                    let reason = p.get("reason").and_then(|x| x.as_str()).map(|s| s.to_string());
+                                    let rtt_us = p.get("rtt_ms").and_then(|x| x.as_f64()).map(|ms| (ms * 1000.0).round() as i64);
+                                    let spec = rdb::PortSpec { transport: "tcp".into(), port: port as u16, state, reason, service_name: None, confidence: 1.0, first_seen_ms: 0, last_seen_ms: 0, rtt_us };
+                                    let _ = dbh.upsert_port(host_id, &spec)?;
+                                }
+                            } else if let Some(ports) = v.get("open").and_then(|x| x.as_array()) {
                                 for p in ports { if let Some(port) = p.as_i64() {
-                                    let spec = rdb::PortSpec { transport: "tcp".into(), port: port as u16, state: "open".into(), reason: Some("connect".into()), service_name: None, confidence: 1.0, first_seen_ms: 0, last_seen_ms: 0 };
+                                    let rtt_us = rtts.and_then(|m| m.get(&port.to_string())).and_then(|x| x.as_f64()).map(|ms| (ms * 1000.0).round() as i64);
+                                    let spec = rdb::PortSpec { transport: "tcp".into(), port: port as u16, state: "open".into(), reason: Some("connect".into()), service_name: None, confidence: 1.0, first_seen_ms: 0, last_seen_ms: 0, rtt_us };
                                     let _ = dbh.upsert_port(host_id, &spec)?;
                                 }}
                             }
-                            host_set.insert(target.to_string());
+                            host_set.insert(addr.to_string());
+                        } else if let Some(addr) = v.get("host").and_then(|x| x.as_str()) {
+                            // discover live host
+                            let host_id = dbh.upsert_host(&run_id, addr, v.get("hostname").and_then(|x| x.as_str()))?;
+                            set_ptr(host_id, &v)?;
+                            if let Some(method) = v.get("method").and_then(|x| x.as_str()) {
+                                let port = v.get("port").and_then(|x| x.as_u64()).map(|p| p as u16);
+                                dbh.set_host_liveness(host_id, method, v.get("reason").and_then(|x| x.as_str()), port)?;
+                            }
+                            if let Some(mac) = v.get("mac").and_then(|x| x.as_str()) {
+                                dbh.set_host_mac(host_id, mac, v.get("vendor").and_then(|x| x.as_str()))?;
+                            }
+                            host_set.insert(addr.to_string());
                         } else {
                             err_count += 1;
                         }
                     }
+                    if ptr {
+                        let missing: Vec<std::net::IpAddr> = dbh.hosts_without_ptr(&run_id)?.iter().filter_map(|a| a.parse().ok()).collect();
+                        let dns = toolbox_core::dns::global();
+                        let ptrs = tokio::runtime::Runtime::new()?.block_on(dns.reverse_all(missing));
+                        for (ip, p) in ptrs {
+                            let host_id = dbh.upsert_host(&run_id, &ip.to_string(), None)?;
+                            dbh.set_host_ptr(host_id, &p.name, p.confirmed)?;
+                        }
+                    }
                     let finished_at = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as i64;
                     dbh.finish_run(&run_id, finished_at, host_set.len() as i64, err_count)?;
+                    dbh.set_run_status(&run_id, &status)?;
                 }
                 ResultsCmd::Export { db, table, format, out } => {
                     use results_sqlite as rdb;
@@ -563,19 +1583,18 @@ fn main() -> Result<()> {
         }
         #[cfg(feature = "webscan")]
         Commands::WebScan { target, targets, ports, timeout_ms, redirects, concurrency, out, csv, no_favicon, strict } => {
-            let targets_list: Vec<String> = if let Some(t) = target {
-                vec![t]
-            } else if let Some(path) = targets {
-                let fh = std::fs::File::open(&path)?;
-                let br = std::io::BufReader::new(fh);
-                use std::io::BufRead;
-                br.lines().filter_map(|l| l.ok()).map(|s| s.trim().to_string()).filter(|s| !s.is_empty() && !s.starts_with('#')).collect()
-            } else { vec![] };
+            let targets_list = target_set(target, targets)?;
             if targets_list.is_empty() { return Err(anyhow::anyhow!("provide a target or --targets <file>")); }
             let ports_vec = modules_port_parse(&ports)?;
-            let opts = web_surface::WebProbeOptions { timeout_ms, redirects, user_agent: format!("toolbox/{}", env!("CARGO_PKG_VERSION")), fetch_favicon: !no_favicon };
+            let opts = web_surface::WebProbeOptions { timeout_ms, redirects, user_agent: format!("toolbox/{}", env!("CARGO_PKG_VERSION")), fetch_favicon: !no_favicon, cancel: cancel.clone(), progress: Default::default() };
+            if let Ok(index) = targets_list.index() { opts.progress.set_total(index.len().saturating_mul(ports_vec.len() as u64)); }
+            let concurrency = profile::cap_concurrency(concurrency);
             let rt = tokio::runtime::Runtime::new()?;
-            let results = rt.block_on(async move { web_surface::probe_many(targets_list, ports_vec, opts, concurrency).await });
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            let reporter = ProgressReporter::start(&rt, cli.progress, cli.progress_interval, "web-scan", None, opts.progress.clone());
+            let results = rt.block_on(async move { web_surface::probe_many(targets_list.expand(), ports_vec, opts, concurrency).await });
+            if let Some(r) = reporter { r.finish(&rt); }
+            let results = results?;
             let failures = results.iter().filter(|r| r.error.is_some()).count();
             if let Some(path) = out.clone() {
                 if csv {
@@ -600,7 +1619,9 @@ fn main() -> Result<()> {
                     }
                     wtr.flush()?;
                 } else {
+                    use std::io::Write;
                     let mut w = std::io::BufWriter::new(std::fs::File::create(&path)?);
+                    if let Some(ev) = profile::event() { writeln!(w, "{}", ev)?; }
                     for r in results {
                         let obj = serde_json::json!({
                             "target": r.target,
@@ -617,11 +1638,13 @@ fn main() -> Result<()> {
                             "favicon_mmh3": r.favicon_mmh3,
                             "error": r.error,
                         });
-                        use std::io::Write;
                         writeln!(w, "{}", serde_json::to_string(&obj)?)?;
                     }
+                    if cancel.is_cancelled() { writeln!(w, "{}", interrupted_event(None))?; }
+                    w.flush()?;
                 }
             } else {
+                if let Some(ev) = profile::event() { println!("{}", ev); }
                 for r in results {
                     let obj = serde_json::json!({
                         "target": r.target,
@@ -640,6 +1663,7 @@ fn main() -> Result<()> {
                     });
                     println!("{}", serde_json::to_string(&obj)?);
                 }
+                if cancel.is_cancelled() { println!("{}", interrupted_event(None)); }
             }
             if strict && failures > 0 {
                 eprintln!("web-scan: {} failures detected", failures);
@@ -647,72 +1671,108 @@ fn main() -> Result<()> {
             }
         }
         #[cfg(feature = "banner")]
-        Commands::Banner { target, port, protocol, follow, cert_full, timeout_ms, format } => {
-            let p = port.unwrap_or_else(|| match protocol.as_deref() { Some("https") => 443, Some("ssh") => 22, _ => 80 });
-            let proto = protocol.unwrap_or_else(|| match p { 443 => "https".into(), 22 => "ssh".into(), _ => "http".into() });
+        Commands::Banner { target, targets, port, protocol, follow, cert_full, timeout_ms, ipv4, ipv6, format } => {
+            let targets_list = target_set(target, targets)?;
+            if targets_list.is_empty() { return Err(anyhow!("provide a target or --targets <file>")); }
+            let default_port = match protocol.as_deref() { Some("https") => 443, Some("ssh") => 22, _ => 80 };
+            let family = toolbox_core::Family::from_flags(ipv4, ipv6);
             let rt = tokio::runtime::Runtime::new()?;
-            let started = Instant::now();
-            let banner = rt.block_on(async {
-                match proto.as_str() {
-                    "https" => if follow { banners::grab_https_follow_one(&target, p, timeout_ms, !cert_full).await } else { banners::grab_https(&target, p, timeout_ms, !cert_full).await },
-                    "ssh" => banners::grab_ssh(&target, p, timeout_ms).await,
-                    _ => if follow { banners::grab_http_follow_one(&target, p, timeout_ms).await } else { banners::grab_http(&target, p, timeout_ms).await },
-                }
-            });
-            let duration_ms = started.elapsed().as_millis();
-            match (format, banner) {
-                (OutputFormat::Text, Ok(b)) => println!("{}:{} {} ({} ms)", target, p, b.summary, duration_ms),
-                (OutputFormat::Json | OutputFormat::Jsonl, Ok(b)) => {
-                    let obj = serde_json::json!({
-                        "target": target,
-                        "port": p,
-                        "protocol": b.protocol,
-                        "summary": b.summary,
-                        "duration_ms": duration_ms,
-                    });
-                    println!("{}", serde_json::to_string(&obj)?);
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            let (mut grabbed, mut failures) = (0, 0);
+            for t in targets_list.expand() {
+                if cancel.is_cancelled() { break; }
+                let p = port.or(t.port).unwrap_or(default_port);
+                let t = toolbox_core::Target { port: Some(p), ..t };
+                let target = t.host.to_string();
+                let started = Instant::now();
+                let Some(grabs) = rt.block_on(cancel.drain(banners::grab_target(&t, p, protocol.as_deref(), family, follow, timeout_ms, !cert_full))) else { break };
+                let duration_ms = started.elapsed().as_millis();
+                let grabs = match grabs {
+                    Ok(grabs) => grabs,
+                    Err(e) => {
+                        grabbed += 1;
+                        failures += 1;
+                        match format {
+                            OutputFormat::Text => println!("{} error: {}", t, e),
+                            OutputFormat::Json | OutputFormat::Jsonl => println!("{}", serde_json::json!({ "target": target, "port": p, "error": e.to_string() })),
+                        }
+                        continue;
+                    }
+                };
+                for (dest, banner) in grabs {
+                    grabbed += 1;
+                    match (format, banner) {
+                        (OutputFormat::Text, Ok(b)) => println!("{} {} ({} ms)", dest_label(&t.host, &dest), b.summary, duration_ms),
+                        (OutputFormat::Json | OutputFormat::Jsonl, Ok(b)) => {
+                            let obj = serde_json::json!({
+                                "target": target,
+                                "port": p,
+                                "protocol": b.protocol,
+                                "summary": b.summary,
+                                "duration_ms": duration_ms,
+                            });
+                            println!("{}", serde_json::to_string(&with_addr(obj, &dest))?);
+                        }
+                        (OutputFormat::Text, Err(e)) => { failures += 1; println!("{} error: {}", dest_label(&t.host, &dest), e); }
+                        (OutputFormat::Json | OutputFormat::Jsonl, Err(e)) => {
+                            failures += 1;
+                            println!("{}", with_addr(serde_json::json!({ "target": target, "port": p, "error": e.to_string() }), &dest));
+                        }
+                    }
                 }
-                (_, Err(e)) => return Err(anyhow!(e.to_string())),
             }
+            if cancel.is_cancelled() && !matches!(format, OutputFormat::Text) { println!("{}", interrupted_event(None)); }
+            if failures > 0 { return Err(anyhow!("{} of {} banner grabs failed", failures, grabbed)); }
         }
         #[cfg(feature = "web")]
-        Commands::Web { target, ports, follow, cert_full, timeout_ms, format } => {
+        Commands::Web { target, targets, ports, follow, cert_full, timeout_ms, ipv4, ipv6, format } => {
             let ports_vec = if let Some(spec) = ports { port_scan::parse_ports(&spec)? } else { vec![80,443] };
+            let targets_list = target_set(target, targets)?;
+            if targets_list.is_empty() { return Err(anyhow::anyhow!("provide a target or --targets <file>")); }
+            let family = toolbox_core::Family::from_flags(ipv4, ipv6);
             let rt = tokio::runtime::Runtime::new()?;
-            let started = Instant::now();
-            let target_for_print = target.clone();
-            let results = rt.block_on(async move {
-                let mut handles = Vec::new();
-                let f = follow;
-                let cn_only = !cert_full;
-                for p in ports_vec.clone() {
-                    let t = target.clone();
-                    handles.push(tokio::spawn(async move {
-                        let res = match p {
-                            443 => if f { banners::grab_https_follow_one(&t, p, timeout_ms, cn_only).await } else { banners::grab_https(&t, p, timeout_ms, cn_only).await },
-                            _ => if f { banners::grab_http_follow_one(&t, p, timeout_ms).await } else { banners::grab_http(&t, p, timeout_ms).await },
-                        };
-                        (p, res)
-                    }));
-                }
-                let mut out = Vec::new();
-                for h in handles { if let Ok(v) = h.await { out.push(v); } }
-                out
-            });
-            let duration_ms = started.elapsed().as_millis();
-            match format {
-                OutputFormat::Text => {
-                    for (p, res) in results {
-                        match res {
-                            Ok(b) => println!("{}:{} {} ({} ms)", target_for_print, p, b.summary, duration_ms),
-                            Err(e) => println!("{}:{} error: {}", target_for_print, p, e),
-                        }
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            for target in targets_list.expand() {
+                if cancel.is_cancelled() { break; }
+                let started = Instant::now();
+                let host = target.host.clone();
+                let target_for_print = host.to_string();
+                let ports_vec = ports_vec.clone();
+                let cancel = cancel.clone();
+                let results = rt.block_on(async move {
+                    let mut handles = Vec::new();
+                    let f = follow;
+                    let cn_only = !cert_full;
+                    for p in target.ports_or(&ports_vec) {
+                        let t = target.clone();
+                        let cancel = cancel.clone();
+                        handles.push(tokio::spawn(async move {
+                            let proto = if p == 443 { "https" } else { "http" };
+                            let res = cancel.drain(banners::grab_target(&t, p, Some(proto), family, f, timeout_ms, cn_only)).await;
+                            res.map(|res| (p, res))
+                        }));
                     }
-                }
-                OutputFormat::Json | OutputFormat::Jsonl => {
-                    for (p, res) in results {
-                        match res {
-                            Ok(b) => {
+                    let mut out = Vec::new();
+                    for h in handles { if let Ok(Some(v)) = h.await { out.push(v); } }
+                    out
+                });
+                let duration_ms = started.elapsed().as_millis();
+                for (p, res) in results {
+                    let grabs = match res {
+                        Ok(grabs) => grabs,
+                        Err(e) => {
+                            match format {
+                                OutputFormat::Text => println!("{} error: {}", toolbox_core::Target { host: host.clone(), port: Some(p), url: None }, e),
+                                OutputFormat::Json | OutputFormat::Jsonl => println!("{}", serde_json::json!({ "target": target_for_print, "port": p, "error": e.to_string() })),
+                            }
+                            continue;
+                        }
+                    };
+                    for (dest, res) in grabs {
+                        match (format, res) {
+                            (OutputFormat::Text, Ok(b)) => println!("{} {} ({} ms)", dest_label(&host, &dest), b.summary, duration_ms),
+                            (OutputFormat::Text, Err(e)) => println!("{} error: {}", dest_label(&host, &dest), e),
+                            (OutputFormat::Json | OutputFormat::Jsonl, Ok(b)) => {
                                 let obj = serde_json::json!({
                                     "target": target_for_print,
                                     "port": p,
@@ -720,130 +1780,255 @@ fn main() -> Result<()> {
                                     "summary": b.summary,
                                     "duration_ms": duration_ms,
                                 });
-                                println!("{}", serde_json::to_string(&obj)?);
+                                println!("{}", serde_json::to_string(&with_addr(obj, &dest))?);
                             }
-                            Err(e) => {
+                            (OutputFormat::Json | OutputFormat::Jsonl, Err(e)) => {
                                 let obj = serde_json::json!({
                                     "target": target_for_print,
                                     "port": p,
                                     "error": e.to_string(),
                                 });
-                                println!("{}", serde_json::to_string(&obj)?);
+                                println!("{}", serde_json::to_string(&with_addr(obj, &dest))?);
                             }
                         }
                     }
                 }
             }
+            if cancel.is_cancelled() && !matches!(format, OutputFormat::Text) { println!("{}", interrupted_event(None)); }
         }
         #[cfg(feature = "udp")]
-        Commands::UdpProbe { target, service, community, timeout_ms, format } => {
+        Commands::UdpProbe { target, targets, service, community, timeout_ms, ipv4, ipv6, format } => {
+            let targets_list = target_set(target, targets)?;
+            if targets_list.is_empty() { return Err(anyhow!("provide a target or --targets <file>")); }
+            let family = toolbox_core::Family::from_flags(ipv4, ipv6);
             let rt = tokio::runtime::Runtime::new()?;
-            let started = Instant::now();
-            let target_c = target.clone();
-            let service_for_task = service.clone();
-            let res = rt.block_on(async move {
-                match service_for_task.as_str() {
-                    "dns" => udp_probe::probe_dns(&target_c, timeout_ms).await,
-                    "ntp" => udp_probe::probe_ntp(&target_c, timeout_ms).await,
-                    _ => udp_probe::probe_snmp_sysdescr(&target_c, &community, timeout_ms).await,
-                }
-            });
-            let duration_ms = started.elapsed().as_millis();
-            match res {
-                Ok(Some(info)) => match format {
-                    OutputFormat::Text => println!("{} {} ok ({}, {} ms)", target, service, info, duration_ms),
-                    OutputFormat::Json | OutputFormat::Jsonl => {
-                        let obj = serde_json::json!({ "target": target, "service": service, "status": "ok", "info": info, "duration_ms": duration_ms });
-                        println!("{}", serde_json::to_string(&obj)?);
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            let (mut probed, mut failures) = (0, 0);
+            for target_c in targets_list.expand() {
+                if cancel.is_cancelled() { break; }
+                let target = target_c.to_string();
+                let started = Instant::now();
+                let probe = async {
+                    match service.as_str() {
+                        "dns" => udp_probe::probe_dns(&target_c, family, timeout_ms).await,
+                        "ntp" => udp_probe::probe_ntp(&target_c, family, timeout_ms).await,
+                        _ => udp_probe::probe_snmp_sysdescr(&target_c, family, &community, timeout_ms).await,
                     }
-                },
-                Ok(None) => match format {
-                    OutputFormat::Text => println!("{} {} no-response ({} ms)", target, service, duration_ms),
-                    OutputFormat::Json | OutputFormat::Jsonl => {
-                        let obj = serde_json::json!({ "target": target, "service": service, "status": "no-response", "duration_ms": duration_ms });
-                        println!("{}", serde_json::to_string(&obj)?);
+                };
+                let Some(res) = rt.block_on(cancel.drain(probe)) else { break };
+                let duration_ms = started.elapsed().as_millis();
+                let replies = match res {
+                    Ok(replies) => replies,
+                    // A policy refusal applies to every target alike.
+                    Err(e) if e.is::<toolbox_core::policy::Refused>() => return Err(e),
+                    Err(e) => {
+                        probed += 1;
+                        failures += 1;
+                        match format {
+                            OutputFormat::Text => println!("{} {} error: {}", target, service, e),
+                            OutputFormat::Json | OutputFormat::Jsonl => {
+                                let obj = serde_json::json!({ "target": target, "service": service, "status": "error", "error": e.to_string() });
+                                println!("{}", serde_json::to_string(&obj)?);
+                            }
+                        }
+                        continue;
                     }
-                },
-                Err(e) => return Err(anyhow!(e.to_string())),
+                };
+                for (addr, res) in replies {
+                    let dest = toolbox_core::connect::Dest::from(addr);
+                    let label = dest_label(&target_c.host, &dest);
+                    probed += 1;
+                    if res.is_err() { failures += 1; }
+                    let obj = match (format, res) {
+                        (OutputFormat::Text, Ok(Some(info))) => { println!("{} {} ok ({}, {} ms)", label, service, info, duration_ms); continue; }
+                        (OutputFormat::Text, Ok(None)) => { println!("{} {} no-response ({} ms)", label, service, duration_ms); continue; }
+                        (OutputFormat::Text, Err(e)) => { println!("{} {} error: {}", label, service, e); continue; }
+                        (OutputFormat::Json | OutputFormat::Jsonl, Ok(Some(info))) => serde_json::json!({ "target": target, "service": service, "status": "ok", "info": info, "duration_ms": duration_ms }),
+                        (OutputFormat::Json | OutputFormat::Jsonl, Ok(None)) => serde_json::json!({ "target": target, "service": service, "status": "no-response", "duration_ms": duration_ms }),
+                        (OutputFormat::Json | OutputFormat::Jsonl, Err(e)) => serde_json::json!({ "target": target, "service": service, "status": "error", "error": e.to_string() }),
+                    };
+                    println!("{}", serde_json::to_string(&with_addr(obj, &dest))?);
+                }
             }
+            if cancel.is_cancelled() && !matches!(format, OutputFormat::Text) { println!("{}", interrupted_event(None)); }
+            if failures > 0 { return Err(anyhow!("{} of {} {} probes failed", failures, probed, service)); }
         }
         #[cfg(feature = "scan")]
-        Commands::Scan { target, targets, mut ports, mut top, mut timeout_ms, mut concurrency, mut qps, mut retries, mut retry_delay_ms, mut host_concurrency, max_connections, mut format, out, csv, mut dns_retries, mut dns_retry_delay_ms } => {
+        Commands::Scan { mut target, mut targets, mut ports, mut top, mut timeout_ms, mut min_rtt_timeout_ms, mut max_rtt_timeout_ms, mut concurrency, mut qps, mut burst, mut adaptive, mut min_qps, mut max_qps, mut retries, mut retry_delay_ms, mut host_concurrency, mut max_connections, mut mode, mut ipv4, mut ipv6, mut randomize, mut seed, mut format, mut out, csv, checkpoint, checkpoint_interval, resume, mut dns_retries, mut dns_retry_delay_ms, mut ptr } => {
             if let Some(cfg) = &loaded_cfg { if let Some(s) = &cfg.scan {
                 if ports.is_none() { ports = s.ports.clone(); }
                 if top.is_none() { top = s.top; }
                 if s.timeout_ms.is_some() { timeout_ms = s.timeout_ms.unwrap(); }
+                if let Some(v) = s.min_rtt_timeout_ms { min_rtt_timeout_ms = v; }
+                if let Some(v) = s.max_rtt_timeout_ms { max_rtt_timeout_ms = v; }
                 if s.concurrency.is_some() { concurrency = s.concurrency.unwrap(); }
                 if s.host_concurrency.is_some() { host_concurrency = s.host_concurrency.unwrap(); }
                 if s.qps.is_some() { qps = s.qps.unwrap(); }
+                if burst.is_none() { burst = s.burst; }
+                if let Some(a) = s.adaptive { adaptive = adaptive || a; }
+                if min_qps.is_none() { min_qps = s.min_qps; }
+                if max_qps.is_none() { max_qps = s.max_qps; }
                 if s.retries.is_some() { retries = s.retries.unwrap(); }
                 if s.retry_delay_ms.is_some() { retry_delay_ms = s.retry_delay_ms.unwrap(); }
+                if let Some(m) = &s.mode { if m == "syn" { mode = ScanModeArg::Syn; } }
+                if let Some(r) = s.randomize { randomize = randomize || r; }
+                if seed.is_none() { seed = s.seed; }
+                if let Some(p) = s.ptr { ptr = ptr || p; }
                 if let Some(f) = &s.format { format = match f.as_str() { "json" => OutputFormat::Json, "jsonl" => OutputFormat::Jsonl, _ => OutputFormat::Text }; }
             }}
-            let ports_vec = match (ports, top) {
-                (Some(spec), _) => port_scan::parse_ports(&spec)?,
-                (None, Some(n)) => {
+            // A resumed scan runs with exactly the options it was started with
+            let resumed = match &resume { Some(p) => Some(checkpoint::load(p)?), None => None };
+            if let Some(c) = &resumed {
+                if c.complete { eprintln!("scan {} already complete", c.run_id); return Ok(()); }
+                let a = c.args.clone();
+                (target, targets, ports, top) = (a.target, a.targets, None, None);
+                (timeout_ms, min_rtt_timeout_ms, max_rtt_timeout_ms) = (a.timeout_ms, a.min_rtt_timeout_ms, a.max_rtt_timeout_ms);
+                (concurrency, host_concurrency, max_connections) = (a.concurrency, a.host_concurrency, a.max_connections);
+                (qps, burst, adaptive, min_qps, max_qps) = (a.qps, a.burst, a.adaptive, a.min_qps, a.max_qps);
+                (retries, retry_delay_ms, dns_retries, dns_retry_delay_ms) = (a.retries, a.retry_delay_ms, a.dns_retries, a.dns_retry_delay_ms);
+                (mode, randomize, seed, format, out) = (a.mode, false, a.seed, a.format, a.out);
+                (ipv4, ipv6, ptr) = (a.ipv4, a.ipv6, a.ptr);
+            }
+            let ports_vec = match (ports, top, &resumed) {
+                (_, _, Some(c)) => c.args.ports.clone(),
+                (Some(spec), _, _) => port_scan::parse_ports(&spec)?,
+                (None, Some(n), _) => {
                     if n == 0 { return Err(anyhow!("--top must be > 0")); }
                     port_scan::top_ports(n)
                 }
                 _ => port_scan::default_top_ports(),
             };
+            if adaptive && qps == 0 { qps = 1000; }
+            // Profile limits apply over flags, config and checkpoint alike
+            if let Some(p) = profile::active() {
+                qps = profile::cap_qps(qps);
+                (min_qps, max_qps) = (min_qps.map(profile::cap_qps), max_qps.map(profile::cap_qps).or(p.rules.max_qps));
+                concurrency = profile::cap_concurrency(concurrency);
+                max_connections = max_connections.or(p.rules.max_concurrency).map(profile::cap_concurrency);
+            }
+            let rtt_bounds = port_scan::RttBounds {
+                initial: std::time::Duration::from_millis(timeout_ms),
+                min: std::time::Duration::from_millis(min_rtt_timeout_ms.min(max_rtt_timeout_ms)),
+                max: std::time::Duration::from_millis(max_rtt_timeout_ms),
+            };
+            let burst_arg = burst;
+            let burst = burst.unwrap_or_else(|| toolbox_core::ratelimiter::default_burst(qps as f64));
+            let args_for_checkpoint = (target.clone(), targets.clone());
+            let target_specs = target_set(target, targets)?;
+            if target_specs.is_empty() { return Err(anyhow!("provide a target or --targets <file>")); }
             let rt = tokio::runtime::Runtime::new()?;
+            // Global QPS token bucket (shared across all hosts), optionally driven by AIMD
+            let global_qps = if qps == 0 { None } else { Some(std::sync::Arc::new(toolbox_core::ratelimiter::RateLimiter::with_burst(qps as f64, burst))) };
+            if adaptive {
+                if max_qps == Some(0) { return Err(anyhow!("--max-qps must be > 0")); }
+                if let (Some(lo), Some(hi)) = (min_qps, max_qps) {
+                    if lo > hi { return Err(anyhow!("--min-qps {} is above --max-qps {}", lo, hi)); }
+                }
+            }
+            let aimd = if adaptive {
+                let mut cfg = toolbox_core::congestion::AimdConfig::around(qps as f64, concurrency);
+                if let Some(m) = min_qps { cfg.min_rate = m as f64; }
+                if let Some(m) = max_qps { cfg.max_rate = m as f64; }
+                cfg.max_window = profile::cap_concurrency(cfg.max_window);
+                Some(toolbox_core::congestion::Aimd::new(cfg, qps as f64, concurrency, global_qps.as_deref().cloned()))
+            } else { None };
+            let scan_mode = match mode {
+                ScanModeArg::Connect => port_scan::ScanMode::Connect,
+                ScanModeArg::Syn if !toolbox_core::connect::global().is_direct() => {
+                    eprintln!("warning: SYN scan cannot go through a proxy; falling back to connect scan");
+                    port_scan::ScanMode::Connect
+                }
+                ScanModeArg::Syn => match port_scan::SynScanner::new() {
+                    Ok(s) => port_scan::ScanMode::Syn(std::sync::Arc::new(s)),
+                    Err(e) => {
+                        let why = if e.kind() == std::io::ErrorKind::PermissionDenied { "raw sockets need CAP_NET_RAW".to_string() } else { e.to_string() };
+                        eprintln!("warning: SYN scan unavailable ({}); falling back to connect scan", why);
+                        port_scan::ScanMode::Connect
+                    }
+                },
+            };
+            let scan_opts = port_scan::ScanOptions {
+                timeouts: rtt_bounds,
+                per_host_concurrency: concurrency,
+                dns_retries,
+                dns_retry_delay: std::time::Duration::from_millis(dns_retry_delay_ms),
+                global_qps,
+                retries,
+                retry_delay: std::time::Duration::from_millis(retry_delay_ms),
+                global_limit: None,
+                adaptive: aimd.clone(),
+                workers: aimd.as_ref().map(|a| a.max_window()).unwrap_or(concurrency),
+                max_hosts: 1,
+                mode: scan_mode,
+                family: toolbox_core::Family::from_flags(ipv4, ipv6),
+                resume: None,
+                cancel: cancel.clone(),
+                progress: Default::default(),
+            };
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            // JSONL streams scan.port events as they are found instead of one line per target
+            let stream_events = matches!(format, OutputFormat::Jsonl) && !csv;
+
+            let seed = if randomize || seed.is_some() {
+                Some(seed.unwrap_or_else(|| {
+                    let s = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+                    eprintln!("scan seed: {}", s);
+                    s
+                }))
+            } else { None };
+
+            let checkpoint_path = checkpoint.or(resume);
 
-            // Single target mode
-            if let Some(target) = target {
-                let target_for_scan = target.clone();
+            // Single target mode (one address; names may have several and go through the pool)
+            let single = target_specs.expand().next().filter(|t| {
+                target_specs.is_single() && !stream_events && seed.is_none() && checkpoint_path.is_none() && !ptr
+                    && t.host.ip().is_some_and(|ip| scan_opts.family.allows(&ip))
+            });
+            if let Some(target_for_scan) = single {
+                let target = target_for_scan.to_string();
+                let ports_vec = target_for_scan.ports_or(&ports_vec);
                 let ports_for_scan = ports_vec.clone();
                 let start = Instant::now();
                 let started_at = now_rfc3339();
-                let open = rt.block_on(async move {
-                    let global_qps = if qps == 0 { None } else { Some(std::sync::Arc::new(toolbox_core::ratelimiter::RateLimiter::new(qps))) };
-                    port_scan::scan_connect_with_limits(
-                        &target_for_scan,
-                        &ports_for_scan,
-                        std::time::Duration::from_millis(timeout_ms),
-                        concurrency,
-                        dns_retries,
-                        std::time::Duration::from_millis(dns_retry_delay_ms),
-                        global_qps,
-                        retries,
-                        std::time::Duration::from_millis(retry_delay_ms),
-                        None,
-                    ).await
-                });
+                scan_opts.progress.set_total(ports_for_scan.len() as u64);
+                let reporter = ProgressReporter::start(&rt, cli.progress, cli.progress_interval, "scan", None, scan_opts.progress.clone());
+                let results = rt.block_on(scan_streaming(&target_for_scan, &ports_for_scan, &scan_opts, |_| {}));
+                if let Some(r) = reporter { r.finish(&rt); }
                 let duration_ms = start.elapsed().as_millis();
                 let ended_at = now_rfc3339();
                 if csv {
                     if let Some(path) = out {
                         let mut wtr = csv::Writer::from_writer(std::fs::File::create(&path)?);
-                        wtr.write_record(["target","port","started_at","ended_at","duration_ms"]) ?;
-                        for p in open { wtr.write_record([&target, &p.to_string(), &started_at, &ended_at, &duration_ms.to_string()])?; }
+                        wtr.write_record(["target","port","state","reason","rtt_ms","started_at","ended_at","duration_ms"]) ?;
+                        for r in &results {
+                            let rtt = r.rtt.map(|d| format!("{:.3}", d.as_secs_f64() * 1000.0)).unwrap_or_default();
+                            wtr.write_record([&target, &r.port.to_string(), r.state.as_str(), r.reason.as_str(), &rtt, &started_at, &ended_at, &duration_ms.to_string()])?;
+                        }
                         wtr.flush()?;
+                        if let Some(a) = &aimd { print_adaptive_summary(&a.summary(), format); }
                         return Ok(());
                     } else {
                         println!("--csv requires --out <file>");
                     }
                 }
                 let line = match format {
-                    OutputFormat::Text => {
-                        if open.is_empty() {
-                            format!("{}: no open ports found ({} scanned)", target, ports_vec.len())
-                        } else {
-                            let list = open.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
-                            format!("{}: open ports [{}] ({} scanned, {} ms)", target, list, ports_vec.len(), duration_ms)
-                        }
-                    }
+                    OutputFormat::Text => scan_text_line(&target, &results, (0, 0), duration_ms),
                     OutputFormat::Json | OutputFormat::Jsonl => {
-                        let obj = serde_json::json!({
+                        let mut obj = serde_json::json!({
                             "target": target,
                             "scanned": ports_vec.len(),
-                            "open": open,
+                            "open": results.iter().filter(|r| r.is_open()).map(|r| r.port).collect::<Vec<_>>(),
+                            "ports": ports_json(&results),
+                            "rtt_ms": rtt_ms_json(&results),
                             "timeout_ms": timeout_ms,
                             "concurrency": concurrency,
                             "duration_ms": duration_ms,
                             "started_at": started_at,
                             "ended_at": ended_at,
                         });
+                        if let Some(a) = &aimd { obj["adaptive"] = adaptive_summary_json(&a.summary()); }
+                        if let Some(p) = profile::active() { obj["profile"] = p.to_json(); }
+                        if cancel.is_cancelled() { obj["status"] = serde_json::json!("interrupted"); }
                         serde_json::to_string(&obj)?
                     }
                 };
@@ -854,152 +2039,310 @@ fn main() -> Result<()> {
                 } else {
                     println!("{}", line);
                 }
+                if let (Some(a), OutputFormat::Text) = (&aimd, format) { print_adaptive_summary(&a.summary(), format); }
                 return Ok(());
             }
 
-            // Multi-target mode: concurrent hosts with global connection limit; outputs one line per target
-            if let Some(file) = targets {
-                let fh = File::open(&file)?;
-                let br = BufReader::new(fh);
-                let mut targets_vec = Vec::new();
-                for line in br.lines() {
-                    let line = line?;
-                    let t = line.trim();
-                    if t.is_empty() || t.starts_with('#') { continue; }
-                    targets_vec.push(t.to_string());
-                }
+            // Multi-target mode: fixed worker pool over lazily expanded targets; outputs one line per
+            // target, or one event per port when streaming
+            {
+                let targets_total = target_specs.index().ok().map(|i| i.len());
+                let targets_hash = target_specs.fingerprint();
+                if let Some(c) = &resumed { c.check_targets(targets_total, &targets_hash)?; }
 
-                // Prepare writer (stdout or file)
+                // Prepare writer (stdout or file); a resumed scan appends to its earlier output
                 let mut writer_file = if let Some(path) = out.clone() {
-                    Some(BufWriter::new(OpenOptions::new().create(true).truncate(true).write(true).open(&path)?))
+                    let mut o = OpenOptions::new();
+                    if resumed.is_some() { o.create(true).append(true); } else { o.create(true).truncate(true).write(true); }
+                    Some(BufWriter::new(o.open(&path)?))
                 } else { None };
 
                 let total_connections = max_connections.unwrap_or_else(|| concurrency.saturating_mul(host_concurrency.max(1)));
-                let total_connections = total_connections.max(1);
-                let host_conc = host_concurrency.max(1);
-                let timeout = std::time::Duration::from_millis(timeout_ms);
-                let dns_delay = std::time::Duration::from_millis(dns_retry_delay_ms);
-                // Global QPS token bucket (shared across all hosts)
-                let global_qps = if qps == 0 { None } else { Some(std::sync::Arc::new(toolbox_core::ratelimiter::RateLimiter::new(qps))) };
-
-                // Channel for lines
-                let (tx, rx) = mpsc::unbounded_channel::<String>();
-                // Writer thread to serialize output
+                let mut scan_opts = port_scan::ScanOptions { workers: total_connections.max(1), max_hosts: host_concurrency.max(1), ..scan_opts };
+                let run_id = resumed.as_ref().map(|c| c.run_id.clone()).unwrap_or_else(|| uuid::Uuid::now_v7().to_string());
+
+                // Progress so far: finished targets, or permutation positions when randomized
+                let mut done = port_scan::Progress::default();
+                let mut pending: std::collections::HashMap<(usize, String), HostResults> = std::collections::HashMap::new();
+                if let Some(c) = &resumed {
+                    done = port_scan::Progress::from_parts(c.done_below, c.done_above.iter().copied());
+                    for p in &c.partial {
+                        let r = HostResults::from_partial(p)?;
+                        pending.insert((p.host, r.addr.clone()), r);
+                    }
+                    let mut partial: std::collections::HashMap<usize, Vec<u16>> = Default::default();
+                    if seed.is_none() {
+                        for ((h, _), r) in &pending { partial.entry(*h).or_default().extend(r.results.iter().map(|r| r.port)); }
+                    }
+                    scan_opts.resume = Some(std::sync::Arc::new(port_scan::Resume { done: done.clone(), partial }));
+                    eprintln!("resuming scan {} ({} {} done)", run_id, done.count(), if seed.is_some() { "probes" } else { "targets" });
+                }
+                let checkpoint_base = checkpoint_path.as_ref().map(|_| {
+                    let (target, targets) = args_for_checkpoint;
+                    checkpoint::ScanCheckpoint::new(run_id.clone(), checkpoint::ScanArgs {
+                        target, targets, ports: ports_vec.clone(), timeout_ms, min_rtt_timeout_ms, max_rtt_timeout_ms, concurrency, qps, burst: burst_arg,
+                        adaptive, min_qps, max_qps, retries, retry_delay_ms, host_concurrency, max_connections, mode, seed, format, out: out.clone(),
+                        dns_retries, dns_retry_delay_ms, ipv4, ipv6, ptr,
+                    }, targets_total, targets_hash)
+                });
+                // Partial results are kept for summaries, and for checkpoints of sequential scans
+                let keep_partial = !stream_events || (checkpoint_base.is_some() && seed.is_none());
+
+                // Bounded channel for lines so a slow writer pushes back on the scanner
+                let (tx, rx) = mpsc::channel::<ScanOutput>(1024);
+                // Writer thread to serialize output and save checkpoints behind it
+                let writer_checkpoint = checkpoint_path.clone();
                 let writer_handle = std::thread::spawn(move || {
                     let mut rx = rx;
-                    while let Some(line) = rx.blocking_recv() {
-                        if let Some(wf) = writer_file.as_mut() {
-                            let _ = writeln!(wf, "{}", line);
-                            let _ = wf.flush();
-                        } else {
-                            println!("{}", line);
+                    while let Some(msg) = rx.blocking_recv() {
+                        match msg {
+                            ScanOutput::Line(line) => {
+                                if let Some(wf) = writer_file.as_mut() {
+                                    let _ = writeln!(wf, "{}", line);
+                                    let _ = wf.flush();
+                                } else {
+                                    println!("{}", line);
+                                }
+                            }
+                            ScanOutput::Checkpoint(c) => {
+                                let Some(path) = &writer_checkpoint else { continue };
+                                if let Err(e) = checkpoint::save(path, &c) { eprintln!("warning: writing checkpoint {}: {}", path.display(), e); }
+                            }
                         }
                     }
                 });
+                if let (false, Some(ev)) = (matches!(format, OutputFormat::Text), profile::event()) {
+                    let _ = tx.blocking_send(ScanOutput::Line(ev.to_string()));
+                }
+                if let Some(n) = targets_total { scan_opts.progress.set_total(n.saturating_mul(ports_vec.len() as u64)); }
+                let reporter = ProgressReporter::start(&rt, cli.progress, cli.progress_interval, "scan", Some(run_id.clone()), scan_opts.progress.clone());
 
                 rt.block_on(async move {
-                    let global = std::sync::Arc::new(tokio::sync::Semaphore::new(total_connections));
-                    let host_sem = std::sync::Arc::new(tokio::sync::Semaphore::new(host_conc));
-                    let global_qps_shared = global_qps.clone();
-                    let mut handles = Vec::with_capacity(targets_vec.len());
-                    for t in targets_vec {
-                        let host_sem_p = host_sem.clone().acquire_owned().await.unwrap();
-                        let txc = tx.clone();
-                        let ports_for_scan = ports_vec.clone();
-                        let global_c = global.clone();
-                        let gq = global_qps_shared.clone();
-                        let target_s = t.clone();
-                        let h = tokio::spawn(async move {
-                            let start = Instant::now();
-                            let open = port_scan::scan_connect_with_limits(
-                                &target_s,
-                                &ports_for_scan,
-                                timeout,
-                                concurrency,
-                                dns_retries,
-                                dns_delay,
-                                gq,
-                                retries,
-                                std::time::Duration::from_millis(retry_delay_ms),
-                                Some(global_c),
-                            ).await;
-                            let duration_ms = start.elapsed().as_millis();
-                            let line = match format {
-                                OutputFormat::Text => {
-                                    if open.is_empty() {
-                                        format!("{}: no open ports found ({} scanned)", target_s, ports_for_scan.len())
-                                    } else {
-                                        let list = open.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
-                                        format!("{}: open ports [{}] ({} scanned, {} ms)", target_s, list, ports_for_scan.len(), duration_ms)
+                    let (etx, mut erx) = mpsc::channel::<port_scan::ScanEvent>(1024);
+                    let snapshot = |done: &port_scan::Progress, pending: &std::collections::HashMap<(usize, String), HostResults>, complete: bool| {
+                        checkpoint_base.as_ref().map(|base| {
+                            let mut c = base.clone();
+                            c.updated_at = now_rfc3339();
+                            c.complete = complete;
+                            (c.done_below, c.done_above) = (done.watermark(), done.above().collect());
+                            c.partial = pending.iter().map(|((h, _), r)| r.to_partial(*h)).collect();
+                            c.partial.sort_unstable_by_key(|p| p.host);
+                            ScanOutput::Checkpoint(Box::new(c))
+                        })
+                    };
+                    let scan = async {
+                        match seed {
+                            Some(seed) => port_scan::scan_permuted(&target_specs, &ports_vec, seed, &scan_opts, etx).await,
+                            None => { port_scan::scan_many(target_specs.expand(), &ports_vec, &scan_opts, etx).await; Ok(()) }
+                        }
+                    };
+                    let consume = async {
+                        // `pending` holds results of target addresses not yet done (at most --host-concurrency
+                        // targets, or all touched ones when randomized, which are summarized at the end and
+                        // so keep only their open ports)
+                        let mut unresolved = std::collections::HashSet::new();
+                        let mut warn_unresolved = |host: usize, target: &toolbox_core::Target| {
+                            if unresolved.insert(host) { eprintln!("warning: skipping {}: no {}address{}", target, match scan_opts.family { toolbox_core::Family::V4 => "IPv4 ", toolbox_core::Family::V6 => "IPv6 ", toolbox_core::Family::Any => "" }, if toolbox_core::scope::global().is_some() { " in scope" } else { "" }); }
+                        };
+                        let dns = toolbox_core::dns::global();
+                        let mut ptr_started = std::collections::HashSet::new();
+                        let mut tick = tokio::time::interval(std::time::Duration::from_secs(checkpoint_interval.max(1)));
+                        tick.tick().await;
+                        loop {
+                            let ev = tokio::select! {
+                                ev = erx.recv() => match ev { Some(ev) => ev, None => break },
+                                _ = tick.tick(), if checkpoint_base.is_some() => {
+                                    if let Some(c) = snapshot(&done, &pending, false) { if tx.send(c).await.is_err() { break; } }
+                                    continue;
+                                }
+                            };
+                            let lines = match ev {
+                                port_scan::ScanEvent::Port(p) => {
+                                    // Start the address's PTR lookup now so it is cached by the time its line is written
+                                    if ptr && ptr_started.insert(p.addr.clone()) {
+                                        if let Ok(ip) = p.addr.parse() { let dns = dns.clone(); tokio::spawn(async move { dns.reverse(ip).await }); }
                                     }
+                                    if keep_partial { pending.entry((p.host, p.addr.clone())).or_insert_with(|| HostResults::new(&p)).push(&p, seed.is_some()); }
+                                    if !stream_events { continue; }
+                                    vec![port_event_json(&run_id, &p).to_string()]
+                                }
+                                port_scan::ScanEvent::NoAddress { host, target, seq } => {
+                                    done.insert(seq);
+                                    warn_unresolved(host, &target);
+                                    continue;
+                                }
+                                port_scan::ScanEvent::Position { seq } => {
+                                    done.insert(seq);
+                                    continue;
                                 }
-                                OutputFormat::Json | OutputFormat::Jsonl => {
-                                    let obj = serde_json::json!({
-                                        "target": target_s,
-                                        "scanned": ports_for_scan.len(),
-                                        "open": open,
-                                        "timeout_ms": timeout_ms,
-                                        "concurrency": concurrency,
-                                        "duration_ms": duration_ms,
-                                    });
-                                    serde_json::to_string(&obj).unwrap()
+                                port_scan::ScanEvent::HostDone(h) => {
+                                    done.insert(h.host as u64);
+                                    if h.addrs.is_empty() { warn_unresolved(h.host, &h.target); }
+                                    // This run's addresses first, then any only the checkpoint knew
+                                    let mut keys: Vec<_> = h.addrs.iter().map(|ip| (h.host, ip.to_string())).collect();
+                                    keys.extend(pending.keys().filter(|(host, addr)| *host == h.host && !h.addrs.iter().any(|ip| ip.to_string() == *addr)).cloned().collect::<Vec<_>>());
+                                    let mut results: Vec<_> = keys.iter().filter_map(|k| pending.remove(k)).collect();
+                                    if stream_events {
+                                        if !ptr { continue; }
+                                        let mut lines = Vec::new();
+                                        for ip in h.addrs.iter() { lines.push(host_event_json(&run_id, ip, dns.hostname(ip), dns.reverse(*ip).await.as_ref()).to_string()); }
+                                        lines
+                                    } else {
+                                        if ptr { for r in &mut results { r.lookup_ptr(&dns).await; } }
+                                        results.into_iter().map(|r| r.line(&h.target.to_string(), h.elapsed.as_millis(), format, timeout_ms, concurrency)).collect()
+                                    }
                                 }
                             };
-                            let _ = txc.send(line);
-                            drop(host_sem_p);
-                        });
-                        handles.push(h);
+                            for line in lines {
+                                if tx.send(ScanOutput::Line(line)).await.is_err() { break; }
+                            }
+                        }
+                        // Interrupted: keep unfinished targets in the checkpoint, then write what they have
+                        if scan_opts.cancel.is_cancelled() {
+                            if let Some(c) = snapshot(&done, &pending, false) { let _ = tx.send(c).await; }
+                        }
+                        let mut rest: Vec<_> = pending.into_iter().collect();
+                        rest.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                        for (_, mut r) in rest {
+                            if stream_events { break; }
+                            if ptr { r.lookup_ptr(&dns).await; }
+                            let (label, duration_ms) = (r.label.clone(), r.span_ms());
+                            if tx.send(ScanOutput::Line(r.line(&label, duration_ms, format, timeout_ms, concurrency))).await.is_err() { break; }
+                        }
+                        if scan_opts.cancel.is_cancelled() && !matches!(format, OutputFormat::Text) {
+                            let _ = tx.send(ScanOutput::Line(interrupted_event(Some(&run_id)).to_string())).await;
+                        }
+                        done
+                    };
+                    let (res, done) = tokio::join!(scan, consume);
+                    if res.is_ok() && !scan_opts.cancel.is_cancelled() {
+                        if let Some(c) = snapshot(&done, &Default::default(), true) { let _ = tx.send(c).await; }
                     }
                     drop(tx);
-                    for h in handles { let _ = h.await; }
-                });
+                    res
+                })?;
+                if let Some(r) = reporter { r.finish(&rt); }
 
                 let _ = writer_handle.join();
-                return Ok(());
+                if let Some(a) = &aimd { print_adaptive_summary(&a.summary(), format); }
             }
-
-            return Err(anyhow!("provide a target or --targets <file>"));
         }
         #[cfg(feature = "discover")]
-        Commands::Discover { target, mut ports, mut timeout_ms, mut concurrency, mut qps, mut format, out } => {
+        Commands::Discover { targets, exclude, mut ports, mut timeout_ms, mut concurrency, mut qps, ipv4, ipv6, mut format, out, mut ptr, mut method } => {
             if let Some(cfg) = &loaded_cfg { if let Some(d) = &cfg.discover {
                 if ports.is_none() { ports = d.ports.clone(); }
                 if d.timeout_ms.is_some() { timeout_ms = d.timeout_ms.unwrap(); }
                 if d.concurrency.is_some() { concurrency = d.concurrency.unwrap(); }
                 if d.qps.is_some() { qps = d.qps.unwrap(); }
+                if let Some(p) = d.ptr { ptr = ptr || p; }
+                if let (true, Some(m)) = (method.is_empty(), &d.method) {
+                    method = m.split(',').map(|s| DiscoverMethodArg::from_str(s.trim(), true).map_err(|e| anyhow!("discover.method: {}", e))).collect::<Result<_>>()?;
+                }
                 if let Some(f) = &d.format { format = match f.as_str() { "json" => OutputFormat::Json, "jsonl" => OutputFormat::Jsonl, _ => OutputFormat::Text }; }
             }}
+            (qps, concurrency) = (profile::cap_qps(qps), profile::cap_concurrency(concurrency));
             let ports_vec = if let Some(spec) = ports { port_scan::parse_ports(&spec)? } else { vec![80,443,22] };
+            if method.is_empty() { method.push(DiscoverMethodArg::Tcp); }
+            if !toolbox_core::connect::global().is_direct() && method.iter().any(|m| !matches!(m, DiscoverMethodArg::Tcp)) {
+                eprintln!("warning: icmp and arp probes do not go through the proxy");
+            }
+            let methods_for_display = method.iter().map(|m| format!("{:?}", m).to_lowercase()).collect::<Vec<_>>().join(",");
             let rt = tokio::runtime::Runtime::new()?;
             let started = Instant::now();
-            // Expand target into IPs
-            let ips = if target.contains('/') {
-                host_discovery::expand_cidr(&target)?
-            } else {
-                let ip = host_discovery::resolve_host_best_effort(&target);
-                if ip.is_unspecified() { return Err(anyhow!("failed to resolve target: {}", target)); }
-                vec![ip]
-            };
+            let specs = toolbox_core::TargetSet::parse(&targets)?;
+            let exclude = toolbox_core::Exclusions::parse(&exclude)?;
+            let target = targets.join(" ");
             let ports_for_display = ports_vec.clone();
-            let live = rt.block_on(async move {
-                let q = if qps == 0 { None } else { Some(qps) };
-                host_discovery::discover_hosts(ips, &ports_vec, std::time::Duration::from_millis(timeout_ms), concurrency, q).await
-            });
+            let opts = host_discovery::DiscoverOptions {
+                ports: ports_vec,
+                timeout: std::time::Duration::from_millis(timeout_ms),
+                concurrency,
+                qps: if qps == 0 { None } else { Some(qps) },
+                family: toolbox_core::Family::from_flags(ipv4, ipv6),
+                methods: method.into_iter().map(Into::into).collect(),
+                exclude,
+                cancel: cancel.clone(),
+                progress: Default::default(),
+            };
+            rt.spawn(interrupt_on_signal(cancel.clone()));
+            let dns = toolbox_core::dns::global();
+            // JSONL is written as hosts are found; text and JSON need the full list.
+            let mut stream: Option<Box<dyn Write>> = match (format, &out) {
+                (OutputFormat::Jsonl, Some(path)) => Some(Box::new(BufWriter::new(OpenOptions::new().create(true).truncate(true).write(true).open(path)?))),
+                (OutputFormat::Jsonl, None) => Some(Box::new(std::io::stdout())),
+                _ => None,
+            };
+            if let (Some(w), Some(ev)) = (stream.as_mut(), profile::event()) { writeln!(w, "{}", ev)?; }
+            if let Ok(index) = specs.index() { opts.progress.set_total(index.len()); }
+            let reporter = ProgressReporter::start(&rt, cli.progress, cli.progress_interval, "discover", None, opts.progress.clone());
+            let live = rt.block_on(async {
+                let (tx, mut rx) = tokio::sync::mpsc::channel::<host_discovery::Live>(1024);
+                let run = host_discovery::discover(specs.expand(), &opts, tx);
+                let collect = async {
+                    let mut live = Vec::new();
+                    while let Some(l) = rx.recv().await {
+                        match stream.as_mut() {
+                            Some(w) => {
+                                let p = if ptr { dns.reverse(l.addr).await } else { None };
+                                writeln!(w, "{}", discover_line(&l, dns.hostname(&l.addr), p.as_ref()))?;
+                                w.flush()?;
+                            }
+                            None => live.push(l),
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(live)
+                };
+                let (done, live) = tokio::join!(run, collect);
+                done.and(live)
+            })?;
+            if let Some(r) = reporter { r.finish(&rt); }
+            if let (true, Some(w)) = (cancel.is_cancelled(), stream.as_mut()) {
+                writeln!(w, "{}", interrupted_event(None))?;
+                w.flush()?;
+            }
+            let ptrs = if ptr && stream.is_none() { rt.block_on(dns.reverse_all(live.iter().map(|l| l.addr))) } else { Default::default() };
             let duration_ms = started.elapsed().as_millis();
 
             match format {
                 OutputFormat::Text => {
                     println!("live hosts ({}):", live.len());
-                    for ip in &live { println!("{}", ip); }
-                    println!("(probed on ports {}, took {} ms)", ports_for_display.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(","), duration_ms);
+                    for l in &live {
+                        let mut line = l.addr.to_string();
+                        if let Some(name) = dns.hostname(&l.addr) { line = format!("{} ({})", line, name); }
+                        if let Some(p) = ptrs.get(&l.addr) { line = format!("{} {}", line, ptr_note(p)); }
+                        line = match l.port {
+                            Some(port) => format!("{} via {} ({} on {})", line, l.probe.as_str(), l.reason.as_str(), port),
+                            None => format!("{} via {} ({})", line, l.probe.as_str(), l.reason.as_str()),
+                        };
+                        if let Some(mac) = l.mac {
+                            match mac.vendor() {
+                                Some(v) => line = format!("{} [mac {} {}]", line, mac, v),
+                                None => line = format!("{} [mac {}]", line, mac),
+                            }
+                        }
+                        println!("{}", line);
+                    }
+                    println!("(probed with {} on ports {}, took {} ms)", methods_for_display, ports_for_display.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(","), duration_ms);
                 }
                 OutputFormat::Json => {
-                    let obj = serde_json::json!({
+                    let mut obj = serde_json::json!({
                         "target": target,
-                        "live": live,
+                        "live": live.iter().map(|l| l.addr).collect::<Vec<_>>(),
+                        "method": live.iter().map(|l| (l.addr.to_string(), l.probe.as_str())).collect::<std::collections::BTreeMap<_, _>>(),
+                        "reason": live.iter().map(|l| (l.addr.to_string(), l.reason.as_str())).collect::<std::collections::BTreeMap<_, _>>(),
+                        "port": live.iter().filter_map(|l| l.port.map(|p| (l.addr.to_string(), p))).collect::<std::collections::BTreeMap<_, _>>(),
+                        "hostnames": live.iter().filter_map(|l| dns.hostname(&l.addr).map(|n| (l.addr.to_string(), n))).collect::<std::collections::BTreeMap<_, _>>(),
                         "ports": ports_for_display,
                         "duration_ms": duration_ms,
                     });
+                    if live.iter().any(|l| l.mac.is_some()) {
+                        obj["mac"] = live.iter().filter_map(|l| l.mac.map(|m| (l.addr.to_string(), serde_json::json!({"mac": m.to_string(), "vendor": m.vendor()})))).collect::<serde_json::Map<_, _>>().into();
+                    }
+                    if let Some(p) = profile::active() { obj["profile"] = p.to_json(); }
+                    if cancel.is_cancelled() { obj["status"] = serde_json::json!("interrupted"); }
+                    if ptr {
+                        obj["ptr"] = ptrs.iter().map(|(ip, p)| (ip.to_string(), serde_json::json!({"name": p.name, "confirmed": p.confirmed}))).collect::<serde_json::Map<_, _>>().into();
+                    }
                     if let Some(path) = out {
                         let file = OpenOptions::new().create(true).truncate(true).write(true).open(&path)?;
                         let mut w = BufWriter::new(file);
@@ -1008,18 +2351,10 @@ fn main() -> Result<()> {
                         println!("{}", serde_json::to_string(&obj)?);
                     }
                 }
-                OutputFormat::Jsonl => {
-                    if let Some(path) = out {
-                        let file = OpenOptions::new().create(true).truncate(true).write(true).open(&path)?;
-                        let mut w = BufWriter::new(file);
-                        for ip in &live { writeln!(w, "{}", serde_json::json!({"host": ip}).to_string())?; }
-                    } else {
-                        for ip in &live { println!("{}", serde_json::json!({"host": ip})); }
-                    }
-                }
+                OutputFormat::Jsonl => {}
             }
         }
-        Commands::Bench { compose_up, qps, out, store } => {
+        Commands::Bench { compose_up, qps, out, store, scale } => {
             let out_dir = std::path::PathBuf::from(&out);
             std::fs::create_dir_all(&out_dir).ok();
             if compose_up {
@@ -1071,6 +2406,31 @@ fn main() -> Result<()> {
                 "wall_ms": wall_scan,
             }).to_string());
 
+            // Scan-scale phase: scanner peak RSS versus target count (one closed loopback port per
+            // host, unpaced) to check memory stays flat as the target set grows
+            let sizes: Vec<u32> = scale.split(',').filter_map(|s| s.trim().parse().ok()).filter(|n| *n > 0).collect();
+            if !sizes.is_empty() {
+                sh("cargo", &["build","-q","-p","toolbox","--features","scan"])?;
+                let bin = std::path::PathBuf::from(std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".into())).join("debug").join("toolbox");
+                for n in sizes {
+                    let prefix = 32 - n.min(1 << 24).next_power_of_two().trailing_zeros();
+                    let cidr = format!("127.0.0.0/{}", prefix);
+                    let scale_jsonl = out_dir.join(format!("scan-scale-{}.jsonl", n));
+                    let t = Instant::now();
+                    let peak = sh_peak_rss_mb(bin.to_str().unwrap(), &["scan", &cidr, "--ports","1", "--qps","0", "--format","jsonl", "--out", scale_jsonl.to_str().unwrap()])?;
+                    println!("{}", serde_json::json!({
+                        "type":"bench.result",
+                        "phase":"scan-scale",
+                        "targets": n,
+                        "cidr": cidr,
+                        "ok_rows": count_lines(&scale_jsonl)?,
+                        "cpu_pct": serde_json::Value::Null,
+                        "rss_mb": peak,
+                        "wall_ms": t.elapsed().as_millis(),
+                    }));
+                }
+            }
+
             if store {
                 let db = out_dir.join("results.db");
                 sh("cargo", &["run","-q","-p","toolbox","--features","results","--","results","import","--db", db.to_str().unwrap(), "--from", web_jsonl.to_str().unwrap()])?;
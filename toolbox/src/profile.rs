@@ -0,0 +1,221 @@
+//! Named policy profiles (`profiles:` in `toolbox.yaml`), selected with `profile:` or `--profile`.
+//!
+//! A profile caps QPS and concurrency, limits which modules, subcommands and ports may be used and
+//! restricts runs to local time-of-day windows. Ports are enforced through the engagement scope;
+//! everything else is applied here before a command starts.
+
+use crate::config::{Config, ProfileConfig};
+use anyhow::{anyhow, bail, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DAY: u32 = 24 * 3600;
+
+pub struct Profile {
+    pub name: String,
+    pub rules: ProfileConfig,
+    /// Windows as (start, end) seconds after local midnight; end before start wraps past midnight.
+    windows: Vec<(u32, u32)>,
+}
+
+static ACTIVE: OnceLock<Profile> = OnceLock::new();
+static WINDOW_CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Activate the profile named by `flag`, or by the config's `profile:`.
+pub fn setup(flag: Option<&str>, cfg: Option<&Config>) -> Result<Option<&'static Profile>> {
+    let Some(name) = flag.map(str::to_string).or_else(|| cfg.and_then(|c| c.profile.clone())) else { return Ok(None) };
+    let rules = cfg.and_then(|c| c.profiles.as_ref()).and_then(|p| p.get(&name)).cloned().ok_or_else(|| anyhow!("unknown profile: {}", name))?;
+    let _ = ACTIVE.set(Profile::new(name, rules)?);
+    Ok(ACTIVE.get())
+}
+
+/// The active profile, if any.
+pub fn active() -> Option<&'static Profile> {
+    ACTIVE.get()
+}
+
+/// Record that the run is being stopped because the active profile's window closed.
+pub fn close_window() {
+    WINDOW_CLOSED.store(true, Ordering::Release);
+}
+
+/// The run was stopped because the active profile's window closed.
+pub fn window_closed() -> bool {
+    WINDOW_CLOSED.load(Ordering::Acquire)
+}
+
+/// `qps` capped by the active profile (see [`Profile::cap_qps`]).
+#[cfg(any(feature = "scan", feature = "discover"))]
+pub fn cap_qps(qps: u32) -> u32 {
+    active().map_or(qps, |p| p.cap_qps(qps))
+}
+
+/// `n` in-flight probes capped by the active profile.
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+pub fn cap_concurrency(n: usize) -> usize {
+    match active().and_then(|p| p.rules.max_concurrency) {
+        Some(max) => n.min(max.max(1)),
+        None => n,
+    }
+}
+
+/// `run.profile` line opening line-oriented output of a run under a profile.
+#[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+pub fn event() -> Option<serde_json::Value> {
+    active().map(|p| serde_json::json!({ "type": "run.profile", "profile": p.to_json() }))
+}
+
+impl Profile {
+    fn new(name: String, rules: ProfileConfig) -> Result<Self> {
+        if rules.max_qps == Some(0) { bail!("profile {}: max_qps must be > 0", name); }
+        let windows = rules.hours.iter().flatten().map(|w| parse_window(w)).collect::<Result<_>>()?;
+        Ok(Profile { name, rules, windows })
+    }
+
+    /// `qps` capped by `max_qps` (0, unlimited, becomes the cap).
+    #[cfg(any(test, feature = "scan", feature = "discover"))]
+    pub fn cap_qps(&self, qps: u32) -> u32 {
+        match self.rules.max_qps {
+            Some(max) if qps == 0 || qps > max => max,
+            _ => qps,
+        }
+    }
+
+    /// Refuse `command`, which runs `modules`, unless the profile lists them (or lists none).
+    pub fn check_command(&self, command: &str, modules: &[&str]) -> Result<()> {
+        if let Some(allowed) = &self.rules.commands {
+            if !allowed.iter().any(|c| c == command) { bail!("profile {} does not allow the {} command", self.name, command); }
+        }
+        if let Some(allowed) = &self.rules.modules {
+            if let Some(m) = modules.iter().find(|m| !allowed.iter().any(|a| a == *m)) { bail!("profile {} does not allow the {} module", self.name, m); }
+        }
+        Ok(())
+    }
+
+    /// Time left in the current window; an error outside every window, `None` without windows.
+    pub fn window_left(&self) -> Result<Option<Duration>> {
+        self.window_left_at(local_seconds())
+    }
+
+    /// [`Profile::window_left`] at `now` seconds after local midnight.
+    fn window_left_at(&self, now: u32) -> Result<Option<Duration>> {
+        if self.windows.is_empty() { return Ok(None); }
+        let left = self.windows.iter().filter_map(|&(start, end)| {
+            if start == end { return Some(DAY); }
+            let (since, len) = ((now + DAY - start) % DAY, (end + DAY - start) % DAY);
+            (since < len).then(|| len - since)
+        }).max();
+        match left {
+            Some(s) => Ok(Some(Duration::from_secs(s as u64))),
+            None => bail!("profile {} only allows runs during {}", self.name, self.rules.hours.iter().flatten().cloned().collect::<Vec<_>>().join(", ")),
+        }
+    }
+
+    /// Name and rules, as recorded with results.
+    #[cfg(any(feature = "scan", feature = "discover", feature = "webscan"))]
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut rules = serde_json::to_value(&self.rules).unwrap_or_default();
+        if let Some(o) = rules.as_object_mut() { o.retain(|_, v| !v.is_null()); }
+        serde_json::json!({ "name": self.name, "rules": rules })
+    }
+}
+
+/// `HH:MM-HH:MM` in local time.
+fn parse_window(w: &str) -> Result<(u32, u32)> {
+    let bad = || anyhow!("invalid time window: {} (expected HH:MM-HH:MM)", w);
+    let clock = |s: &str| -> Option<u32> {
+        let (h, m) = s.trim().split_once(':')?;
+        let (h, m) = (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?);
+        (h < 24 && m < 60).then_some(h * 3600 + m * 60)
+    };
+    let (a, b) = w.split_once('-').ok_or_else(bad)?;
+    Ok((clock(a).ok_or_else(bad)?, clock(b).ok_or_else(bad)?))
+}
+
+/// Seconds since local midnight.
+#[cfg(unix)]
+fn local_seconds() -> u32 {
+    // SAFETY: localtime_r writes only to the tm we pass; all-zero is a valid tm.
+    let tm = unsafe {
+        let t = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&t, &mut tm);
+        tm
+    };
+    (tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec) as u32
+}
+
+/// Seconds since midnight UTC (local time is not looked up off Unix).
+#[cfg(not(unix))]
+fn local_seconds() -> u32 {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    (now.as_secs() % DAY as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(rules: ProfileConfig) -> Profile {
+        Profile::new("lab".into(), rules).unwrap()
+    }
+
+    fn hours(windows: &[&str]) -> Profile {
+        profile(ProfileConfig { hours: Some(windows.iter().map(|w| w.to_string()).collect()), ..Default::default() })
+    }
+
+    const fn at(h: u32, m: u32) -> u32 {
+        h * 3600 + m * 60
+    }
+
+    #[test]
+    fn parses_windows() {
+        assert_eq!(parse_window("09:00-17:30").unwrap(), (at(9, 0), at(17, 30)));
+        assert_eq!(parse_window(" 22:00 - 02:00 ").unwrap(), (at(22, 0), at(2, 0)));
+        assert_eq!(parse_window("00:00-23:59").unwrap(), (0, at(23, 59)));
+        for bad in ["24:00-01:00", "09:00-24:00", "09:60-10:00", "0900-1000", "09:00", "9-10", "a:b-c:d", ""] {
+            assert!(parse_window(bad).is_err(), "{}", bad);
+        }
+        assert!(Profile::new("lab".into(), ProfileConfig { hours: Some(vec!["25:00-26:00".into()]), ..Default::default() }).is_err());
+        assert!(Profile::new("lab".into(), ProfileConfig { max_qps: Some(0), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn time_left_in_windows() {
+        assert_eq!(profile(ProfileConfig::default()).window_left_at(at(3, 0)).unwrap(), None);
+        let day = hours(&["09:00-17:30"]);
+        assert_eq!(day.window_left_at(at(9, 0)).unwrap(), Some(Duration::from_secs(at(8, 30) as u64)));
+        assert_eq!(day.window_left_at(at(17, 0)).unwrap(), Some(Duration::from_secs(at(0, 30) as u64)));
+        assert!(day.window_left_at(at(17, 30)).is_err() && day.window_left_at(at(8, 59)).is_err());
+
+        // Wraps past midnight.
+        let night = hours(&["22:00-02:00"]);
+        assert_eq!(night.window_left_at(at(23, 0)).unwrap(), Some(Duration::from_secs(at(3, 0) as u64)));
+        assert_eq!(night.window_left_at(at(1, 0)).unwrap(), Some(Duration::from_secs(at(1, 0) as u64)));
+        let err = night.window_left_at(at(12, 0)).unwrap_err().to_string();
+        assert!(err.contains("22:00-02:00"), "{}", err);
+
+        // Start equal to end is the whole day; overlapping windows give the longest time left.
+        assert_eq!(hours(&["08:00-08:00"]).window_left_at(at(20, 0)).unwrap(), Some(Duration::from_secs(DAY as u64)));
+        assert_eq!(hours(&["09:00-17:30", "16:00-18:00"]).window_left_at(at(17, 0)).unwrap(), Some(Duration::from_secs(at(1, 0) as u64)));
+    }
+
+    #[test]
+    fn checks_commands_and_modules() {
+        let p = profile(ProfileConfig { commands: Some(vec!["scan".into(), "discover".into()]), modules: Some(vec!["port-scan".into()]), ..Default::default() });
+        assert!(p.check_command("scan", &["port-scan"]).is_ok());
+        let err = p.check_command("banner", &["port-scan"]).unwrap_err().to_string();
+        assert!(err.contains("the banner command"), "{}", err);
+        let err = p.check_command("discover", &["host-discovery"]).unwrap_err().to_string();
+        assert!(err.contains("the host-discovery module"), "{}", err);
+        assert!(profile(ProfileConfig::default()).check_command("banner", &["banners"]).is_ok());
+    }
+
+    #[test]
+    fn caps_qps() {
+        let p = profile(ProfileConfig { max_qps: Some(200), ..Default::default() });
+        assert_eq!((p.cap_qps(0), p.cap_qps(50), p.cap_qps(200), p.cap_qps(5000)), (200, 50, 200, 200));
+        assert_eq!(profile(ProfileConfig::default()).cap_qps(0), 0);
+    }
+}